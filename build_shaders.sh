@@ -1,5 +1,12 @@
 #!/bin/sh
 
-for file in $(find . -type f -name "*.glsl"); do
+for file in $(find . -type f -name "*.glsl" ! -name "*.comp.glsl"); do
     glslangValidator -V ${file} -o ${file/glsl/spv};
-done
\ No newline at end of file
+done
+
+# the compute shaders are built once for every workgroup size GpuForcelayout can pick
+for file in $(find . -type f -name "*.comp.glsl"); do
+    for size in 64 128 256; do
+        glslangValidator -V -DWORKGROUP_SIZE=${size} ${file} -o ${file/.comp.glsl/_${size}.comp.spv};
+    done
+done
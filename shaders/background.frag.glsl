@@ -2,8 +2,8 @@
 
 layout(location = 0) in vec2 v_position;
 layout(location = 1) flat in vec2 v_resolution;
-layout(location = 2) flat in vec2 v_scroll_offset;
-layout(location = 3) flat in float v_zoom;
+layout(location = 2) flat in vec2 v_camera_center;
+layout(location = 3) flat in float v_camera_scale;
 
 layout(location = 0) out vec4 out_color;
 
@@ -22,19 +22,20 @@ void main() {
 
     // TODO: properly adapt the grid while zooming in and out.
     float grid_scale = 5.0;
-    if (v_zoom < 2.5) {
+    if (v_camera_scale < 2.5) {
         grid_scale = 1.0;
     }
 
-    vec2 pos = px_position + v_scroll_offset * v_zoom;
+    // the grid is laid out in simulation units, so it moves and scales with the bubbles
+    vec2 pos = px_position + v_camera_center * v_camera_scale;
 
-    if (mod(pos.x, 20.0 / grid_scale * v_zoom) <= 1.0 ||
-        mod(pos.y, 20.0 / grid_scale * v_zoom) <= 1.0) {
+    if (mod(pos.x, 20.0 / grid_scale * v_camera_scale) <= 1.0 ||
+        mod(pos.y, 20.0 / grid_scale * v_camera_scale) <= 1.0) {
         out_color *= 1.2;
     }
 
-    if (mod(pos.x, 100.0 / grid_scale * v_zoom) <= 2.0 ||
-        mod(pos.y, 100.0 / grid_scale * v_zoom) <= 2.0) {
+    if (mod(pos.x, 100.0 / grid_scale * v_camera_scale) <= 2.0 ||
+        mod(pos.y, 100.0 / grid_scale * v_camera_scale) <= 2.0) {
         out_color *= 1.2;
     }
 }
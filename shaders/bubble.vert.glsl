@@ -0,0 +1,78 @@
+#version 450
+
+layout(std140, binding = 0)
+uniform Globals {
+    vec2 u_resolution;
+    vec2 u_camera_center;
+    float u_camera_scale;
+    uint u_bubble_count;
+};
+
+struct Bubble {
+    float m;
+    uint flags;
+    vec2 p;
+    vec2 v;
+    vec2 a;
+};
+
+struct InstanceStyle {
+    vec4 color;
+    float width;
+    float _pad1;
+    float _pad2;
+    float _pad3;
+};
+
+// the same buffer GpuForcelayout simulates in
+layout(std140, binding = 1) readonly buffer B1 { Bubble bubbles[]; };
+// one style per instance, the bubble meshes first (mesh by mesh), then the edges
+layout(std430, binding = 2) readonly buffer B2 { InstanceStyle styles[]; };
+
+layout(location = 0) in vec2 a_position;
+layout(location = 1) in vec2 a_normal;
+
+layout(location = 0) out vec4 v_color;
+
+// `size * 0.9 * VIEW_SCALE_FACTOR` and `size * 0.95 * VIEW_SCALE_FACTOR`, see `Bubble::radius()`
+const float FILL_SCALE = 0.09;
+const float STROKE_SCALE = 0.095;
+
+void main() {
+    // every bubble mesh is drawn with bubble_count instances
+    uint mesh_index = uint(gl_InstanceIndex) / u_bubble_count;
+    uint bubble_index = uint(gl_InstanceIndex) % u_bubble_count;
+    Bubble bubble = bubbles[bubble_index];
+    InstanceStyle style = styles[gl_InstanceIndex];
+
+    float angle = 0.0;
+    float scale = bubble.m * FILL_SCALE;
+    vec4 color = style.color;
+    if (mesh_index == 0) {
+        // the fill turns red when the bubble is pushed hard
+        color.r += length(bubble.a) * 5.0;
+    } else if (mesh_index == 1) {
+        scale = bubble.m * STROKE_SCALE;
+    } else {
+        // the velocity arrow
+        angle = atan(bubble.v.y, bubble.v.x);
+        scale = log(length(bubble.v) + 1.0) / log(10.0) * 30.0;
+    }
+
+    vec2 invert_y = vec2(1.0, -1.0);
+
+    mat2 rotation = mat2(
+        cos(angle), -sin(angle),
+        sin(angle), cos(angle)
+    );
+
+    // the meshes are sized in simulation units, so the camera scales them with everything else
+    vec2 local_pos = (a_position * scale + a_normal * style.width) * rotation;
+    vec2 world_pos = local_pos + bubble.p;
+    vec2 screen_pos = (world_pos - u_camera_center) * u_camera_scale;
+    vec2 transformed_pos = screen_pos / (vec2(0.5, 0.5) * u_resolution) * invert_y;
+
+    float z = 10.0 / 4096.0;
+    gl_Position = vec4(transformed_pos, z / 1000.0, 1.0);
+    v_color = color;
+}
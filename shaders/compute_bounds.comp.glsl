@@ -0,0 +1,60 @@
+#version 450
+// build_shaders.sh compiles a variant for each workgroup size GpuForcelayout can pick
+#ifndef WORKGROUP_SIZE
+#define WORKGROUP_SIZE 64
+#endif
+// it's dispatched as a single workgroup, every invocation walks a strided share of the bubbles
+layout(local_size_x = WORKGROUP_SIZE) in;
+
+struct Bubble {
+    float m;
+    uint flags;
+    vec2 p;
+    vec2 v;
+    vec2 a;
+};
+
+layout(std140, binding = 0) buffer B1 {
+    Bubble[] input_bubbles;
+};
+
+layout(std140, binding = 1) buffer B2 {
+    uint bubble_count;
+    uint edge_count;
+    float collision_padding;
+    uint _pad2;
+};
+
+// the rect around all the bubbles, the vertex shaders fit it into the window
+layout(std430, binding = 4) buffer B5 {
+    vec2 bounds_min;
+    vec2 bounds_max;
+};
+
+shared vec4 partial_bounds[WORKGROUP_SIZE]; // xy: min, zw: max
+
+void main() {
+    uint local_index = gl_LocalInvocationID.x;
+    vec4 b = vec4(3.4e38, 3.4e38, -3.4e38, -3.4e38);
+    for (uint i = local_index; i < bubble_count; i += uint(WORKGROUP_SIZE)) {
+        vec2 p = input_bubbles[i].p;
+        b = vec4(min(b.xy, p), max(b.zw, p));
+    }
+    partial_bounds[local_index] = b;
+    barrier();
+
+    // halve the number of partial results until only one is left
+    for (uint stride = uint(WORKGROUP_SIZE) / 2; stride > 0; stride = stride / 2) {
+        if (local_index < stride) {
+            vec4 other = partial_bounds[local_index + stride];
+            vec4 mine = partial_bounds[local_index];
+            partial_bounds[local_index] = vec4(min(mine.xy, other.xy), max(mine.zw, other.zw));
+        }
+        barrier();
+    }
+
+    if (local_index == 0) {
+        bounds_min = partial_bounds[0].xy;
+        bounds_max = partial_bounds[0].zw;
+    }
+}
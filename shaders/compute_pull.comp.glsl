@@ -1,74 +1,78 @@
-#version 450
-layout(local_size_x = 1) in; // our thread block size is 1, that is we only have 1 thread per block
-
-struct Bubble {
-    float m;
-    float _pad1;
-    vec2 p;
-    vec2 v;
-    vec2 a;
-};
-
-struct Edge {
-    int from;
-    int to;
-    int _pad1;
-    int _pad2;
-};
-
-// make sure to use only a single set and keep all your n parameters in n storage buffers in bindings 0 to n-1
-// you shouldn't use push constants or anything OTHER than storage buffers for passing stuff into the kernel
-// just use buffers with one buffer per binding
-layout(std140, binding = 0) buffer B1 {
-    Bubble input_bubbles[];
-}; // this is used as both input and output for convenience
-
-layout(std140, binding = 1) buffer B2 {
-    uint bubble_count;
-    uint edge_count;
-    uint _pad1;
-    uint _pad2;
-}; // this is used as both input and output for convenience
-
-layout(std140, binding = 2) buffer B3 {
-    Edge[] edges;
-};
-
-vec2 calculate_bubble_repulsion(Bubble bubble_from, Bubble bubble_to) {
-    vec2 d_from_to = bubble_to.p - bubble_from.p;
-    float pull_force_factor = 1.0;
-    vec2 pull_force_from_to = d_from_to * pull_force_factor;
-
-    vec2 a_from = pull_force_from_to / bubble_from.m;
-    return a_from;
-}
-
-int compute_repulsion(uint bubble_index) {
-    for (int i = 0; i < edge_count; i++) {
-        Edge edge = edges[i];
-        int i_from = edge.from;
-        int i_to = edge.to;
-        int connected_index = -1;
-        if (i_from == bubble_index) {
-            connected_index = i_to;
-        }
-        if (i_to == bubble_index) {
-            connected_index = i_from;
-        }
-        if (connected_index != -1) {
-            Bubble bubble = input_bubbles[bubble_index];
-            Bubble connected_bubble = input_bubbles[connected_index];
-            bubble.a = bubble.a + calculate_bubble_repulsion(bubble, connected_bubble);
-            input_bubbles[bubble_index] = bubble;
-        }
-    }
-
-    return 0;
-}
-
-// there should be only one entry point and it should be named "main"
-// ultimately, Emu has to kind of restrict how you use GLSL because it is compute focused
-void main() {
-    uint index = gl_GlobalInvocationID.x; // this gives us the index in the x dimension of the thread space
-    compute_repulsion(index);
+#version 450
+// build_shaders.sh compiles a variant for each workgroup size GpuForcelayout can pick
+#ifndef WORKGROUP_SIZE
+#define WORKGROUP_SIZE 64
+#endif
+layout(local_size_x = WORKGROUP_SIZE) in;
+
+struct Bubble {
+    float m;
+    uint flags;
+    vec2 p;
+    vec2 v;
+    vec2 a;
+};
+
+struct Neighbour {
+    uint index;
+    float weight;
+};
+
+// make sure to use only a single set and keep all your n parameters in n storage buffers in bindings 0 to n-1
+// you shouldn't use push constants or anything OTHER than storage buffers for passing stuff into the kernel
+// just use buffers with one buffer per binding
+layout(std140, binding = 0) buffer B1 {
+    Bubble input_bubbles[];
+}; // this is used as both input and output for convenience
+
+layout(std140, binding = 1) buffer B2 {
+    uint bubble_count;
+    uint edge_count;
+    float collision_padding;
+    uint _pad2;
+}; // this is used as both input and output for convenience
+
+// the neighbours of bubble i are neighbours[adjacency_offsets[i]..adjacency_offsets[i + 1]]
+layout(std430, binding = 2) buffer B3 {
+    uint adjacency_offsets[];
+};
+
+layout(std430, binding = 3) buffer B4 {
+    Neighbour neighbours[];
+};
+
+vec2 calculate_bubble_pull(Bubble bubble_from, Bubble bubble_to) {
+    vec2 d_from_to = bubble_to.p - bubble_from.p;
+    float pull_force_factor = 1.0;
+    vec2 pull_force_from_to = d_from_to * pull_force_factor;
+
+    vec2 a_from = pull_force_from_to / bubble_from.m;
+    return a_from;
+}
+
+int compute_pull(uint bubble_index) {
+    Bubble bubble = input_bubbles[bubble_index];
+    vec2 a = bubble.a;
+    uint start = adjacency_offsets[bubble_index];
+    uint end = adjacency_offsets[bubble_index + 1];
+    for (uint i = start; i < end; i++) {
+        Neighbour neighbour = neighbours[i];
+        Bubble connected_bubble = input_bubbles[neighbour.index];
+        a = a + calculate_bubble_pull(bubble, connected_bubble) * neighbour.weight;
+    }
+    // only the acceleration changes here, the neighbours are reading the positions at the same time
+    input_bubbles[bubble_index].a = a;
+
+    return 0;
+}
+
+// there should be only one entry point and it should be named "main"
+// ultimately, Emu has to kind of restrict how you use GLSL because it is compute focused
+void main() {
+    uint index = gl_GlobalInvocationID.x; // this gives us the index in the x dimension of the thread space
+    // the last workgroup runs past the end of the bubbles
+    if (index >= bubble_count) {
+        return;
+    }
+    compute_pull(index);
 }
\ No newline at end of file
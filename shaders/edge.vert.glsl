@@ -0,0 +1,72 @@
+#version 450
+
+layout(std140, binding = 0)
+uniform Globals {
+    vec2 u_resolution;
+    vec2 u_camera_center;
+    float u_camera_scale;
+    uint u_bubble_count;
+};
+
+struct Bubble {
+    float m;
+    uint flags;
+    vec2 p;
+    vec2 v;
+    vec2 a;
+};
+
+struct InstanceStyle {
+    vec4 color;
+    float width;
+    float _pad1;
+    float _pad2;
+    float _pad3;
+};
+
+struct Edge {
+    uint from;
+    uint to;
+    float weight;
+    float _pad1;
+};
+
+layout(std140, binding = 1) readonly buffer B1 { Bubble bubbles[]; };
+layout(std430, binding = 2) readonly buffer B2 { InstanceStyle styles[]; };
+layout(std430, binding = 3) readonly buffer B3 { Edge edges[]; };
+
+layout(location = 0) in vec2 a_position;
+layout(location = 1) in vec2 a_normal;
+
+layout(location = 0) out vec4 v_color;
+
+// the styles of the three bubble meshes come before the edge styles
+const uint BUBBLE_MESH_COUNT = 3;
+
+void main() {
+    Edge edge = edges[gl_InstanceIndex];
+    InstanceStyle style = styles[BUBBLE_MESH_COUNT * u_bubble_count + uint(gl_InstanceIndex)];
+
+    // the edge mesh is a line from (0, 0) to (1, 0), it's stretched from one bubble to the other
+    vec2 position_from = bubbles[edge.from].p;
+    vec2 position_to = bubbles[edge.to].p;
+    vec2 d = position_to - position_from;
+    float angle = atan(d.y, d.x);
+    float scale = length(d);
+
+    vec2 invert_y = vec2(1.0, -1.0);
+
+    mat2 rotation = mat2(
+        cos(angle), -sin(angle),
+        sin(angle), cos(angle)
+    );
+
+    vec2 local_pos = (a_position * scale + a_normal * style.width) * rotation;
+    vec2 world_pos = local_pos + position_from;
+    vec2 screen_pos = (world_pos - u_camera_center) * u_camera_scale;
+    vec2 transformed_pos = screen_pos / (vec2(0.5, 0.5) * u_resolution) * invert_y;
+
+    float z = 10.0 / 4096.0;
+    gl_Position = vec4(transformed_pos, z / 1000.0, 1.0);
+    v_color = style.color;
+}
@@ -0,0 +1,24 @@
+#version 450
+
+layout(std140, binding = 0)
+uniform Globals {
+    vec2 u_resolution;
+    vec2 u_camera_center;
+    float u_camera_scale;
+    uint u_bubble_count;
+};
+
+// in window pixels, y down, like the cursor
+layout(location = 0) in vec2 a_position;
+layout(location = 1) in vec4 a_color;
+
+layout(location = 0) out vec4 v_color;
+
+void main() {
+    vec2 invert_y = vec2(1.0, -1.0);
+    vec2 transformed_pos = (a_position / u_resolution * 2.0 - 1.0) * invert_y;
+
+    // in front of the bubbles and the edges
+    gl_Position = vec4(transformed_pos, 0.5, 1.0);
+    v_color = a_color;
+}
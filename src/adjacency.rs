@@ -0,0 +1,88 @@
+/// The edges in compressed sparse row form.
+/// The neighbours of bubble `i` are `neighbours[offsets[i]..offsets[i + 1]]` with the matching `weights`
+/// and the index of the edge leading to each, every edge is listed under both of its ends.
+pub struct Adjacency {
+    pub offsets: Vec<u32>,
+    pub neighbours: Vec<u32>,
+    pub weights: Vec<f32>,
+    pub edges: Vec<u32>,
+}
+
+impl Adjacency {
+    /// Builds the rows from `(from, to, weight)` triples with a counting pass and a filling pass,
+    /// so it's O(N + E) and the neighbours keep the order of the edges.
+    pub fn new<I>(bubble_count: usize, edges: I) -> Self
+    where
+        I: Iterator<Item = (usize, usize, f32)> + Clone,
+    {
+        let mut offsets = vec![0u32; bubble_count + 1];
+        for (from, to, _) in edges.clone() {
+            offsets[from + 1] += 1;
+            offsets[to + 1] += 1;
+        }
+        for i in 0..bubble_count {
+            offsets[i + 1] += offsets[i];
+        }
+
+        let entry_count = offsets[bubble_count] as usize;
+        let mut neighbours = vec![0u32; entry_count];
+        let mut weights = vec![0.0; entry_count];
+        let mut edge_indices = vec![0u32; entry_count];
+        let mut cursor: Vec<u32> = offsets[..bubble_count].to_vec();
+        for (edge, (from, to, weight)) in edges.enumerate() {
+            for &(a, b) in [(from, to), (to, from)].iter() {
+                let slot = cursor[a] as usize;
+                neighbours[slot] = b as u32;
+                weights[slot] = weight;
+                edge_indices[slot] = edge as u32;
+                cursor[a] += 1;
+            }
+        }
+
+        Adjacency {
+            offsets,
+            neighbours,
+            weights,
+            edges: edge_indices,
+        }
+    }
+
+    pub fn bubble_count(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    /// The entries of bubble `i`'s row, as `(neighbour, weight, edge)`.
+    pub fn neighbours_of(&self, i: usize) -> impl Iterator<Item = (usize, f32, usize)> + '_ {
+        let row = self.offsets[i] as usize..self.offsets[i + 1] as usize;
+        row.map(move |slot| (self.neighbours[slot] as usize, self.weights[slot], self.edges[slot] as usize))
+    }
+
+    /// How many hops each bubble is from the closest of `sources`, breadth first.
+    /// The bubbles further than `max_hops` away, or not connected at all, are `None`.
+    pub fn hop_distances(&self, sources: &[usize], max_hops: u32) -> Vec<Option<u32>> {
+        let mut distances = vec![None; self.bubble_count()];
+        let mut frontier = vec![];
+        for &i in sources {
+            if distances[i].is_none() {
+                distances[i] = Some(0);
+                frontier.push(i);
+            }
+        }
+        for hop in 1..=max_hops {
+            let mut next = vec![];
+            for &i in frontier.iter() {
+                for (j, _, _) in self.neighbours_of(i) {
+                    if distances[j].is_none() {
+                        distances[j] = Some(hop);
+                        next.push(j);
+                    }
+                }
+            }
+            if next.is_empty() {
+                break;
+            }
+            frontier = next;
+        }
+        distances
+    }
+}
@@ -0,0 +1,152 @@
+use crate::math::Vector2;
+
+// how far the camera can zoom out and in, in pixels per unit of the simulation
+pub const MIN_SCALE: f32 = 0.001;
+pub const MAX_SCALE: f32 = 100.0;
+// the room left around whatever is framed, in pixels
+const FRAME_PADDING: f32 = 100.0;
+// the share of the way to the target the camera moves every frame
+const SMOOTHING: f32 = 1.0 / 3.0;
+
+/// Looks at the simulation with the same scale on both axes.
+/// It's the one place that maps between the simulation (world) and the window (screen, in pixels, y down).
+pub struct Camera {
+    center: Vector2,
+    scale: f32,
+    target_center: Vector2,
+    target_scale: f32,
+    resolution: Vector2,
+    // keeps framing the bounds it's given, until the view is moved by hand
+    pub auto_fit: bool,
+}
+
+impl Camera {
+    pub fn new(resolution: Vector2) -> Self {
+        Camera {
+            center: Vector2::new(),
+            scale: 1.0,
+            target_center: Vector2::new(),
+            target_scale: 1.0,
+            resolution,
+            auto_fit: true,
+        }
+    }
+
+    pub fn set_resolution(&mut self, resolution: Vector2) {
+        self.resolution = resolution;
+    }
+
+    pub fn center(&self) -> Vector2 {
+        self.center
+    }
+
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    pub fn to_screen(&self, p: &Vector2) -> Vector2 {
+        p.sub(&self.center).mul_s(self.scale).add(&self.resolution.mul_s(0.5))
+    }
+
+    pub fn to_world(&self, s: &Vector2) -> Vector2 {
+        s.sub(&self.resolution.mul_s(0.5)).mul_s(1.0 / self.scale).add(&self.center)
+    }
+
+    pub fn to_screen_length(&self, length: f32) -> f32 {
+        length * self.scale
+    }
+
+    /// Moves the view by `delta` pixels, right away when it follows a drag, or smoothly.
+    pub fn pan(&mut self, delta: &Vector2, immediate: bool) {
+        self.auto_fit = false;
+        self.target_center = self.target_center.sub(&delta.mul_s(1.0 / self.target_scale));
+        if immediate {
+            self.center = self.target_center;
+        }
+    }
+
+    /// Zooms by `factor`, what's under `cursor` stays where it is.
+    pub fn zoom_at(&mut self, cursor: &Vector2, factor: f32) {
+        self.auto_fit = false;
+        let from_center = cursor.sub(&self.resolution.mul_s(0.5));
+        let anchor = self.target_center.add(&from_center.mul_s(1.0 / self.target_scale));
+        self.target_scale = (self.target_scale * factor).clamp(MIN_SCALE, MAX_SCALE);
+        self.target_center = anchor.sub(&from_center.mul_s(1.0 / self.target_scale));
+    }
+
+    /// Heads for the view that shows the world rect from `min` to `max` in the middle of the window.
+    pub fn frame(&mut self, min: &Vector2, max: &Vector2) {
+        let size = max.sub(min);
+        let target_size = self.resolution.add_s(-2.0 * FRAME_PADDING);
+        let scale_x = if size.x > 0.0 { target_size.x / size.x } else { MAX_SCALE };
+        let scale_y = if size.y > 0.0 { target_size.y / size.y } else { MAX_SCALE };
+        self.target_scale = scale_x.min(scale_y).clamp(MIN_SCALE, MAX_SCALE);
+        self.target_center = min.add(max).mul_s(0.5);
+    }
+
+    /// Moves toward the target once a frame, the target follows `bounds` while auto fit is on.
+    pub fn update(&mut self, bounds: Option<(Vector2, Vector2)>) {
+        if let (true, Some((min, max))) = (self.auto_fit, bounds) {
+            if min.x <= max.x && min.y <= max.y {
+                self.frame(&min, &max);
+            }
+        }
+        self.center = self.center.add(&self.target_center.sub(&self.center).mul_s(SMOOTHING));
+        self.scale += (self.target_scale - self.scale) * SMOOTHING;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // long enough for the smoothing to get the camera to its target
+    fn settle(camera: &mut Camera) {
+        for _ in 0..200 {
+            camera.update(None);
+        }
+    }
+
+    fn assert_near(a: &Vector2, b: &Vector2) {
+        assert!(a.sub(b).len() < 1e-3, "{:?} isn't {:?}", a, b);
+    }
+
+    #[test]
+    fn zooming_keeps_the_point_under_the_cursor() {
+        let mut camera = Camera::new(Vector2 { x: 800.0, y: 600.0 });
+        let cursor = Vector2 { x: 620.0, y: 150.0 };
+        let under_cursor = camera.to_world(&cursor);
+        camera.zoom_at(&cursor, 4.0);
+        settle(&mut camera);
+        assert!((camera.scale() - 4.0).abs() < 1e-3);
+        assert_near(&camera.to_world(&cursor), &under_cursor);
+    }
+
+    #[test]
+    fn framing_fits_the_bounds_into_the_window() {
+        let mut camera = Camera::new(Vector2 { x: 800.0, y: 600.0 });
+        let min = Vector2 { x: -1000.0, y: 200.0 };
+        let max = Vector2 { x: 3000.0, y: 1200.0 };
+        camera.frame(&min, &max);
+        settle(&mut camera);
+        // the width is what limits the scale, it fills the window up to the padding, the height fits in the middle
+        let top_left = camera.to_screen(&min);
+        let bottom_right = camera.to_screen(&max);
+        assert!((top_left.x - FRAME_PADDING).abs() < 1e-2);
+        assert!((bottom_right.x - (800.0 - FRAME_PADDING)).abs() < 1e-2);
+        assert!(top_left.y >= FRAME_PADDING && bottom_right.y <= 600.0 - FRAME_PADDING);
+        assert!((top_left.y + bottom_right.y - 600.0).abs() < 1e-2);
+    }
+
+    #[test]
+    fn screen_and_world_map_back_and_forth() {
+        let mut camera = Camera::new(Vector2 { x: 800.0, y: 600.0 });
+        camera.pan(&Vector2 { x: 35.0, y: -80.0 }, true);
+        camera.zoom_at(&Vector2 { x: 100.0, y: 500.0 }, 0.25);
+        settle(&mut camera);
+        let screen = Vector2 { x: 123.0, y: 456.0 };
+        assert_near(&camera.to_screen(&camera.to_world(&screen)), &screen);
+        let world = Vector2 { x: -2500.0, y: 720.0 };
+        assert_near(&camera.to_world(&camera.to_screen(&world)), &world);
+    }
+}
@@ -0,0 +1,338 @@
+use std::collections::BTreeMap;
+use std::f32::consts::PI;
+
+use lyon::math::point;
+
+use crate::adjacency::Adjacency;
+use crate::bubble::Bubble;
+use crate::create_dataset::GROUP;
+use crate::edge::Edge;
+use crate::math::Vector2;
+use crate::shape_builder::ShapeBuilder;
+use crate::stress::EDGE_LENGTH;
+use crate::tree::move_into_place;
+
+// the room left between two bubbles next to each other past their radii, and the extra room between two groups
+const NODE_SPACING: f32 = 0.5 * EDGE_LENGTH;
+const GROUP_SPACING: f32 = EDGE_LENGTH;
+// sweeps over the order, the one with the fewest crossings is kept
+const ORDERING_SWEEPS: usize = 24;
+// label propagation rounds, it usually settles after a handful
+const COMMUNITY_ROUNDS: usize = 100;
+// how far a chord bends from the straight line towards the centre of the circle, 1 takes it through the centre
+const CHORD_BEND: f32 = 0.5;
+// the most a flattened curve is off the real one
+const CURVE_TOLERANCE: f32 = 1.0;
+
+/// Circular layout: the bubbles go round a circle, a group after the other, and the edges are chords bent towards the centre.
+/// The groups are the dataset's, or else the communities label propagation finds, and they're ordered with the bubbles
+/// inside them to cross as few chords as the sweeps find. The pinned bubbles stay put.
+pub fn circular(bubbles: &mut [Bubble], edges: &mut [Edge]) {
+    let n = bubbles.len();
+    if n == 0 {
+        return;
+    }
+    let (order, arcs, circumference) = arrange(bubbles, edges);
+    let widths: Vec<f32> = order.iter().map(|&v| 2.0 * bubbles[v].radius()).collect();
+
+    // the circle is big enough that the chord between every two neighbours on it parts them
+    let angles: Vec<f32> = arcs.iter().map(|s| 2.0 * PI * s / circumference).collect();
+    let mut radius: f32 = 0.0;
+    if n > 1 {
+        for k in 0..n {
+            let next = (k + 1) % n;
+            let angle = if next == 0 { 2.0 * PI - angles[k] } else { angles[next] - angles[k] };
+            let separation = 0.5 * (widths[k] + widths[next]) + NODE_SPACING;
+            radius = radius.max(separation / (2.0 * (0.5 * angle.min(PI)).sin()));
+        }
+    }
+    let mut positions = vec![Vector2::new(); n];
+    for (k, &v) in order.iter().enumerate() {
+        positions[v] = Vector2 {
+            x: radius * angles[k].cos(),
+            y: radius * angles[k].sin(),
+        };
+    }
+    let center = move_into_place(bubbles, &positions);
+
+    for edge in edges.iter_mut().filter(|e| e.from != e.to) {
+        let (from, to) = (bubbles[edge.from].position, bubbles[edge.to].position);
+        let middle = from.add(&to).mul_s(0.5);
+        let control = middle.add(&center.sub(&middle).mul_s(CHORD_BEND));
+        edge.route = route(|builder| {
+            builder.begin(point(from.x, from.y));
+            builder.quadratic_bezier_to(point(control.x, control.y), point(to.x, to.y));
+            builder.end(false);
+        });
+    }
+}
+
+/// Arc diagram: the bubbles in a row, in the order the circular layout puts them round the circle,
+/// and the edges half circles on one side of it. Two arcs cross exactly when the two chords would.
+/// The pinned bubbles stay put.
+pub fn arc_diagram(bubbles: &mut [Bubble], edges: &mut [Edge]) {
+    let n = bubbles.len();
+    if n == 0 {
+        return;
+    }
+    let (order, arcs, _) = arrange(bubbles, edges);
+    let mut positions = vec![Vector2::new(); n];
+    for (k, &v) in order.iter().enumerate() {
+        positions[v] = Vector2 { x: arcs[k], y: 0.0 };
+    }
+    move_into_place(bubbles, &positions);
+
+    for edge in edges.iter_mut().filter(|e| e.from != e.to) {
+        let (from, to) = (bubbles[edge.from].position, bubbles[edge.to].position);
+        // a cubic with its controls 4/3 of the radius out is as close to a half circle as one goes
+        let height = 2.0 / 3.0 * (to.x - from.x).abs();
+        edge.route = route(|builder| {
+            builder.begin(point(from.x, from.y));
+            builder.cubic_bezier_to(point(from.x, from.y + height), point(to.x, to.y + height), point(to.x, to.y));
+            builder.end(false);
+        });
+    }
+}
+
+// the bends of a curved edge, its two ends are the bubbles' middles and aren't part of the route
+fn route(build: impl Fn(&mut lyon::path::path::Builder)) -> Vec<Vector2> {
+    let points = ShapeBuilder::flatten(CURVE_TOLERANCE, build);
+    match points.len() {
+        0..=2 => vec![],
+        len => points[1..len - 1].to_vec(),
+    }
+}
+
+// the order of the bubbles along the circle or the row, and how far along it each one is, with the whole length round the circle
+fn arrange(bubbles: &[Bubble], edges: &[Edge]) -> (Vec<usize>, Vec<f32>, f32) {
+    let n = bubbles.len();
+    let adjacency = Adjacency::new(n, edges.iter().map(|e| (e.from, e.to, e.weight)));
+    let groups = groups(bubbles, &adjacency);
+    let order = crossing_order(&adjacency, &groups);
+
+    let half_width = |v: usize| bubbles[v].radius();
+    let mut arcs = vec![0.0; n];
+    for k in 1..n {
+        let (previous, v) = (order[k - 1], order[k]);
+        arcs[k] = arcs[k - 1] + half_width(previous) + NODE_SPACING + half_width(v);
+        if groups[previous] != groups[v] {
+            arcs[k] += GROUP_SPACING;
+        }
+    }
+    let (first, last) = (order[0], order[n - 1]);
+    let mut circumference = arcs[n - 1] + half_width(last) + NODE_SPACING + half_width(first);
+    if groups[first] != groups[last] {
+        circumference += GROUP_SPACING;
+    }
+    (order, arcs, circumference)
+}
+
+// the group of every bubble, numbered from 0: the dataset's if every bubble has one, or else its community
+fn groups(bubbles: &[Bubble], adjacency: &Adjacency) -> Vec<usize> {
+    let keys: Option<Vec<f32>> = bubbles.iter().map(|b| b.attributes.get(GROUP).copied()).collect();
+    match keys {
+        Some(keys) => {
+            let mut distinct = keys.clone();
+            distinct.sort_by(|a, b| a.total_cmp(b));
+            distinct.dedup();
+            keys.iter()
+                .map(|key| distinct.binary_search_by(|d| d.total_cmp(key)).unwrap())
+                .collect()
+        }
+        None => communities(adjacency),
+    }
+}
+
+// label propagation: every bubble takes the label most of its edge weight goes to, until none changes,
+// it's run in bubble order with the ties going to the smallest label so it comes out the same every time
+fn communities(adjacency: &Adjacency) -> Vec<usize> {
+    let n = adjacency.bubble_count();
+    let mut labels: Vec<usize> = (0..n).collect();
+    for _ in 0..COMMUNITY_ROUNDS {
+        let mut changed = false;
+        for v in 0..n {
+            let mut weights: BTreeMap<usize, f32> = BTreeMap::new();
+            for (w, weight, _) in adjacency.neighbours_of(v).filter(|&(w, _, _)| w != v) {
+                *weights.entry(labels[w]).or_insert(0.0) += weight;
+            }
+            let best = weights.values().copied().fold(f32::NEG_INFINITY, f32::max);
+            if weights.get(&labels[v]).is_some_and(|&weight| weight == best) {
+                continue;
+            }
+            if let Some((&label, _)) = weights.iter().find(|&(_, &weight)| weight == best) {
+                labels[v] = label;
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+    let mut numbers: BTreeMap<usize, usize> = BTreeMap::new();
+    labels
+        .iter()
+        .map(|label| {
+            let next = numbers.len();
+            *numbers.entry(*label).or_insert(next)
+        })
+        .collect()
+}
+
+// the bubbles round the circle with every group in one piece: each sweep moves every bubble towards the
+// circular mean of its neighbours within its group, and every other sweep the groups towards theirs the same way
+fn crossing_order(adjacency: &Adjacency, groups: &[usize]) -> Vec<usize> {
+    let n = adjacency.bubble_count();
+    let group_count = groups.iter().max().map_or(0, |g| g + 1);
+    let mut members: Vec<Vec<usize>> = vec![vec![]; group_count];
+    for v in 0..n {
+        members[groups[v]].push(v);
+    }
+
+    let flatten = |members: &[Vec<usize>]| -> Vec<usize> { members.iter().flatten().copied().collect() };
+    let mut best = flatten(&members);
+    let mut best_crossings = count_crossings(adjacency, &best);
+    for sweep in 0..ORDERING_SWEEPS {
+        if best_crossings == 0 {
+            break;
+        }
+        let order = flatten(&members);
+        let mut angles = vec![0.0; n];
+        for (k, &v) in order.iter().enumerate() {
+            angles[v] = 2.0 * PI * k as f32 / n as f32;
+        }
+        // where the neighbours of a set of bubbles are round the circle, seen from `from`, in [-π, π]
+        let pull = |vs: &[usize], from: f32, outside: bool| -> Option<f32> {
+            let mut sum = Vector2::new();
+            for &v in vs {
+                for (w, weight, _) in adjacency.neighbours_of(v) {
+                    if w != v && (groups[w] != groups[v]) == outside {
+                        sum = sum.add(&Vector2 { x: angles[w].cos(), y: angles[w].sin() }.mul_s(weight));
+                    }
+                }
+            }
+            (sum.len() > 0.0).then(|| wrap(sum.y.atan2(sum.x) - from))
+        };
+
+        if sweep % 2 == 1 && group_count > 2 {
+            // the first group stays where it is, the others are put in order of where they're pulled round from it
+            let start = angles[members[0][0]];
+            let mut keys: Vec<(f32, usize)> = (1..group_count)
+                .map(|g| {
+                    let middle = angles[members[g][members[g].len() / 2]];
+                    let key = pull(&members[g], start, true).unwrap_or_else(|| wrap(middle - start));
+                    (key.rem_euclid(2.0 * PI), g)
+                })
+                .collect();
+            keys.sort_by(|a, b| a.0.total_cmp(&b.0));
+            let mut reordered = vec![members[0].clone()];
+            reordered.extend(keys.iter().map(|&(_, g)| members[g].clone()));
+            members = reordered;
+        }
+        for group in members.iter_mut() {
+            // seen from the middle of the group, so the group's two ends don't meet round the back
+            let middle = angles[group[group.len() / 2]];
+            let mut keys: Vec<(f32, usize)> = group
+                .iter()
+                .map(|&v| (pull(&[v], middle, false).unwrap_or_else(|| wrap(angles[v] - middle)), v))
+                .collect();
+            keys.sort_by(|a, b| a.0.total_cmp(&b.0));
+            *group = keys.iter().map(|&(_, v)| v).collect();
+        }
+
+        let order = flatten(&members);
+        let crossings = count_crossings(adjacency, &order);
+        if crossings < best_crossings {
+            best = order;
+            best_crossings = crossings;
+        }
+    }
+    best
+}
+
+// the angle in [-π, π]
+fn wrap(angle: f32) -> f32 {
+    (angle + PI).rem_euclid(2.0 * PI) - PI
+}
+
+// two chords cross when one end of either is strictly between the ends of the other, which for the edges in order of
+// their first end is the ones before it with their second end strictly inside it, counted with a Fenwick tree
+fn count_crossings(adjacency: &Adjacency, order: &[usize]) -> usize {
+    let n = order.len();
+    let mut positions = vec![0; n];
+    for (k, &v) in order.iter().enumerate() {
+        positions[v] = k;
+    }
+    let mut chords: Vec<(usize, usize)> = (0..n)
+        .flat_map(|v| adjacency.neighbours_of(v).map(move |(w, _, _)| (v, w)))
+        .map(|(v, w)| (positions[v], positions[w]))
+        .filter(|&(a, b)| a < b)
+        .collect();
+    chords.sort_unstable();
+    chords.dedup();
+
+    let mut tree = vec![0usize; n + 1];
+    // how many of the chords so far end at or before `position`
+    let ending_by = |tree: &[usize], position: usize| {
+        let mut count = 0;
+        let mut i = position + 1;
+        while i > 0 {
+            count += tree[i];
+            i -= i & i.wrapping_neg();
+        }
+        count
+    };
+    let mut crossings = 0;
+    let mut start = 0;
+    while start < chords.len() {
+        // the chords from the same bubble don't cross, they're all counted before any of them goes in
+        let first = chords[start].0;
+        let end = start + chords[start..].iter().take_while(|c| c.0 == first).count();
+        for &(a, b) in chords[start..end].iter() {
+            crossings += ending_by(&tree, b - 1) - ending_by(&tree, a);
+        }
+        for &(_, b) in chords[start..end].iter() {
+            let mut i = b + 1;
+            while i <= n {
+                tree[i] += 1;
+                i += i & i.wrapping_neg();
+            }
+        }
+        start = end;
+    }
+    crossings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_graph::graph;
+
+    fn adjacency(n: usize, edges: &[(usize, usize)]) -> Adjacency {
+        Adjacency::new(n, edges.iter().map(|&(from, to)| (from, to, 1.0)))
+    }
+
+    #[test]
+    fn the_diagonals_of_k4_cross_once_in_any_order() {
+        let k4 = adjacency(4, &[(0, 1), (0, 2), (0, 3), (1, 2), (1, 3), (2, 3)]);
+        assert_eq!(count_crossings(&k4, &[0, 1, 2, 3]), 1);
+        assert_eq!(count_crossings(&k4, &[2, 0, 3, 1]), 1);
+    }
+
+    #[test]
+    fn a_ring_crosses_only_out_of_order() {
+        let ring = adjacency(6, &[(0, 1), (1, 2), (2, 3), (3, 4), (4, 5), (5, 0)]);
+        assert_eq!(count_crossings(&ring, &[0, 1, 2, 3, 4, 5]), 0);
+        // 0-1 crosses 2-3 and 3-4, 1-2 crosses 3-4 and 4-5, and 2-3 crosses 4-5, the chords sharing a bubble don't cross
+        assert_eq!(count_crossings(&ring, &[0, 3, 1, 4, 2, 5]), 5);
+    }
+
+    #[test]
+    fn the_bubbles_go_round_a_circle() {
+        let (mut bubbles, mut edges) = graph(6, &[(0, 1), (1, 2), (2, 0), (3, 4), (4, 5), (5, 3), (2, 3)]);
+        circular(&mut bubbles, &mut edges);
+        let center = bubbles.iter().fold(Vector2::new(), |sum, b| sum.add(&b.position)).mul_s(1.0 / 6.0);
+        let radius = bubbles[0].position.sub(&center).len();
+        assert!(bubbles.iter().all(|b| (b.position.sub(&center).len() - radius).abs() < 1e-2 * radius));
+        assert!(edges.iter().all(|e| !e.route.is_empty()));
+    }
+}
@@ -0,0 +1,279 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::BuildHasherDefault;
+
+use super::bubble::*;
+use super::math::*;
+use super::spectral::hashed_values;
+
+// the gap we want to keep between two bubbles, in the same unit as `Bubble::radius()`
+pub const COLLISION_PADDING: f32 = 2.0;
+pub const COLLISION_FORCE_FACTOR: f32 = 10.0;
+// how many times `remove_overlaps` moves the overlapping pairs apart before it scales the layout instead
+pub const OVERLAP_REMOVAL_ITERATIONS: usize = 100;
+
+// the cells that haven't been visited yet when walking the grid cell by cell,
+// so that every pair of neighbouring cells is only checked once
+const FORWARD_NEIGHBOURS: [(i32, i32); 4] = [(1, -1), (1, 0), (1, 1), (0, 1)];
+
+/// A uniform grid over the bubble positions.
+/// The cell is as large as the biggest possible collision distance, so
+/// two bubbles can only touch if they're in the same or in adjacent cells.
+pub struct SpatialGrid {
+    cell_size: f32,
+    // hashed the same way every run, so the pairs come in the same order and the layouts come out the same
+    cells: HashMap<(i32, i32), Vec<usize>, BuildHasherDefault<DefaultHasher>>,
+}
+
+impl SpatialGrid {
+    pub fn new(bubbles: &[Bubble], padding: f32) -> Self {
+        let max_radius = bubbles.iter().map(|b| b.radius()).fold(0.0, f32::max);
+        SpatialGrid::with_cell_size(bubbles.iter().map(|b| &b.position), 2.0 * max_radius + padding)
+    }
+
+    /// A grid over `positions` with cells of `cell_size`, the points closer than that are in the same or in adjacent cells.
+    pub fn with_cell_size<'a>(positions: impl Iterator<Item = &'a Vector2>, cell_size: f32) -> Self {
+        let mut grid = SpatialGrid {
+            cell_size: cell_size.max(f32::EPSILON),
+            cells: HashMap::default(),
+        };
+        for (i, position) in positions.enumerate() {
+            let cell = grid.cell_of(position);
+            grid.cells.entry(cell).or_default().push(i);
+        }
+        grid
+    }
+
+    fn cell_of(&self, p: &Vector2) -> (i32, i32) {
+        (
+            (p.x / self.cell_size).floor() as i32,
+            (p.y / self.cell_size).floor() as i32,
+        )
+    }
+
+    /// Calls `f` once for every pair of bubbles that are close enough to possibly overlap, or to be within a cell of each other.
+    pub fn for_each_candidate_pair<F>(&self, mut f: F)
+    where
+        F: FnMut(usize, usize),
+    {
+        for (&(cx, cy), members) in self.cells.iter() {
+            for (k, &i) in members.iter().enumerate() {
+                for &j in &members[(k + 1)..] {
+                    f(i, j);
+                }
+            }
+            for (dx, dy) in FORWARD_NEIGHBOURS.iter() {
+                if let Some(neighbours) = self.cells.get(&(cx + dx, cy + dy)) {
+                    for &i in members {
+                        for &j in neighbours {
+                            f(i, j);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// returns the unit vector from a to b, and how far a and b are apart
+fn separation(a: &Bubble, b: &Bubble, i: usize, j: usize) -> (Vector2, f32) {
+    let d = b.position.sub(&a.position);
+    let len = d.len();
+    if len > f32::EPSILON {
+        return (d.mul_s(1.0 / len), len);
+    }
+    // the two bubbles sit on top of each other, any direction works as long as it's stable
+    let angle = (i * 7 + j * 13) as f32;
+    (Vector2 { x: angle.cos(), y: angle.sin() }, 0.0)
+}
+
+fn overlap(a: &Bubble, b: &Bubble, len: f32, padding: f32) -> f32 {
+    a.radius() + b.radius() + padding - len
+}
+
+/// Pushes overlapping bubbles apart by adding to their accelerations.
+/// The force grows linearly with how deep the two bubbles go into each other.
+pub fn collide(bubbles: &mut [Bubble], padding: f32) {
+    let grid = SpatialGrid::new(bubbles, padding);
+    grid.for_each_candidate_pair(|i, j| {
+        let (n, len) = separation(&bubbles[i], &bubbles[j], i, j);
+        let depth = overlap(&bubbles[i], &bubbles[j], len, padding);
+        if depth <= 0.0 {
+            return;
+        }
+        let force = n.mul_s(COLLISION_FORCE_FACTOR * depth);
+        let m_i = bubbles[i].size;
+        let m_j = bubbles[j].size;
+        bubbles[i].a = bubbles[i].a.sub(&force.mul_s(1.0 / m_i));
+        bubbles[j].a = bubbles[j].a.add(&force.mul_s(1.0 / m_j));
+    });
+}
+
+pub fn count_overlaps(bubbles: &[Bubble], padding: f32) -> usize {
+    let grid = SpatialGrid::new(bubbles, padding);
+    let mut count = 0;
+    grid.for_each_candidate_pair(|i, j| {
+        let (_, len) = separation(&bubbles[i], &bubbles[j], i, j);
+        if overlap(&bubbles[i], &bubbles[j], len, padding) > 0.0 {
+            count += 1;
+        }
+    });
+    count
+}
+
+/// The post-processing pass to run once the layout has settled.
+/// It moves every overlapping pair apart along the line between their centres,
+/// the lighter bubble moves more and a pinned one doesn't move at all. If that doesn't converge within `max_iterations`,
+/// the unpinned bubbles are scaled apart just enough to separate the remaining pairs.
+/// The pinned bubbles stay put, so two of them overlapping are left as they are,
+/// and with more than one pinned the scaling can push a few bubbles into them that the pairs pass can't get out again.
+pub fn remove_overlaps(bubbles: &mut [Bubble], padding: f32, max_iterations: usize) {
+    if !push_apart(bubbles, padding, max_iterations) {
+        return;
+    }
+    scale_apart(bubbles, padding);
+    // the scale is worked out around the pinned bubbles, the unpinned can still land on one of them if there're several
+    push_apart(bubbles, padding, max_iterations);
+}
+
+// returns whether there're overlaps left after `max_iterations`
+fn push_apart(bubbles: &mut [Bubble], padding: f32, max_iterations: usize) -> bool {
+    for _ in 0..max_iterations {
+        let grid = SpatialGrid::new(bubbles, padding);
+        let mut moved = false;
+        grid.for_each_candidate_pair(|i, j| {
+            if bubbles[i].pinned && bubbles[j].pinned {
+                return;
+            }
+            let (n, len) = separation(&bubbles[i], &bubbles[j], i, j);
+            let depth = overlap(&bubbles[i], &bubbles[j], len, padding);
+            if depth <= 0.0 {
+                return;
+            }
+            // the share of the push each bubble takes
+            let (s_i, s_j) = if bubbles[i].pinned {
+                (0.0, 1.0)
+            } else if bubbles[j].pinned {
+                (1.0, 0.0)
+            } else {
+                let m_i = bubbles[i].size;
+                let m_j = bubbles[j].size;
+                (m_j / (m_i + m_j), m_i / (m_i + m_j))
+            };
+            // push a little further than needed so that rounding doesn't leave them touching
+            let d = depth * 1.01;
+            bubbles[i].position = bubbles[i].position.sub(&n.mul_s(d * s_i));
+            bubbles[j].position = bubbles[j].position.add(&n.mul_s(d * s_j));
+            moved = true;
+        });
+        if !moved {
+            return false;
+        }
+    }
+    true
+}
+
+// separating the pairs might have pushed them into others, scaling can't do that
+fn scale_apart(bubbles: &mut [Bubble], padding: f32) {
+    // no scale can separate two bubbles at the same spot, and the ones almost on top of each other would need a huge one,
+    // they're moved apart before the scale is worked out, and again until none are left, since moving one can put it next to another.
+    // every pass moves them by other amounts, so a pile of them comes apart in a pass or two
+    for pass in 0.. {
+        let offsets = hashed_values(2 * bubbles.len(), pass);
+        let grid = SpatialGrid::new(bubbles, padding);
+        let mut moved = false;
+        grid.for_each_candidate_pair(|i, j| {
+            if bubbles[i].pinned && bubbles[j].pinned {
+                return;
+            }
+            let (_, len) = separation(&bubbles[i], &bubbles[j], i, j);
+            let min_distance = bubbles[i].radius() + bubbles[j].radius() + padding;
+            if len >= 0.01 * min_distance {
+                return;
+            }
+            let (from, to) = if bubbles[j].pinned { (j, i) } else { (i, j) };
+            let offset = Vector2 {
+                x: offsets[2 * to] as f32,
+                y: offsets[2 * to + 1] as f32,
+            };
+            bubbles[to].position = bubbles[from].position.add(&offset.mul_s(0.1 * min_distance));
+            moved = true;
+        });
+        if !moved {
+            break;
+        }
+    }
+
+    let grid = SpatialGrid::new(bubbles, padding);
+    let mut scale: f32 = 1.0;
+    grid.for_each_candidate_pair(|i, j| {
+        if bubbles[i].pinned && bubbles[j].pinned {
+            return;
+        }
+        let (_, len) = separation(&bubbles[i], &bubbles[j], i, j);
+        let min_distance = bubbles[i].radius() + bubbles[j].radius() + padding;
+        if len < min_distance {
+            scale = scale.max(min_distance / len);
+        }
+    });
+    if scale <= 1.0 {
+        return;
+    }
+
+    // the pinned bubbles stay where they are, so the others are scaled around them,
+    // with a single pinned bubble that keeps every bubble clear of it too
+    let pinned_count = bubbles.iter().filter(|b| b.pinned).count();
+    let around: Vec<&Bubble> = bubbles.iter().filter(|b| b.pinned || pinned_count == 0).collect();
+    let mut center = Vector2::new();
+    for b in around.iter() {
+        center = center.add(&b.position);
+    }
+    center = center.mul_s(1.0 / around.len() as f32);
+    for b in bubbles.iter_mut().filter(|b| !b.pinned) {
+        b.position = b.position.sub(&center).mul_s(scale * 1.01).add(&center);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_graph::bubble;
+
+    #[test]
+    fn remove_overlaps_separates_stacked_bubbles() {
+        // a pile on one spot and a row of touching bubbles, with too few iterations to push them apart one pair at a time
+        let mut bubbles: Vec<Bubble> = (0..6).map(|_| bubble(0.0, 0.0)).collect();
+        bubbles.extend((0..6).map(|i| bubble(i as f32, 0.0)));
+        remove_overlaps(&mut bubbles, COLLISION_PADDING, 1);
+        assert_eq!(count_overlaps(&bubbles, COLLISION_PADDING), 0);
+    }
+
+    #[test]
+    fn remove_overlaps_leaves_separate_bubbles_alone() {
+        let mut bubbles = vec![bubble(0.0, 0.0), bubble(100.0, 0.0)];
+        remove_overlaps(&mut bubbles, COLLISION_PADDING, 10);
+        assert_eq!(bubbles[0].position.x, 0.0);
+        assert_eq!(bubbles[1].position.x, 100.0);
+    }
+
+    #[test]
+    fn remove_overlaps_separates_a_big_pile() {
+        // more bubbles on one spot than the unstacking used to take passes for
+        let mut bubbles: Vec<Bubble> = (0..40).map(|_| bubble(0.0, 0.0)).collect();
+        remove_overlaps(&mut bubbles, COLLISION_PADDING, 1);
+        assert_eq!(count_overlaps(&bubbles, COLLISION_PADDING), 0);
+    }
+
+    #[test]
+    fn remove_overlaps_leaves_the_pinned_bubbles_alone() {
+        let mut bubbles: Vec<Bubble> = (0..12).map(|i| bubble(i as f32, 0.0)).collect();
+        bubbles[0].pinned = true;
+        bubbles[5].pinned = true;
+        bubbles[5].position = bubbles[0].position;
+        remove_overlaps(&mut bubbles, COLLISION_PADDING, 1);
+        assert_eq!(bubbles[0].position.x, 0.0);
+        assert_eq!(bubbles[5].position.x, 0.0);
+        // the two pinned bubbles on top of each other are the only overlap left
+        assert_eq!(count_overlaps(&bubbles, COLLISION_PADDING), 1);
+    }
+}
@@ -1,107 +1,149 @@
-use crate::mesh::Mesh;
-use std::fs::File;
-use std::io::Read;
-use serde_json::{Result, Value};
-
-use super::bubble::*;
-use super::edge::*;
-use super::math::*;
-
-fn get_random_vec2() -> Vector2 {
-    Vector2 {
-        x: rand::random(),
-        y: rand::random(),
-    }
-}
-
-pub fn create_bubbles(bubble_count: u64) -> Vec<Bubble> {
-    let mut bubbles: Vec<Bubble> = (0..bubble_count)
-        .map(|_| Bubble {
-            position: get_random_vec2().add_s(-0.5).mul_s(100.0),
-            size: rand::random::<f32>() * 24.0 + 1.0,
-            // size: 100.0,
-            v: Vector2{x: 0.0, y: 0.0},
-            a: Vector2{x: 0.0, y: 0.0},
-            meshes: [Mesh::default(), Mesh::default(), Mesh::default()],
-            label: String::from(""),
-        })
-        .collect();
-    // bubbles[0].position = Vector2{x: 0.0, y: 0.0};
-    // bubbles[1].position = Vector2{x: 1.0, y: 0.0};
-    bubbles
-}
-
-pub fn create_edges(bubble_count: usize, group_size: usize) -> Vec<Edge> {
-    let mut edges = vec![];
-    let group_count = ((bubble_count as f32) / (group_size as f32)).ceil() as usize;
-    for i in 0..group_count {
-        let group_item_count = (bubble_count - i * group_size).min(group_size);
-        for j in 1..group_item_count {
-            edges.push(Edge {
-                position_from: get_random_vec2(),
-                position_to: get_random_vec2(),
-                from: 0 + i*group_size,
-                to: j + i*group_size,
-                pull_force: 0.0,
-                mesh: Mesh::default(),
-            })
-        }
-    }
-    edges
-}
-
-pub fn create_dataset_from_file() -> Result<(Vec<Bubble>, Vec<Edge>)> {
-    // let mut file = File::open("datasets/miserables.json").unwrap();
-    let bytes = include_bytes!("datasets/miserables.json");
-    let data = String::from_utf8_lossy(bytes).into_owned();
-    // file.read_to_string(&mut data).unwrap();
-    let v: Value = serde_json::from_str(&data)?;
-
-    let nodes =  v["nodes"].as_array().unwrap();
-
-    let node_names: Vec<&str> = nodes.into_iter().map(|node| {
-        node["id"].as_str().unwrap()
-    }).collect();
-
-    let mut bubbles: Vec<Bubble> = nodes.into_iter().map(|node| Bubble {
-        position: get_random_vec2().add_s(-0.5).mul_s(100.0),
-        size: 100.0,
-        // size: 100.0,
-        v: Vector2{x: 0.0, y: 0.0},
-        a: Vector2{x: 0.0, y: 0.0},
-        meshes: [Mesh::default(), Mesh::default(), Mesh::default()],
-        label: String::from(node["id"].as_str().unwrap()),
-    })
-    .collect();
-
-    let links = v["links"].as_array().unwrap();
-    
-    let edges = links.into_iter().map(|link| {
-        let source = link["source"].as_str().unwrap();
-        let from = (&node_names).into_iter().position(|&name| name == source).unwrap();
-        {
-            let from_bubble = &mut bubbles[from];
-            from_bubble.size += 10.0;
-        }
-
-        let target = link["target"].as_str().unwrap();
-        let to = (&node_names).into_iter().position(|&name| name == target).unwrap();
-        
-        {
-            let to_bubble = &mut bubbles[to];
-            to_bubble.size += 10.0;
-        }
-
-        Edge {
-            position_from: get_random_vec2(),
-            position_to: get_random_vec2(),
-            from,
-            to,
-            pull_force: 0.0,
-            mesh: Mesh::default(),
-        }
-    })
-    .collect();
-
-    Ok((bubbles, edges))
-}
+use crate::adjacency::Adjacency;
+use crate::mesh::Mesh;
+use crate::metrics;
+use crate::pivot_mds;
+use crate::spectral;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::Read;
+use serde_json::{Result, Value};
+
+use super::bubble::*;
+use super::edge::*;
+use super::math::*;
+
+// the attribute the dataset's group of a node goes into, the circular layouts keep the groups together
+pub const GROUP: &str = "group";
+// past this many bubbles, the dataset starts from pivot mds rather than the spectral layout
+const PIVOT_MDS_LIMIT: usize = 10000;
+
+fn get_random_vec2() -> Vector2 {
+    Vector2 {
+        x: rand::random(),
+        y: rand::random(),
+    }
+}
+
+pub fn create_bubbles(bubble_count: u64) -> Vec<Bubble> {
+    let mut bubbles: Vec<Bubble> = (0..bubble_count)
+        .map(|_| Bubble {
+            position: get_random_vec2().add_s(-0.5).mul_s(100.0),
+            size: rand::random::<f32>() * 24.0 + 1.0,
+            // size: 100.0,
+            v: Vector2{x: 0.0, y: 0.0},
+            a: Vector2{x: 0.0, y: 0.0},
+            meshes: [Mesh::default(), Mesh::default(), Mesh::default()],
+            label: String::from(""),
+            pinned: false,
+            attributes: BTreeMap::new(),
+        })
+        .collect();
+    // bubbles[0].position = Vector2{x: 0.0, y: 0.0};
+    // bubbles[1].position = Vector2{x: 1.0, y: 0.0};
+    bubbles
+}
+
+pub fn create_edges(bubble_count: usize, group_size: usize) -> Vec<Edge> {
+    let mut edges = vec![];
+    let group_count = ((bubble_count as f32) / (group_size as f32)).ceil() as usize;
+    for i in 0..group_count {
+        let group_item_count = (bubble_count - i * group_size).min(group_size);
+        for j in 1..group_item_count {
+            edges.push(Edge {
+                from: 0 + i*group_size,
+                to: j + i*group_size,
+                weight: 1.0,
+                pull_force: 0.0,
+                mesh: Mesh::default(),
+                route: vec![],
+            })
+        }
+    }
+    edges
+}
+
+/// `bubble_count` random bubbles with the edges of `create_edges`, starting out like the file's dataset does.
+pub fn create_generated_dataset(bubble_count: usize, group_size: usize) -> (Vec<Bubble>, Vec<Edge>) {
+    let mut bubbles = create_bubbles(bubble_count as u64);
+    let edges = create_edges(bubbles.len(), group_size);
+    seed_positions(&mut bubbles, &edges);
+    (bubbles, edges)
+}
+
+pub fn create_dataset_from_file() -> Result<(Vec<Bubble>, Vec<Edge>)> {
+    // let mut file = File::open("datasets/miserables.json").unwrap();
+    let bytes = include_bytes!("datasets/miserables.json");
+    let data = String::from_utf8_lossy(bytes).into_owned();
+    // file.read_to_string(&mut data).unwrap();
+    let v: Value = serde_json::from_str(&data)?;
+
+    let nodes =  v["nodes"].as_array().unwrap();
+
+    let node_indices: HashMap<&str, usize> = nodes.into_iter().enumerate().map(|(i, node)| {
+        (node["id"].as_str().unwrap(), i)
+    }).collect();
+
+    // the positions are the spectral layout's, once the edges are known
+    let mut bubbles: Vec<Bubble> = nodes.into_iter().map(|node| Bubble {
+        position: Vector2::new(),
+        size: 100.0,
+        // size: 100.0,
+        v: Vector2{x: 0.0, y: 0.0},
+        a: Vector2{x: 0.0, y: 0.0},
+        meshes: [Mesh::default(), Mesh::default(), Mesh::default()],
+        label: String::from(node["id"].as_str().unwrap()),
+        pinned: false,
+        attributes: node["group"]
+            .as_f64()
+            .map(|group| (String::from(GROUP), group as f32))
+            .into_iter()
+            .collect(),
+    })
+    .collect();
+
+    let links = v["links"].as_array().unwrap();
+    
+    let edges = links.into_iter().map(|link| {
+        let source = link["source"].as_str().unwrap();
+        let from = node_indices[source];
+
+        let target = link["target"].as_str().unwrap();
+        let to = node_indices[target];
+
+        let weight = link["value"].as_f64().map_or(1.0, |v| v as f32);
+
+        Edge {
+            from,
+            to,
+            weight,
+            pull_force: 0.0,
+            mesh: Mesh::default(),
+            route: vec![],
+        }
+    })
+    .collect::<Vec<Edge>>();
+
+    // the more edges a bubble has, the bigger it is
+    let adjacency = Adjacency::new(bubbles.len(), edges.iter().map(|e| (e.from, e.to, e.weight)));
+    for (bubble, degree) in bubbles.iter_mut().zip(metrics::degree(&adjacency)) {
+        bubble.size += 10.0 * degree;
+        bubble.attributes.insert(String::from(metrics::DEGREE), degree);
+    }
+
+    seed_positions(&mut bubbles, &edges);
+
+    Ok((bubbles, edges))
+}
+
+// the force simulation settles sooner, and the same way every time, from the spectral layout than from a random scatter,
+// the big graphs start from pivot mds, which takes a fixed number of searches through the graph instead
+fn seed_positions(bubbles: &mut [Bubble], edges: &[Edge]) {
+    let positions = if bubbles.len() > PIVOT_MDS_LIMIT {
+        pivot_mds::pivot_mds_positions(bubbles, edges)
+    } else {
+        spectral::spectral_positions(bubbles, edges)
+    };
+    for (bubble, position) in bubbles.iter_mut().zip(positions) {
+        bubble.position = position;
+    }
+}
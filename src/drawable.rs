@@ -1,4 +1,4 @@
-use crate::{mesh::Mesh};
-pub trait Drawable {
-    fn get_mesh() -> Mesh;
+use crate::{mesh::Mesh};
+pub trait Drawable {
+    fn get_mesh() -> Mesh;
 }
\ No newline at end of file
@@ -0,0 +1,360 @@
+use crate::adjacency::Adjacency;
+use crate::bubble::Bubble;
+use crate::edge::Edge;
+use crate::math::Vector2;
+use crate::metrics;
+
+// how fast a single bubble may go, the ks and ksmax of the paper
+const NODE_SPEED: f32 = 0.1;
+const MAX_NODE_SPEED: f32 = 10.0;
+// the global speed grows by this much at most from one iteration to the next
+const MAX_SPEED_GROWTH: f32 = 1.5;
+// the repulsion between overlapping bubbles, when `prevent_overlap` is on, over the usual one
+const OVERLAP_REPULSION: f32 = 100.0;
+// Gephi switches to the Barnes-Hut approximation of the repulsion from this many nodes on, with this theta
+const BARNES_HUT_MIN_BUBBLES: usize = 1000;
+const BARNES_HUT_THETA: f32 = 1.2;
+// the cells aren't split further than this, the bubbles on the same spot share the last one
+const MAX_TREE_DEPTH: usize = 24;
+
+/// The parameters of ForceAtlas2, named after the ones in Gephi's panel.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ForceAtlas2Settings {
+    // how much the bubbles push each other away, Gephi starts graphs under 100 nodes at 10
+    pub scaling_ratio: f32,
+    // how much everything is pulled to the origin, so the components don't drift away
+    pub gravity: f32,
+    // the gravity grows with the distance to the origin instead of staying the same
+    pub strong_gravity: bool,
+    // the edges pull with the log of their length, which makes the clusters tighter
+    pub lin_log: bool,
+    // the edges pull with weight ^ this, 0 ignores the weights
+    pub edge_weight_influence: f32,
+    // the pull on a bubble is divided by its degree, which pushes the hubs out to the border
+    pub dissuade_hubs: bool,
+    // the bubbles repel each other much harder when they overlap
+    pub prevent_overlap: bool,
+    // how much swinging is put up with before the bubbles are slowed down
+    pub jitter_tolerance: f32,
+    pub iterations: usize,
+}
+
+impl ForceAtlas2Settings {
+    pub const DEFAULT: ForceAtlas2Settings = ForceAtlas2Settings {
+        scaling_ratio: 2.0,
+        gravity: 1.0,
+        strong_gravity: false,
+        lin_log: false,
+        edge_weight_influence: 1.0,
+        dissuade_hubs: false,
+        prevent_overlap: false,
+        jitter_tolerance: 1.0,
+        iterations: 1000,
+    };
+}
+
+impl Default for ForceAtlas2Settings {
+    fn default() -> Self {
+        ForceAtlas2Settings::DEFAULT
+    }
+}
+
+/// ForceAtlas2 (Jacomy et al., 2014) as Gephi runs it: the repulsion is weighted by the degrees,
+/// and every bubble gets its own speed from how much it swings.
+/// From `BARNES_HUT_MIN_BUBBLES` bubbles on the repulsion is approximated with a quadtree, like Gephi does.
+/// It starts from `Bubble::position` and the pinned bubbles stay put.
+pub fn forceatlas2(bubbles: &mut [Bubble], edges: &[Edge], settings: &ForceAtlas2Settings) {
+    let n = bubbles.len();
+    if n == 0 {
+        return;
+    }
+    let adjacency = Adjacency::new(n, edges.iter().map(|e| (e.from, e.to, e.weight)));
+    // the "mass" of a bubble is its degree + 1, so the bubbles without edges still push
+    let masses: Vec<f32> = metrics::degree(&adjacency).iter().map(|d| d + 1.0).collect();
+    // with the hubs dissuaded, the pull is scaled back up by the average mass so the layout keeps its size
+    let attraction_compensation = if settings.dissuade_hubs {
+        masses.iter().sum::<f32>() / n as f32
+    } else {
+        1.0
+    };
+
+    let mut positions: Vec<Vector2> = bubbles.iter().map(|b| b.position).collect();
+    let mut previous_forces = vec![Vector2::new(); n];
+    let mut speed = 1.0;
+    for _ in 0..settings.iterations {
+        let mut forces = vec![Vector2::new(); n];
+
+        if n >= BARNES_HUT_MIN_BUBBLES {
+            let tree = QuadTree::new(&positions, &masses);
+            for (i, force) in forces.iter_mut().enumerate() {
+                *force = force.add(&tree.repulsion_on(i, &positions, &masses, bubbles, settings));
+            }
+        } else {
+            for i in 0..n {
+                for j in (i + 1)..n {
+                    let d = positions[i].sub(&positions[j]);
+                    let radii = bubbles[i].radius() + bubbles[j].radius();
+                    let f = repulsion(settings, &d, masses[i] * masses[j], radii);
+                    forces[i] = forces[i].add(&f);
+                    forces[j] = forces[j].sub(&f);
+                }
+            }
+        }
+
+        for (i, p) in positions.iter().enumerate() {
+            let distance = p.len();
+            if distance == 0.0 {
+                continue;
+            }
+            let pull = if settings.strong_gravity {
+                settings.gravity * masses[i] * distance
+            } else {
+                settings.gravity * masses[i]
+            };
+            forces[i] = forces[i].sub(&p.mul_s(pull / distance));
+        }
+
+        for edge in edges.iter().filter(|e| e.from != e.to) {
+            let d = positions[edge.to].sub(&positions[edge.from]);
+            let distance = d.len();
+            if distance == 0.0 {
+                continue;
+            }
+            let mut pull = if settings.edge_weight_influence == 0.0 {
+                1.0
+            } else {
+                edge.weight.max(0.0).powf(settings.edge_weight_influence)
+            };
+            pull *= if settings.lin_log { (1.0 + distance).ln() } else { distance };
+            if settings.dissuade_hubs {
+                pull *= attraction_compensation / masses[edge.from];
+            }
+            let f = d.mul_s(pull / distance);
+            forces[edge.from] = forces[edge.from].add(&f);
+            forces[edge.to] = forces[edge.to].sub(&f);
+        }
+
+        // swinging is a force that turned around since the last iteration, traction one that kept its way
+        let mut swing_sum = 0.0;
+        let mut traction_sum = 0.0;
+        let swings: Vec<f32> = (0..n)
+            .map(|i| {
+                let swing = forces[i].sub(&previous_forces[i]).len();
+                swing_sum += masses[i] * swing;
+                traction_sum += masses[i] * forces[i].add(&previous_forces[i]).len() / 2.0;
+                swing
+            })
+            .collect();
+        if swing_sum > 0.0 {
+            speed = (settings.jitter_tolerance * traction_sum / swing_sum).min(MAX_SPEED_GROWTH * speed);
+        }
+
+        for i in 0..n {
+            if bubbles[i].pinned {
+                continue;
+            }
+            let force = forces[i].len();
+            let mut node_speed = NODE_SPEED * speed / (1.0 + speed * swings[i].sqrt());
+            if force > 0.0 {
+                node_speed = node_speed.min(MAX_NODE_SPEED / force);
+            }
+            positions[i] = positions[i].add(&forces[i].mul_s(node_speed));
+        }
+        previous_forces = forces;
+    }
+
+    for (bubble, position) in bubbles.iter_mut().zip(positions) {
+        bubble.position = position;
+        bubble.v = Vector2::new();
+    }
+}
+
+// the push on a bubble away from another one, or from a whole cell of them, `d` goes from the other one to it
+fn repulsion(settings: &ForceAtlas2Settings, d: &Vector2, mass_product: f32, radii: f32) -> Vector2 {
+    let length = d.len();
+    if length == 0.0 {
+        return Vector2::new();
+    }
+    let mut distance = length;
+    if settings.prevent_overlap {
+        distance -= radii;
+    }
+    let repulsion = settings.scaling_ratio * mass_product;
+    let repulsion = if distance > 0.0 {
+        repulsion / distance
+    } else {
+        OVERLAP_REPULSION * repulsion
+    };
+    // d / length is the direction, the repulsion is spread along it
+    d.mul_s(repulsion / length)
+}
+
+// a cell of the Barnes-Hut tree, either split in up to four or holding the bubbles in it
+struct Cell {
+    // the centre of mass of the bubbles in the cell
+    center: Vector2,
+    mass: f32,
+    // the square the cell covers
+    min: Vector2,
+    size: f32,
+    children: Vec<usize>,
+    bubbles: Vec<usize>,
+}
+
+/// The quadtree the repulsion is approximated with, a cell far enough away pushes like a single bubble
+/// as heavy as all of the ones in it, from their centre of mass.
+struct QuadTree {
+    cells: Vec<Cell>,
+    root: usize,
+}
+
+impl QuadTree {
+    fn new(positions: &[Vector2], masses: &[f32]) -> Self {
+        let mut min = positions[0];
+        let mut max = positions[0];
+        for p in positions.iter() {
+            min = Vector2 { x: min.x.min(p.x), y: min.y.min(p.y) };
+            max = Vector2 { x: max.x.max(p.x), y: max.y.max(p.y) };
+        }
+        let size = (max.x - min.x).max(max.y - min.y).max(f32::EPSILON);
+        let mut tree = QuadTree { cells: vec![], root: 0 };
+        tree.root = tree.build(positions, masses, (0..positions.len()).collect(), min, size, 0);
+        tree
+    }
+
+    // the cells are pushed children first, returns the index of the one for `members`
+    fn build(&mut self, positions: &[Vector2], masses: &[f32], members: Vec<usize>, min: Vector2, size: f32, depth: usize) -> usize {
+        let mass: f32 = members.iter().map(|&i| masses[i]).sum();
+        let mut center = Vector2::new();
+        for &i in members.iter() {
+            center = center.add(&positions[i].mul_s(masses[i] / mass));
+        }
+        let mut children = vec![];
+        if members.len() > 1 && depth < MAX_TREE_DEPTH {
+            let half = size / 2.0;
+            let mut quadrants: [Vec<usize>; 4] = Default::default();
+            for &i in members.iter() {
+                let right = positions[i].x >= min.x + half;
+                let below = positions[i].y >= min.y + half;
+                quadrants[right as usize + 2 * below as usize].push(i);
+            }
+            for (q, quadrant) in quadrants.iter_mut().enumerate() {
+                if quadrant.is_empty() {
+                    continue;
+                }
+                let corner = Vector2 {
+                    x: min.x + half * (q % 2) as f32,
+                    y: min.y + half * (q / 2) as f32,
+                };
+                children.push(self.build(positions, masses, std::mem::take(quadrant), corner, half, depth + 1));
+            }
+        }
+        let bubbles = if children.is_empty() { members } else { vec![] };
+        self.cells.push(Cell { center, mass, min, size, children, bubbles });
+        self.cells.len() - 1
+    }
+
+    fn repulsion_on(&self, i: usize, positions: &[Vector2], masses: &[f32], bubbles: &[Bubble], settings: &ForceAtlas2Settings) -> Vector2 {
+        let mut force = Vector2::new();
+        let mut stack = vec![self.root];
+        while let Some(c) = stack.pop() {
+            let cell = &self.cells[c];
+            if cell.children.is_empty() {
+                for &j in cell.bubbles.iter().filter(|&&j| j != i) {
+                    let d = positions[i].sub(&positions[j]);
+                    let radii = bubbles[i].radius() + bubbles[j].radius();
+                    force = force.add(&repulsion(settings, &d, masses[i] * masses[j], radii));
+                }
+                continue;
+            }
+            // a cell the bubble is in would push it away from itself too
+            let p = &positions[i];
+            let inside = p.x >= cell.min.x && p.x <= cell.min.x + cell.size && p.y >= cell.min.y && p.y <= cell.min.y + cell.size;
+            let d = p.sub(&cell.center);
+            if !inside && cell.size < BARNES_HUT_THETA * d.len() {
+                force = force.add(&repulsion(settings, &d, masses[i] * cell.mass, 0.0));
+            } else {
+                stack.extend(cell.children.iter().copied());
+            }
+        }
+        force
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_graph::graph;
+
+    // two groups of four bubbles, all linked inside a group and a single edge between the groups
+    fn two_groups() -> (Vec<Bubble>, Vec<Edge>) {
+        let mut pairs = vec![(3, 4)];
+        for group in [0, 4].iter() {
+            for i in 0..4 {
+                for j in i + 1..4 {
+                    pairs.push((group + i, group + j));
+                }
+            }
+        }
+        graph(8, &pairs)
+    }
+
+    fn mean_distance(bubbles: &[Bubble], pairs: impl Iterator<Item = (usize, usize)>) -> f32 {
+        let distances: Vec<f32> = pairs.map(|(a, b)| bubbles[a].position.sub(&bubbles[b].position).len()).collect();
+        distances.iter().sum::<f32>() / distances.len() as f32
+    }
+
+    #[test]
+    fn the_groups_come_apart() {
+        let (mut bubbles, edges) = two_groups();
+        forceatlas2(&mut bubbles, &edges, &ForceAtlas2Settings::DEFAULT);
+        assert!(bubbles.iter().all(|b| b.position.x.is_finite() && b.position.y.is_finite()));
+        let same_group = (0..8)
+            .flat_map(|i| (i + 1..8).map(move |j| (i, j)))
+            .filter(|&(i, j)| i / 4 == j / 4);
+        let inside = mean_distance(&bubbles, same_group);
+        let between = mean_distance(&bubbles, (0..4).flat_map(|i| (4..8).map(move |j| (i, j))));
+        assert!(inside < between, "{} inside a group, {} between them", inside, between);
+    }
+
+    #[test]
+    fn the_pinned_bubbles_stay_put() {
+        let (mut bubbles, edges) = two_groups();
+        bubbles[5].pinned = true;
+        let pinned_at = bubbles[5].position;
+        let settings = ForceAtlas2Settings {
+            lin_log: true,
+            prevent_overlap: true,
+            ..ForceAtlas2Settings::DEFAULT
+        };
+        forceatlas2(&mut bubbles, &edges, &settings);
+        assert_eq!((bubbles[5].position.x, bubbles[5].position.y), (pinned_at.x, pinned_at.y));
+    }
+
+    #[test]
+    fn the_quadtree_pushes_about_as_hard_as_every_pair() {
+        let n = BARNES_HUT_MIN_BUBBLES;
+        let xs = crate::spectral::hashed_values(n, 0);
+        let ys = crate::spectral::hashed_values(n, 1);
+        let (mut bubbles, _) = graph(n, &[]);
+        for (i, bubble) in bubbles.iter_mut().enumerate() {
+            bubble.position = Vector2 { x: xs[i] as f32 * 10_000.0, y: ys[i] as f32 * 10_000.0 };
+        }
+        let positions: Vec<Vector2> = bubbles.iter().map(|b| b.position).collect();
+        let masses: Vec<f32> = (0..n).map(|i| 1.0 + (i % 5) as f32).collect();
+        let settings = ForceAtlas2Settings::DEFAULT;
+        let tree = QuadTree::new(&positions, &masses);
+        for i in (0..n).step_by(97) {
+            // the pushes from all sides mostly cancel out, the error is measured against how hard they push in all
+            let mut exact = Vector2::new();
+            let mut total = 0.0;
+            for j in (0..n).filter(|&j| j != i) {
+                let f = repulsion(&settings, &positions[i].sub(&positions[j]), masses[i] * masses[j], 0.0);
+                exact = exact.add(&f);
+                total += f.len();
+            }
+            let approximated = tree.repulsion_on(i, &positions, &masses, &bubbles, &settings);
+            assert!(approximated.sub(&exact).len() < 0.05 * total, "{:?} isn't {:?}", approximated, exact);
+        }
+    }
+}
@@ -1,78 +1,296 @@
-use std::f32::consts;
-
-use crate::{physics::Physics};
-
-use super::bubble::*;
-use super::edge::*;
-// use super::vector2::*;
-
-pub fn forcelayout(bubbles: &mut Vec<Bubble>, edges: &mut Vec<Edge>) {
-    let time_step = 0.5;
-    let bubble_len = bubbles.len();
-    for i in 0..bubble_len {
-        let a = &mut bubbles[i].a;
-        a.x = 0.0;
-        a.y = 0.0;
-    }
-    for i in 0..(bubble_len - 1) {
-        // I need to use unsafe here because rust thinks bubble_a and bubble_b are two borrows from a single object..
-        unsafe {
-            let imr_a = &mut bubbles[i];
-            let bubble_a = imr_a as *mut Bubble;
-            for j in (i + 1)..bubble_len {
-                let imr_b = &mut bubbles[j];
-                let bubble_b = imr_b as *mut Bubble;
-
-                let m_a = (*bubble_a).get_m();
-                let m_b = (*bubble_b).get_m();
-
-                let d_ab = (*bubble_b).position.sub(&(*bubble_a).position);
-                let nd_ab = d_ab.norm();
-                let repulsive_force_factor = 1.0;
-                let repulsive_force = nd_ab.mul_s(repulsive_force_factor * m_a * m_b / d_ab.sqrt_len());
-
-                let a_a = repulsive_force.mul_s(-1.0 / m_a);
-                (*bubble_a).a = (*bubble_a).a.add(&a_a);
-
-                let a_b = a_a.mul_s(-1.0 * m_a / m_b);
-                (*bubble_b).a = (*bubble_b).a.add(&a_b);
-            }
-        }
-    }
-
-    let edge_len = edges.len();
-
-    for i in 0..edge_len {
-        let edge = &mut edges[i];
-        let bubble_from = (& mut bubbles[edge.from]) as *mut Bubble;
-        let bubble_to = (& mut bubbles[edge.to]) as *mut Bubble;
-        unsafe {
-            let m_from = (*bubble_from).get_m();
-            let m_to = (*bubble_to).get_m();
-
-            let d_from_to = (*bubble_to).position.sub(&(*bubble_from).position);
-            let pull_force_factor = 1.0;
-            let pull_force_from_to = d_from_to.mul_s(pull_force_factor);
-            edge.pull_force = pull_force_from_to.len();
-            let a_from = pull_force_from_to.mul_s(1.0/m_from);
-            (*bubble_from).a = (*bubble_from).a.add(&a_from);
-            let a_to = a_from.mul_s(-1.0 * m_from / m_to);
-            (*bubble_to).a = (*bubble_to).a.add(&a_to);
-        }
-    }
-
-
-    for bubble in bubbles.iter_mut() {
-        bubble.v = bubble.v.add(&bubble.a.mul_s(time_step));
-
-        // damping, the higher the velocity is, the quicker it damps
-        bubble.v = bubble.v.mul_s((1.0 - (bubble.v.len() * 0.1).atan() *2.0 / std::f32::consts::PI).min(0.9));
-
-        bubble.position = bubble.position.add(&bubble.v.mul_s(time_step));
-    }
-
-    for edge in edges {
-        edge.position_from.set(& (&bubbles[edge.from]).position);
-        edge.position_to.set(& (&bubbles[edge.to]).position);
-    }
-}
+use std::f32::consts;
+
+use crate::{circular, collision::{collide, remove_overlaps, SpatialGrid, COLLISION_PADDING, OVERLAP_REMOVAL_ITERATIONS}, forceatlas2::{self, ForceAtlas2Settings}, fruchterman_reingold, kamada_kawai, math::Vector2, multilevel, sugiyama, physics::Physics, pivot_mds, spectral, stress, tree};
+
+use super::bubble::*;
+use super::edge::*;
+// use super::vector2::*;
+
+// past this many bubbles the cpu step only pushes apart the ones near each other
+const EXACT_REPULSION_LIMIT: usize = 2000;
+// how near, in lengths an edge between the two heaviest bubbles settles at
+const REPULSION_CUTOFF: f32 = 4.0;
+
+/// The algorithms the bubbles can be laid out with, see `layout`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LayoutMode {
+    // the spring-electrical simulation, it keeps running, on the gpu in the viewer
+    Force,
+    // stress majorization over the hop distances
+    Stress,
+    // springs between every pair of bubbles, for small graphs
+    KamadaKawai,
+    // Gephi's force model
+    ForceAtlas2(ForceAtlas2Settings),
+    // the textbook force model, with a cooling temperature
+    FruchtermanReingold,
+    // this model's force steps over a hierarchy of coarser graphs, for big graphs
+    Multilevel,
+    // layers for directed graphs, with the long edges routed round the bubbles
+    Sugiyama,
+    // a tidy tree hanging from the root, the selected bubble in the viewer or else the one with the most edges
+    TidyTree(Option<usize>),
+    // the same tree bent round its root, the levels become rings
+    RadialTree(Option<usize>),
+    // round a circle by group, with the edges as chords
+    Circular,
+    // in a row by group, with the edges as arcs
+    ArcDiagram,
+    // the eigenvectors of the graph Laplacian, what the force simulation starts from
+    Spectral,
+    // classical scaling on the distances to a few pivots, what the force simulation starts big graphs from
+    PivotMds,
+}
+
+impl LayoutMode {
+    pub const ALL: [LayoutMode; 13] = [
+        LayoutMode::Force,
+        LayoutMode::Stress,
+        LayoutMode::KamadaKawai,
+        LayoutMode::ForceAtlas2(ForceAtlas2Settings::DEFAULT),
+        LayoutMode::FruchtermanReingold,
+        LayoutMode::Multilevel,
+        LayoutMode::Sugiyama,
+        LayoutMode::TidyTree(None),
+        LayoutMode::RadialTree(None),
+        LayoutMode::Circular,
+        LayoutMode::ArcDiagram,
+        LayoutMode::Spectral,
+        LayoutMode::PivotMds,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LayoutMode::Force => "force",
+            LayoutMode::Stress => "stress",
+            LayoutMode::KamadaKawai => "kamada-kawai",
+            LayoutMode::ForceAtlas2(_) => "forceatlas2",
+            LayoutMode::FruchtermanReingold => "fruchterman-reingold",
+            LayoutMode::Multilevel => "multilevel",
+            LayoutMode::Sugiyama => "sugiyama",
+            LayoutMode::TidyTree(_) => "tidy-tree",
+            LayoutMode::RadialTree(_) => "radial-tree",
+            LayoutMode::Circular => "circular",
+            LayoutMode::ArcDiagram => "arc-diagram",
+            LayoutMode::Spectral => "spectral",
+            LayoutMode::PivotMds => "pivot-mds",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        LayoutMode::ALL.iter().copied().find(|mode| mode.name() == name)
+    }
+
+    /// The same mode rooted at `root`, for the tree layouts, the others don't have a root.
+    pub fn with_root(self, root: Option<usize>) -> Self {
+        match self {
+            LayoutMode::TidyTree(_) => LayoutMode::TidyTree(root),
+            LayoutMode::RadialTree(_) => LayoutMode::RadialTree(root),
+            mode => mode,
+        }
+    }
+
+    /// Whether the layout routes the edges, it spaces the bubbles out itself and moving them would leave the routes behind.
+    pub fn routes_edges(self) -> bool {
+        matches!(self, LayoutMode::Sugiyama | LayoutMode::Circular | LayoutMode::ArcDiagram)
+    }
+}
+
+/// Lays the bubbles out with `mode`, into `Bubble::position`.
+/// The force simulation takes a single step, the other layouts run until they've settled
+/// and are finished off with `remove_overlaps`, which leaves the pinned bubbles where they are too,
+/// apart from the ones that route edges.
+/// The old edge routes don't fit the new positions, they're cleared and only the layouts that route edges fill them in.
+pub fn layout(mode: LayoutMode, bubbles: &mut [Bubble], edges: &mut [Edge]) {
+    for edge in edges.iter_mut() {
+        edge.route.clear();
+    }
+    match mode {
+        LayoutMode::Force => forcelayout(bubbles, edges),
+        LayoutMode::Stress => stress::stress_majorization(bubbles, edges),
+        LayoutMode::KamadaKawai => kamada_kawai::kamada_kawai(bubbles, edges),
+        LayoutMode::ForceAtlas2(settings) => forceatlas2::forceatlas2(bubbles, edges, &settings),
+        LayoutMode::FruchtermanReingold => fruchterman_reingold::fruchterman_reingold(bubbles, edges),
+        LayoutMode::Multilevel => multilevel::multilevel(bubbles, edges, multilevel::cpu_refine),
+        LayoutMode::Sugiyama => sugiyama::sugiyama(bubbles, edges),
+        LayoutMode::TidyTree(root) => tree::tidy_tree(bubbles, edges, root),
+        LayoutMode::RadialTree(root) => tree::radial_tree(bubbles, edges, root),
+        LayoutMode::Circular => circular::circular(bubbles, edges),
+        LayoutMode::ArcDiagram => circular::arc_diagram(bubbles, edges),
+        LayoutMode::Spectral => spectral::spectral(bubbles, edges),
+        LayoutMode::PivotMds => pivot_mds::pivot_mds(bubbles, edges),
+    }
+    if mode != LayoutMode::Force && !mode.routes_edges() {
+        remove_overlaps(bubbles, COLLISION_PADDING, OVERLAP_REMOVAL_ITERATIONS);
+    }
+}
+
+/// One step of the spring-electrical simulation on the cpu, the pinned bubbles stay put.
+/// Past `EXACT_REPULSION_LIMIT` bubbles only the ones near each other push, see `push_apart_nearby`.
+pub fn forcelayout(bubbles: &mut [Bubble], edges: &mut [Edge]) {
+    let time_step = 0.5;
+    let bubble_len = bubbles.len();
+    if bubble_len == 0 {
+        return;
+    }
+    for i in 0..bubble_len {
+        let a = &mut bubbles[i].a;
+        a.x = 0.0;
+        a.y = 0.0;
+    }
+    if bubble_len > EXACT_REPULSION_LIMIT {
+        push_apart_nearby(bubbles);
+    } else {
+        push_apart(bubbles);
+    }
+
+    let edge_len = edges.len();
+
+    for i in 0..edge_len {
+        let edge = &mut edges[i];
+        let bubble_from = (& mut bubbles[edge.from]) as *mut Bubble;
+        let bubble_to = (& mut bubbles[edge.to]) as *mut Bubble;
+        unsafe {
+            let m_from = (*bubble_from).get_m();
+            let m_to = (*bubble_to).get_m();
+
+            let d_from_to = (*bubble_to).position.sub(&(*bubble_from).position);
+            // every edge pulls the same, the weights are for the metrics and the paths, not for stiffer springs
+            let pull_force_factor = 1.0;
+            let pull_force_from_to = d_from_to.mul_s(pull_force_factor);
+            edge.pull_force = pull_force_from_to.len();
+            let a_from = pull_force_from_to.mul_s(1.0/m_from);
+            (*bubble_from).a = (*bubble_from).a.add(&a_from);
+            let a_to = a_from.mul_s(-1.0 * m_from / m_to);
+            (*bubble_to).a = (*bubble_to).a.add(&a_to);
+        }
+    }
+
+    collide(bubbles, COLLISION_PADDING);
+
+    for bubble in bubbles.iter_mut() {
+        // pinned bubbles are felt by the others but stay where they were put, like on the gpu
+        if bubble.pinned {
+            bubble.v = Vector2::new();
+            continue;
+        }
+        bubble.v = bubble.v.add(&bubble.a.mul_s(time_step));
+
+        // damping, the higher the velocity is, the quicker it damps
+        bubble.v = bubble.v.mul_s((1.0 - (bubble.v.len() * 0.1).atan() *2.0 / std::f32::consts::PI).min(0.9));
+
+        bubble.position = bubble.position.add(&bubble.v.mul_s(time_step));
+    }
+}
+
+// every pair pushes with m_a·m_b / d², shared out by their masses
+fn push_apart(bubbles: &mut [Bubble]) {
+    for i in 0..bubbles.len() {
+        for j in (i + 1)..bubbles.len() {
+            repel(bubbles, i, j);
+        }
+    }
+}
+
+// the push falls off with the square of the distance, so past `REPULSION_CUTOFF` times the length
+// an edge between the two heaviest bubbles settles at, where it's a sixteenth of the pull, it's left out
+fn push_apart_nearby(bubbles: &mut [Bubble]) {
+    let max_mass = bubbles.iter().map(|b| b.get_m()).fold(0.0, f32::max);
+    let cutoff = REPULSION_CUTOFF * (max_mass * max_mass).cbrt();
+    let grid = SpatialGrid::with_cell_size(bubbles.iter().map(|b| &b.position), cutoff);
+    grid.for_each_candidate_pair(|i, j| {
+        if bubbles[i].position.sub(&bubbles[j].position).len() < cutoff {
+            repel(bubbles, i, j);
+        }
+    });
+}
+
+fn repel(bubbles: &mut [Bubble], i: usize, j: usize) {
+    let m_a = bubbles[i].get_m();
+    let m_b = bubbles[j].get_m();
+
+    let d_ab = bubbles[j].position.sub(&bubbles[i].position);
+    let nd_ab = d_ab.norm();
+    let repulsive_force_factor = 1.0;
+    let repulsive_force = nd_ab.mul_s(repulsive_force_factor * m_a * m_b / d_ab.sqrt_len());
+
+    let a_a = repulsive_force.mul_s(-1.0 / m_a);
+    bubbles[i].a = bubbles[i].a.add(&a_a);
+
+    let a_b = a_a.mul_s(-1.0 * m_a / m_b);
+    bubbles[j].a = bubbles[j].a.add(&a_b);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collision::count_overlaps;
+    use crate::test_graph::graph;
+
+    #[test]
+    fn every_layout_leaves_the_pinned_bubble_where_it_is() {
+        // a small tree with a chord across it, so the tree layouts have a root to start from and the others a cycle
+        let links = [(0, 1), (0, 2), (1, 3), (1, 4), (2, 5), (2, 6), (3, 7), (6, 7)];
+        for &mode in LayoutMode::ALL.iter() {
+            let (mut bubbles, mut edges) = graph(8, &links);
+            bubbles[3].pinned = true;
+            let pinned_at = bubbles[3].position;
+            layout(mode.with_root(Some(0)), &mut bubbles, &mut edges);
+            let moved = (bubbles[3].position.x, bubbles[3].position.y) != (pinned_at.x, pinned_at.y);
+            assert!(!moved, "{} moved the pinned bubble", mode.name());
+        }
+    }
+
+    #[test]
+    fn the_routed_layouts_are_left_as_they_routed_the_edges() {
+        let links = [(0, 1), (0, 2), (0, 3), (1, 4), (0, 4), (2, 5), (4, 5)];
+        let pinned_at = |p: Vector2| {
+            let (mut bubbles, edges) = graph(6, &links);
+            bubbles[5].pinned = true;
+            bubbles[5].position = p;
+            (bubbles, edges)
+        };
+        for &mode in [LayoutMode::Sugiyama, LayoutMode::Circular, LayoutMode::ArcDiagram].iter() {
+            let route = |bubbles: &mut [Bubble], edges: &mut [Edge]| match mode {
+                LayoutMode::Sugiyama => sugiyama::sugiyama(bubbles, edges),
+                LayoutMode::Circular => circular::circular(bubbles, edges),
+                _ => circular::arc_diagram(bubbles, edges),
+            };
+            // the layout is centred on where the bubbles were, the pinned one too, so bubble 3 ends up at a + p / 6,
+            // pinned at p = a * 6 / 5 it's on top of bubble 3
+            let (mut bubbles, mut edges) = pinned_at(Vector2::new());
+            route(&mut bubbles, &mut edges);
+            let on_top = bubbles[3].position.mul_s(6.0 / 5.0);
+            let (mut expected, mut expected_edges) = pinned_at(on_top);
+            route(&mut expected, &mut expected_edges);
+            assert_eq!(count_overlaps(&expected, COLLISION_PADDING), 1, "{}", mode.name());
+            let (mut routed, mut routed_edges) = pinned_at(on_top);
+            layout(mode, &mut routed, &mut routed_edges);
+            let positions = |bubbles: &[Bubble]| -> Vec<(f32, f32)> { bubbles.iter().map(|b| (b.position.x, b.position.y)).collect() };
+            assert_eq!(positions(&routed), positions(&expected), "{} moved the bubbles off their routes", mode.name());
+        }
+    }
+
+    #[test]
+    fn only_the_near_bubbles_push_on_big_graphs() {
+        let scattered = || {
+            let (mut bubbles, _) = graph(12, &[]);
+            // the first ones well within the cutoff of each other, `graph` spreads them too far
+            for bubble in bubbles.iter_mut() {
+                bubble.position = bubble.position.mul_s(0.08);
+            }
+            bubbles[11].position = Vector2 { x: 10_000.0, y: 0.0 };
+            bubbles
+        };
+        let mut exact = scattered();
+        push_apart(&mut exact);
+        let mut near = scattered();
+        push_apart_nearby(&mut near);
+        // the lone bubble is too far off to be pushed, the others push each other as they would anyway
+        assert_eq!((near[11].a.x, near[11].a.y), (0.0, 0.0));
+        assert!(exact[11].a.len() > 0.0);
+        for (near, exact) in near.iter().zip(exact.iter()).take(11) {
+            assert!(near.a.sub(&exact.a).len() <= 1e-3 * exact.a.len(), "{:?} isn't {:?}", near.a, exact.a);
+        }
+    }
+}
@@ -0,0 +1,156 @@
+use crate::bubble::Bubble;
+use crate::collision::SpatialGrid;
+use crate::edge::Edge;
+use crate::math::Vector2;
+use crate::stress::EDGE_LENGTH;
+
+const ITERATIONS: usize = 500;
+// past this many bubbles only the ones closer than 2k push each other, found on a grid, the paper's grid variant
+const EXACT_REPULSION_LIMIT: usize = 1000;
+
+/// Fruchterman-Reingold, as in the paper: the edges pull with d² / k, every pair pushes with k² / d,
+/// and a bubble moves no further than the temperature, which cools down linearly to nothing.
+/// k is `EDGE_LENGTH`, and the bubbles are kept in a square frame of area n k² around where they started.
+/// The pinned bubbles stay put. Past `EXACT_REPULSION_LIMIT` bubbles the pushes are cut off at 2k, as the paper does.
+pub fn fruchterman_reingold(bubbles: &mut [Bubble], edges: &[Edge]) {
+    let n = bubbles.len();
+    if n == 0 {
+        return;
+    }
+    let k = EDGE_LENGTH;
+    let half_width = 0.5 * k * (n as f32).sqrt();
+    let mut positions: Vec<Vector2> = bubbles.iter().map(|b| b.position).collect();
+    let center = positions
+        .iter()
+        .fold(Vector2::new(), |sum, p| sum.add(p))
+        .mul_s(1.0 / n as f32);
+    // a layout spread wider than the frame is shrunk into it, clamped it'd pile up on the border, the pinned bubbles stay put
+    let spread = positions
+        .iter()
+        .map(|p| (p.x - center.x).abs().max((p.y - center.y).abs()))
+        .fold(0.0, f32::max);
+    if spread > half_width {
+        for (p, bubble) in positions.iter_mut().zip(bubbles.iter()) {
+            if !bubble.pinned {
+                *p = p.sub(&center).mul_s(half_width / spread).add(&center);
+            }
+        }
+    }
+    let start_temperature = 0.1 * 2.0 * half_width;
+
+    for iteration in 0..ITERATIONS {
+        let temperature = start_temperature * (1.0 - iteration as f32 / ITERATIONS as f32);
+        let mut displacements = if n > EXACT_REPULSION_LIMIT {
+            grid_repulsion(&positions, k)
+        } else {
+            repulsion(&positions, k)
+        };
+
+        for edge in edges.iter().filter(|e| e.from != e.to) {
+            let d = positions[edge.to].sub(&positions[edge.from]);
+            let pull = d.mul_s(d.len() / k);
+            displacements[edge.from] = displacements[edge.from].add(&pull);
+            displacements[edge.to] = displacements[edge.to].sub(&pull);
+        }
+
+        for (i, displacement) in displacements.iter().enumerate() {
+            let length = displacement.len();
+            if bubbles[i].pinned || length == 0.0 {
+                continue;
+            }
+            let p = positions[i].add(&displacement.mul_s(length.min(temperature) / length));
+            positions[i] = Vector2 {
+                x: p.x.clamp(center.x - half_width, center.x + half_width),
+                y: p.y.clamp(center.y - half_width, center.y + half_width),
+            };
+        }
+    }
+
+    for (bubble, position) in bubbles.iter_mut().zip(positions) {
+        bubble.position = position;
+        bubble.v = Vector2::new();
+    }
+}
+
+// every pair pushes with k² / d
+fn repulsion(positions: &[Vector2], k: f32) -> Vec<Vector2> {
+    let n = positions.len();
+    let mut displacements = vec![Vector2::new(); n];
+    for i in 0..n {
+        for j in (i + 1)..n {
+            push(positions, k, i, j, &mut displacements);
+        }
+    }
+    displacements
+}
+
+// only the pairs closer than 2k push, they're in the same or neighbouring cells of a grid of 2k
+fn grid_repulsion(positions: &[Vector2], k: f32) -> Vec<Vector2> {
+    let mut displacements = vec![Vector2::new(); positions.len()];
+    let grid = SpatialGrid::with_cell_size(positions.iter(), 2.0 * k);
+    grid.for_each_candidate_pair(|i, j| {
+        if positions[i].sub(&positions[j]).len() < 2.0 * k {
+            push(positions, k, i, j, &mut displacements);
+        }
+    });
+    displacements
+}
+
+fn push(positions: &[Vector2], k: f32, i: usize, j: usize, displacements: &mut [Vector2]) {
+    let d = positions[i].sub(&positions[j]);
+    let distance = d.len();
+    if distance == 0.0 {
+        return;
+    }
+    let push = d.mul_s(k * k / (distance * distance));
+    displacements[i] = displacements[i].add(&push);
+    displacements[j] = displacements[j].sub(&push);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout_quality::LayoutQuality;
+    use crate::test_graph::cycle;
+
+    #[test]
+    fn fruchterman_reingold_opens_a_ring_up() {
+        let (mut bubbles, edges) = cycle(8);
+        let before = LayoutQuality::evaluate(&bubbles, &edges);
+        fruchterman_reingold(&mut bubbles, &edges);
+        let after = LayoutQuality::evaluate(&bubbles, &edges);
+        assert!(after.stress < 0.5 * before.stress, "the stress went from {} to {}", before.stress, after.stress);
+        assert_eq!(after.edge_crossings, Some(0));
+    }
+
+    #[test]
+    fn the_bubbles_stay_in_the_frame() {
+        let (mut bubbles, edges) = cycle(9);
+        let n = bubbles.len() as f32;
+        let center = bubbles.iter().fold(Vector2::new(), |sum, b| sum.add(&b.position)).mul_s(1.0 / n);
+        bubbles[4].pinned = true;
+        let pinned_at = bubbles[4].position;
+        fruchterman_reingold(&mut bubbles, &edges);
+        let half_width = 0.5 * EDGE_LENGTH * n.sqrt();
+        for b in bubbles.iter().filter(|b| !b.pinned) {
+            assert!((b.position.x - center.x).abs() <= half_width + 1e-3);
+            assert!((b.position.y - center.y).abs() <= half_width + 1e-3);
+        }
+        assert_eq!((bubbles[4].position.x, bubbles[4].position.y), (pinned_at.x, pinned_at.y));
+    }
+
+    #[test]
+    fn the_grid_finds_every_push_within_2k() {
+        // a row with the bubbles 0.3k apart, the ones more than 6 apart don't push on the grid
+        let positions: Vec<Vector2> = (0..40).map(|i| Vector2 { x: i as f32 * 0.3 * EDGE_LENGTH, y: 0.0 }).collect();
+        let mut near = vec![Vector2::new(); positions.len()];
+        for i in 0..positions.len() {
+            for j in (i + 1)..positions.len().min(i + 7) {
+                push(&positions, EDGE_LENGTH, i, j, &mut near);
+            }
+        }
+        for (grid, exact) in grid_repulsion(&positions, EDGE_LENGTH).iter().zip(near.iter()) {
+            assert!(grid.sub(exact).len() < 1e-3 * EDGE_LENGTH, "{:?} isn't {:?}", grid, exact);
+        }
+    }
+}
@@ -1,221 +1,619 @@
-use std::{convert::TryInto, mem::size_of};
-
-use futures::executor::block_on;
-use wgpu::{BindGroup, BindGroupLayoutEntry, Buffer, BufferUsages, Instance};
-
-use crate::math::Vector2;
-
-#[repr(C)]
-#[derive(Copy, Clone)]
-pub struct BubbleGpuEntity {
-    pub m: f32,
-    // if i don't pad a float here, the os will fill a random number here anyway, and it ruins the buffer size calculation
-    // see https://renderdoc.org/vkspec_chunked/chap16.html#interfaces-resources-layout
-    pub _pad1: f32, 
-    pub p: [f32; 2],
-    pub v: [f32; 2],
-    pub a: [f32; 2],
-}
-unsafe impl bytemuck::Pod for BubbleGpuEntity {}
-unsafe impl bytemuck::Zeroable for BubbleGpuEntity {}
-
-pub struct GpuForcelayout {
-    instance: Instance,
-    adapter: wgpu::Adapter,
-    device: wgpu::Device,
-    queue: wgpu::Queue,
-    edge_buffer: Buffer,
-    bubble_buffer: Buffer,
-    globals_buffer: Buffer,
-    staging_buffer: Buffer,
-    bubble_buffer_size: u64,
-    bind_group: BindGroup,
-    compute_repulsion_pipeline: wgpu::ComputePipeline,
-    compute_pull_pipeline: wgpu::ComputePipeline,
-    compute_position_pipeline: wgpu::ComputePipeline,
-    bubble_count: u32,
-    edge_count: u32,
-}
-
-type Globals = [u32; 4];
-
-// and every element in an array should be 16 bytes(4 u32)
-// https://www.cnblogs.com/murongxiaopifu/p/9697704.html
-pub type EdgeEntity = [u32; 4];
-// unsafe impl bytemuck::Pod for GpuForcelayout {}
-// unsafe impl bytemuck::Zeroable for GpuForcelayout {}
-
-fn create_buffer(device: &wgpu::Device, size: u64, usage: BufferUsages) -> Buffer {
-    device.create_buffer(&wgpu::BufferDescriptor {
-        label: None,
-        size,
-        // usage: BufferUsages::COPY_DST | BufferUsages::COPY_SRC | BufferUsages::STORAGE | BufferUsages::MAP_READ,
-        usage,
-        mapped_at_creation: false,
-    })
-}
-
-impl GpuForcelayout {
-    pub fn new(bubbles: Vec<BubbleGpuEntity>, edges: Vec<EdgeEntity>) -> Self {
-        let globals:Globals = [bubbles.len() as u32, edges.len() as u32, 0, 0];
-        
-        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor{
-            backends: wgpu::Backends::PRIMARY,
-            dx12_shader_compiler: wgpu::Dx12Compiler::default(),
-        });
-        // create an adapter
-        let adapter = block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
-            power_preference: wgpu::PowerPreference::default(),
-            compatible_surface: None,
-            force_fallback_adapter: false,
-        }))
-        .unwrap();
-        // create a device and a queue
-        let (device, queue) = block_on(adapter.request_device(
-            &wgpu::DeviceDescriptor {
-                label: None,
-                features: wgpu::Features::default(),
-                limits: wgpu::Limits::default(),
-            },
-            None,
-        ))
-        .unwrap();
-
-        let compute_repulsion_module = &device.create_shader_module(wgpu::include_spirv!("./../shaders/compute_repulsion.comp.spv"));
-        let compute_pull_module = &device.create_shader_module(wgpu::include_spirv!("./../shaders/compute_pull.comp.spv"));
-        let compute_position_module = &device.create_shader_module(wgpu::include_spirv!("./../shaders/compute_position.comp.spv"));
-
-        let bubble_buffer_size = (size_of::<BubbleGpuEntity>() * bubbles.len()) as u64;
-        let edge_buffer_size = (size_of::<EdgeEntity>() * edges.len()) as u64;
-        let globals_buffer_size = size_of::<Globals>() as u64;
-        let edge_buffer = create_buffer(&device, edge_buffer_size, BufferUsages::STORAGE | BufferUsages::COPY_DST);
-        let bubble_buffer = create_buffer(&device, bubble_buffer_size, BufferUsages::COPY_DST | BufferUsages::COPY_SRC | BufferUsages::STORAGE);
-        let globals_buffer = create_buffer(&device, globals_buffer_size, BufferUsages::COPY_SRC| BufferUsages::COPY_DST | BufferUsages::STORAGE);
-        let staging_buffer = create_buffer(&device, bubble_buffer_size, BufferUsages::COPY_DST | BufferUsages::MAP_READ);
-
-        let create_bind_group_layout_desc = |binding: u32, size: u64| wgpu::BindGroupLayoutEntry {
-            binding,
-            visibility: wgpu::ShaderStages::COMPUTE,
-            ty: wgpu::BindingType::Buffer {
-                ty: wgpu::BufferBindingType::Storage { read_only: false },
-                has_dynamic_offset: false,
-                min_binding_size: wgpu::BufferSize::new(size),
-            },
-            count: None,
-        };
-        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: None,
-            entries: &[
-                create_bind_group_layout_desc(0, bubble_buffer_size),
-                create_bind_group_layout_desc(1, globals_buffer_size),
-                create_bind_group_layout_desc(2, edge_buffer_size),
-            ],
-        });
-
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: None,
-            layout: &bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry{ binding: 0, resource: bubble_buffer.as_entire_binding() },
-                wgpu::BindGroupEntry{ binding: 1, resource: globals_buffer.as_entire_binding() },
-                wgpu::BindGroupEntry{ binding: 2, resource: edge_buffer.as_entire_binding() },
-            ],
-        });
-
-        queue.write_buffer(&bubble_buffer, 0, bytemuck::cast_slice(&bubbles));
-        queue.write_buffer(&globals_buffer, 0, bytemuck::cast_slice(&globals));
-        queue.write_buffer(&edge_buffer, 0, bytemuck::cast_slice(&edges));
-        queue.submit(None);
-
-        let create_compute_pipeline = |module: &wgpu::ShaderModule| {
-            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-                label: None,
-                layout: Some(
-                    &device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                        label: None,
-                        bind_group_layouts: &[&bind_group_layout],
-                        push_constant_ranges: &[],
-                    }),
-                ),
-                module,
-                entry_point: "main",
-            })
-        };
-
-        let compute_repulsion_pipeline = create_compute_pipeline(&compute_repulsion_module);
-        let compute_pull_pipeline = create_compute_pipeline(&compute_pull_module);
-        let compute_position_pipeline = create_compute_pipeline(&compute_position_module);
-
-        Self {
-            instance,
-            adapter,
-            device,
-            queue,
-            edge_buffer,
-            bubble_buffer,
-            globals_buffer,
-            staging_buffer,
-            compute_repulsion_pipeline,
-            compute_pull_pipeline,
-            compute_position_pipeline,
-            bubble_count: bubbles.len() as u32,
-            edge_count: edges.len() as u32,
-            bind_group,
-            bubble_buffer_size,
-        }
-    }
-
-    async fn get_compute_result(&self) -> Vec<BubbleGpuEntity> {
-        // Note that we're not calling `.await` here.
-        let staging_buffer = &self.staging_buffer;
-        let buffer_slice = staging_buffer.slice(..);
-        // Gets the future representing when `staging_buffer` can be read from
-        let buffer_future = buffer_slice.map_async(wgpu::MapMode::Read, Result::unwrap);
-
-        // Poll the device in a blocking manner so that our future resolves.
-        // In an actual application, `device.poll(...)` should
-        // be called in an event loop or on another thread.
-        self.device.poll(wgpu::Maintain::Wait);
-
-        // Awaits until `buffer_future` can be read from
-        // if let Ok(()) = buffer_future.await {
-            // Gets contents of buffer
-            let data = buffer_slice.get_mapped_range();
-            // Since contents are got in bytes, this converts these bytes back to u32
-            let result = data
-                .chunks_exact(size_of::<BubbleGpuEntity>())
-                .map(|entity_bytes| *bytemuck::from_bytes::<BubbleGpuEntity>(&entity_bytes))
-                .collect();
-
-            // With the current interface, we have to make sure all mapped views are
-            // dropped before we unmap the buffer.
-            drop(data);
-            staging_buffer.unmap();
-            result
-        // } else {
-        //     panic!("failed to run compute on gpu!")
-        // }
-    }
-
-    pub async fn compute(&mut self) -> Vec<BubbleGpuEntity> {
-        let mut encoder = self
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
-        {
-            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
-            pass.set_bind_group(0, &self.bind_group, &[]);
-
-            pass.set_pipeline(&self.compute_repulsion_pipeline);
-            pass.dispatch_workgroups(self.bubble_count, 1, 1);
-            
-            pass.set_pipeline(&self.compute_pull_pipeline);
-            pass.dispatch_workgroups(self.bubble_count, 1, 1);
-
-            pass.set_pipeline(&self.compute_position_pipeline);
-            pass.dispatch_workgroups(self.bubble_count, 1, 1);
-        }
-        encoder.copy_buffer_to_buffer(&self.bubble_buffer, 0, &self.staging_buffer, 0, self.bubble_buffer_size);
-        self.queue.submit(Some(encoder.finish()));
-        self.get_compute_result().await
-    }
-}
+use std::{
+    convert::TryInto,
+    mem::size_of,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use futures::executor::block_on;
+use wgpu::{BindGroup, BindGroupLayoutEntry, Buffer, BufferUsages, CommandEncoder};
+
+use crate::adjacency::Adjacency;
+use crate::collision::COLLISION_PADDING;
+use crate::graph_edit::{self, EdgeEnds};
+use crate::math::Vector2;
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct BubbleGpuEntity {
+    pub m: f32,
+    // if i don't pad a float here, the os will fill a random number here anyway, and it ruins the buffer size calculation
+    // see https://renderdoc.org/vkspec_chunked/chap16.html#interfaces-resources-layout
+    // the slot carries the BUBBLE_* flags
+    pub flags: u32,
+    pub p: [f32; 2],
+    pub v: [f32; 2],
+    pub a: [f32; 2],
+}
+unsafe impl bytemuck::Pod for BubbleGpuEntity {}
+unsafe impl bytemuck::Zeroable for BubbleGpuEntity {}
+
+// the position pass leaves the bubble where it is
+pub const BUBBLE_PINNED: u32 = 1;
+
+pub struct GpuForcelayout {
+    device: Arc<wgpu::Device>,
+    queue: Arc<wgpu::Queue>,
+    adjacency_offset_buffer: GrowableBuffer,
+    neighbour_buffer: GrowableBuffer,
+    edge_buffer: GrowableBuffer,
+    bounds_buffer: Buffer,
+    // the bounds are read back without waiting, a copy is mapped once the gpu gets to it
+    bounds_staging_buffer: Buffer,
+    bounds_mapped: Arc<AtomicBool>,
+    // a failed mapping has to free the staging buffer for the next copy all the same
+    bounds_failed: Arc<AtomicBool>,
+    bounds_in_flight: bool,
+    bubble_buffer: GrowableBuffer,
+    globals_buffer: Buffer,
+    staging_buffer: GrowableBuffer,
+    // a bubble on its way to another slot, a buffer can't be copied onto itself
+    swap_buffer: Buffer,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: BindGroup,
+    // the gpu only keeps the adjacency, the edges are kept here to rebuild it when they change
+    edges: Vec<EdgeEntity>,
+    compute_repulsion_pipeline: wgpu::ComputePipeline,
+    compute_pull_pipeline: wgpu::ComputePipeline,
+    compute_position_pipeline: wgpu::ComputePipeline,
+    compute_bounds_pipeline: wgpu::ComputePipeline,
+    bubble_count: u32,
+    edge_count: u32,
+    workgroup_size: u32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct Globals {
+    bubble_count: u32,
+    edge_count: u32,
+    collision_padding: f32,
+    _pad1: u32,
+}
+unsafe impl bytemuck::Pod for Globals {}
+unsafe impl bytemuck::Zeroable for Globals {}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct EdgeEntity {
+    pub from: u32,
+    pub to: u32,
+    pub weight: f32,
+    pub _pad1: f32,
+}
+unsafe impl bytemuck::Pod for EdgeEntity {}
+unsafe impl bytemuck::Zeroable for EdgeEntity {}
+
+impl EdgeEnds for EdgeEntity {
+    fn ends(&self) -> (usize, usize) {
+        (self.from as usize, self.to as usize)
+    }
+
+    fn set_ends(&mut self, from: usize, to: usize) {
+        self.from = from as u32;
+        self.to = to as u32;
+    }
+}
+
+// the min and max corners of the rect around all the bubbles
+type BoundsEntity = [f32; 4];
+
+// the adjacency buffers are std430, so unlike the std140 arrays they're tightly packed
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct NeighbourEntity {
+    index: u32,
+    weight: f32,
+}
+unsafe impl bytemuck::Pod for NeighbourEntity {}
+unsafe impl bytemuck::Zeroable for NeighbourEntity {}
+// unsafe impl bytemuck::Pod for GpuForcelayout {}
+// unsafe impl bytemuck::Zeroable for GpuForcelayout {}
+
+// the sizes the compute shaders are built for, see build_shaders.sh
+const WORKGROUP_SIZES: [u32; 3] = [256, 128, 64];
+
+// the largest workgroup the device can run, the repulsion tile and the bounds reduction take a vec4 per invocation
+fn pick_workgroup_size(limits: &wgpu::Limits) -> u32 {
+    WORKGROUP_SIZES
+        .iter()
+        .copied()
+        .find(|&size| {
+            size <= limits.max_compute_invocations_per_workgroup
+                && size <= limits.max_compute_workgroup_size_x
+                && size * 16 <= limits.max_compute_workgroup_storage_size
+        })
+        .unwrap_or(64)
+}
+
+macro_rules! compute_shader_modules {
+    ($device:expr, $size:literal) => {
+        [
+            $device.create_shader_module(wgpu::include_spirv!(concat!("./../shaders/compute_repulsion_", $size, ".comp.spv"))),
+            $device.create_shader_module(wgpu::include_spirv!(concat!("./../shaders/compute_pull_", $size, ".comp.spv"))),
+            $device.create_shader_module(wgpu::include_spirv!(concat!("./../shaders/compute_position_", $size, ".comp.spv"))),
+            $device.create_shader_module(wgpu::include_spirv!(concat!("./../shaders/compute_bounds_", $size, ".comp.spv"))),
+        ]
+    };
+}
+
+fn create_buffer(device: &wgpu::Device, size: u64, usage: BufferUsages) -> Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size,
+        // usage: BufferUsages::COPY_DST | BufferUsages::COPY_SRC | BufferUsages::STORAGE | BufferUsages::MAP_READ,
+        usage,
+        mapped_at_creation: false,
+    })
+}
+
+// a buffer that doubles its capacity when it runs out of room, the same way a Vec does
+struct GrowableBuffer {
+    buffer: Buffer,
+    capacity: u64,
+    usage: BufferUsages,
+}
+
+impl GrowableBuffer {
+    fn new(device: &wgpu::Device, size: u64, usage: BufferUsages) -> Self {
+        Self {
+            buffer: create_buffer(device, size, usage),
+            capacity: size,
+            usage,
+        }
+    }
+
+    // makes room for `size` bytes, the first `keep` bytes are carried over on the gpu when it has to grow.
+    // returns whether the buffer was replaced, the bind groups pointing at it are stale then
+    fn reserve(&mut self, device: &wgpu::Device, encoder: &mut CommandEncoder, size: u64, keep: u64) -> bool {
+        if size <= self.capacity {
+            return false;
+        }
+        let capacity = size.max(self.capacity * 2);
+        let buffer = create_buffer(device, capacity, self.usage);
+        if keep > 0 {
+            encoder.copy_buffer_to_buffer(&self.buffer, 0, &buffer, 0, keep);
+        }
+        self.buffer = buffer;
+        self.capacity = capacity;
+        true
+    }
+}
+
+// the compute passes read and write every binding, the arrays in them are sized by the globals
+fn create_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    bubble_buffer: &Buffer,
+    globals_buffer: &Buffer,
+    adjacency_offset_buffer: &Buffer,
+    neighbour_buffer: &Buffer,
+    bounds_buffer: &Buffer,
+) -> BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: None,
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry{ binding: 0, resource: bubble_buffer.as_entire_binding() },
+            wgpu::BindGroupEntry{ binding: 1, resource: globals_buffer.as_entire_binding() },
+            wgpu::BindGroupEntry{ binding: 2, resource: adjacency_offset_buffer.as_entire_binding() },
+            wgpu::BindGroupEntry{ binding: 3, resource: neighbour_buffer.as_entire_binding() },
+            wgpu::BindGroupEntry{ binding: 4, resource: bounds_buffer.as_entire_binding() },
+        ],
+    })
+}
+
+impl GpuForcelayout {
+    /// Runs the layout on a device of its own, for when nothing is drawn from the buffers.
+    pub fn new(bubbles: Vec<BubbleGpuEntity>, edges: Vec<EdgeEntity>) -> Self {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor{
+            backends: wgpu::Backends::PRIMARY,
+            dx12_shader_compiler: wgpu::Dx12Compiler::default(),
+        });
+        // create an adapter
+        let adapter = block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::default(),
+            compatible_surface: None,
+            force_fallback_adapter: false,
+        }))
+        .unwrap();
+        // create a device and a queue
+        let (device, queue) = block_on(adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                label: None,
+                features: wgpu::Features::default(),
+                limits: wgpu::Limits::default(),
+            },
+            None,
+        ))
+        .unwrap();
+
+        Self::with_device(Arc::new(device), Arc::new(queue), bubbles, edges)
+    }
+
+    /// Runs the layout on the device the viewer renders with, so the bubble buffer can be drawn from directly.
+    pub fn with_device(device: Arc<wgpu::Device>, queue: Arc<wgpu::Queue>, bubbles: Vec<BubbleGpuEntity>, edges: Vec<EdgeEntity>) -> Self {
+        let workgroup_size = pick_workgroup_size(&device.limits());
+        let [compute_repulsion_module, compute_pull_module, compute_position_module, compute_bounds_module] = match workgroup_size {
+            256 => compute_shader_modules!(device, "256"),
+            128 => compute_shader_modules!(device, "128"),
+            _ => compute_shader_modules!(device, "64"),
+        };
+
+        // a binding can't be empty, every buffer keeps room for at least one entry so the graph can start out empty.
+        // they all grow on their own as nodes and edges are added, see `GrowableBuffer`
+        let bubble_buffer_size = (size_of::<BubbleGpuEntity>() * bubbles.len().max(1)) as u64;
+        let adjacency_offset_buffer_size = (size_of::<u32>() * (bubbles.len() + 1)) as u64;
+        let neighbour_buffer_size = (size_of::<NeighbourEntity>() * (2 * edges.len()).max(1)) as u64;
+        let globals_buffer_size = size_of::<Globals>() as u64;
+        // the edges aren't needed by the simulation, they're kept on the gpu for drawing
+        let edge_buffer_size = (size_of::<EdgeEntity>() * edges.len().max(1)) as u64;
+        let bounds_buffer_size = size_of::<BoundsEntity>() as u64;
+        let edge_buffer = GrowableBuffer::new(&device, edge_buffer_size, BufferUsages::STORAGE | BufferUsages::COPY_DST | BufferUsages::COPY_SRC);
+        let bounds_buffer = create_buffer(&device, bounds_buffer_size, BufferUsages::STORAGE | BufferUsages::COPY_SRC);
+        let bounds_staging_buffer = create_buffer(&device, bounds_buffer_size, BufferUsages::COPY_DST | BufferUsages::MAP_READ);
+        let adjacency_offset_buffer = GrowableBuffer::new(&device, adjacency_offset_buffer_size, BufferUsages::STORAGE | BufferUsages::COPY_DST);
+        let neighbour_buffer = GrowableBuffer::new(&device, neighbour_buffer_size, BufferUsages::STORAGE | BufferUsages::COPY_DST);
+        let bubble_buffer = GrowableBuffer::new(&device, bubble_buffer_size, BufferUsages::COPY_DST | BufferUsages::COPY_SRC | BufferUsages::STORAGE);
+        let globals_buffer = create_buffer(&device, globals_buffer_size, BufferUsages::COPY_SRC| BufferUsages::COPY_DST | BufferUsages::STORAGE);
+        let staging_buffer = GrowableBuffer::new(&device, bubble_buffer_size, BufferUsages::COPY_DST | BufferUsages::MAP_READ);
+        let swap_buffer = create_buffer(&device, size_of::<BubbleGpuEntity>() as u64, BufferUsages::COPY_DST | BufferUsages::COPY_SRC);
+
+        // the minimum sizes are a single entry, so the buffers can be swapped for bigger ones under the same layout
+        let create_bind_group_layout_desc = |binding: u32, size: usize| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: wgpu::BufferSize::new(size as u64),
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                create_bind_group_layout_desc(0, size_of::<BubbleGpuEntity>()),
+                create_bind_group_layout_desc(1, size_of::<Globals>()),
+                create_bind_group_layout_desc(2, size_of::<u32>()),
+                create_bind_group_layout_desc(3, size_of::<NeighbourEntity>()),
+                create_bind_group_layout_desc(4, size_of::<BoundsEntity>()),
+            ],
+        });
+
+        let bind_group = create_bind_group(
+            &device,
+            &bind_group_layout,
+            &bubble_buffer.buffer,
+            &globals_buffer,
+            &adjacency_offset_buffer.buffer,
+            &neighbour_buffer.buffer,
+            &bounds_buffer,
+        );
+
+        let create_compute_pipeline = |module: &wgpu::ShaderModule| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: None,
+                layout: Some(
+                    &device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                        label: None,
+                        bind_group_layouts: &[&bind_group_layout],
+                        push_constant_ranges: &[],
+                    }),
+                ),
+                module,
+                entry_point: "main",
+            })
+        };
+
+        let compute_repulsion_pipeline = create_compute_pipeline(&compute_repulsion_module);
+        let compute_pull_pipeline = create_compute_pipeline(&compute_pull_module);
+        let compute_position_pipeline = create_compute_pipeline(&compute_position_module);
+        let compute_bounds_pipeline = create_compute_pipeline(&compute_bounds_module);
+
+        let mut gpu_forcelayout = Self {
+            device,
+            queue,
+            adjacency_offset_buffer,
+            neighbour_buffer,
+            edge_buffer,
+            bounds_buffer,
+            bounds_staging_buffer,
+            bounds_mapped: Arc::new(AtomicBool::new(false)),
+            bounds_failed: Arc::new(AtomicBool::new(false)),
+            bounds_in_flight: false,
+            bubble_buffer,
+            globals_buffer,
+            staging_buffer,
+            swap_buffer,
+            bind_group_layout,
+            bind_group,
+            edges: vec![],
+            compute_repulsion_pipeline,
+            compute_pull_pipeline,
+            compute_position_pipeline,
+            compute_bounds_pipeline,
+            bubble_count: 0,
+            edge_count: 0,
+            workgroup_size,
+        };
+        gpu_forcelayout.add_nodes(&bubbles);
+        gpu_forcelayout.add_edges(&edges);
+        gpu_forcelayout
+    }
+
+    async fn get_compute_result(&self, size: u64) -> Vec<BubbleGpuEntity> {
+        // Note that we're not calling `.await` here.
+        let staging_buffer = &self.staging_buffer.buffer;
+        let buffer_slice = staging_buffer.slice(..size);
+        // Gets the future representing when `staging_buffer` can be read from
+        let buffer_future = buffer_slice.map_async(wgpu::MapMode::Read, Result::unwrap);
+
+        // Poll the device in a blocking manner so that our future resolves.
+        // In an actual application, `device.poll(...)` should
+        // be called in an event loop or on another thread.
+        self.device.poll(wgpu::Maintain::Wait);
+
+        // Awaits until `buffer_future` can be read from
+        // if let Ok(()) = buffer_future.await {
+            // Gets contents of buffer
+            let data = buffer_slice.get_mapped_range();
+            // Since contents are got in bytes, this converts these bytes back to u32
+            let result = data
+                .chunks_exact(size_of::<BubbleGpuEntity>())
+                .map(|entity_bytes| *bytemuck::from_bytes::<BubbleGpuEntity>(&entity_bytes))
+                .collect();
+
+            // With the current interface, we have to make sure all mapped views are
+            // dropped before we unmap the buffer.
+            drop(data);
+            staging_buffer.unmap();
+            result
+        // } else {
+        //     panic!("failed to run compute on gpu!")
+        // }
+    }
+
+    fn create_encoder(&self) -> CommandEncoder {
+        self.device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default())
+    }
+
+    // rebuilds the adjacency for the current bubbles and edges, and writes the edges from `first_edge` on.
+    // returns whether a buffer of the compute bind group was replaced
+    fn upload_edges(&mut self, encoder: &mut CommandEncoder, first_edge: usize) -> bool {
+        let adjacency = Adjacency::new(
+            self.bubble_count as usize,
+            self.edges.iter().map(|e| (e.from as usize, e.to as usize, e.weight)),
+        );
+        let neighbours: Vec<NeighbourEntity> = adjacency
+            .neighbours
+            .iter()
+            .zip(&adjacency.weights)
+            .map(|(&index, &weight)| NeighbourEntity { index, weight })
+            .collect();
+
+        let offsets_size = (size_of::<u32>() * adjacency.offsets.len()) as u64;
+        let neighbours_size = (size_of::<NeighbourEntity>() * neighbours.len()) as u64;
+        let edge_size = size_of::<EdgeEntity>() as u64;
+        let mut replaced = self.adjacency_offset_buffer.reserve(&self.device, encoder, offsets_size, 0);
+        replaced |= self.neighbour_buffer.reserve(&self.device, encoder, neighbours_size, 0);
+        // the edges before `first_edge` didn't change, they're carried over if the buffer grows
+        self.edge_buffer.reserve(
+            &self.device,
+            encoder,
+            edge_size * self.edges.len() as u64,
+            edge_size * first_edge as u64,
+        );
+
+        self.queue.write_buffer(&self.adjacency_offset_buffer.buffer, 0, bytemuck::cast_slice(&adjacency.offsets));
+        if !neighbours.is_empty() {
+            self.queue.write_buffer(&self.neighbour_buffer.buffer, 0, bytemuck::cast_slice(&neighbours));
+        }
+        if first_edge < self.edges.len() {
+            self.queue.write_buffer(
+                &self.edge_buffer.buffer,
+                edge_size * first_edge as u64,
+                bytemuck::cast_slice(&self.edges[first_edge..]),
+            );
+        }
+        self.edge_count = self.edges.len() as u32;
+        replaced
+    }
+
+    // submits the copies of an edit together with the new counts
+    fn finish_edit(&mut self, encoder: CommandEncoder, replaced: bool) {
+        if replaced {
+            self.bind_group = create_bind_group(
+                &self.device,
+                &self.bind_group_layout,
+                &self.bubble_buffer.buffer,
+                &self.globals_buffer,
+                &self.adjacency_offset_buffer.buffer,
+                &self.neighbour_buffer.buffer,
+                &self.bounds_buffer,
+            );
+        }
+        let globals = Globals {
+            bubble_count: self.bubble_count,
+            edge_count: self.edge_count,
+            collision_padding: COLLISION_PADDING,
+            _pad1: 0,
+        };
+        self.queue.write_buffer(&self.globals_buffer, 0, bytemuck::bytes_of(&globals));
+        self.queue.submit(Some(encoder.finish()));
+    }
+
+    /// Appends the bubbles, the ones already in the simulation keep their place and their motion.
+    pub fn add_nodes(&mut self, bubbles: &[BubbleGpuEntity]) {
+        let mut encoder = self.create_encoder();
+        let bubble_size = size_of::<BubbleGpuEntity>() as u64;
+        let old_size = bubble_size * self.bubble_count as u64;
+        let new_size = old_size + bubble_size * bubbles.len() as u64;
+        let mut replaced = self.bubble_buffer.reserve(&self.device, &mut encoder, new_size, old_size);
+        self.staging_buffer.reserve(&self.device, &mut encoder, new_size, 0);
+        if !bubbles.is_empty() {
+            self.queue.write_buffer(&self.bubble_buffer.buffer, old_size, bytemuck::cast_slice(bubbles));
+        }
+        self.bubble_count += bubbles.len() as u32;
+
+        // the new bubbles get empty rows in the adjacency
+        let edge_count = self.edges.len();
+        replaced |= self.upload_edges(&mut encoder, edge_count);
+        self.finish_edit(encoder, replaced);
+    }
+
+    /// Removes the bubbles and every edge touching them the way `graph_edit::remove_nodes` does,
+    /// so a cpu copy of the graph stays in step by calling it with the same indices.
+    pub fn remove_nodes(&mut self, indices: &[u32]) {
+        let mut encoder = self.create_encoder();
+        let bubble_size = size_of::<BubbleGpuEntity>() as u64;
+        let indices: Vec<usize> = indices.iter().map(|&i| i as usize).collect();
+
+        // the last bubble is copied into the freed slot right on the gpu, the positions are never read back
+        let bubble_buffer = &self.bubble_buffer.buffer;
+        let swap_buffer = &self.swap_buffer;
+        let bubble_count = graph_edit::remove_nodes(self.bubble_count as usize, &mut self.edges, &indices, |from, to| {
+            encoder.copy_buffer_to_buffer(bubble_buffer, from as u64 * bubble_size, swap_buffer, 0, bubble_size);
+            encoder.copy_buffer_to_buffer(swap_buffer, 0, bubble_buffer, to as u64 * bubble_size, bubble_size);
+        });
+        self.bubble_count = bubble_count as u32;
+
+        let replaced = self.upload_edges(&mut encoder, 0);
+        self.finish_edit(encoder, replaced);
+    }
+
+    /// Appends the edges.
+    pub fn add_edges(&mut self, edges: &[EdgeEntity]) {
+        let mut encoder = self.create_encoder();
+        let first_edge = self.edges.len();
+        self.edges.extend_from_slice(edges);
+        let replaced = self.upload_edges(&mut encoder, first_edge);
+        self.finish_edit(encoder, replaced);
+    }
+
+    /// Removes the edges the way `graph_edit::remove_edges` does.
+    pub fn remove_edges(&mut self, indices: &[u32]) {
+        let mut encoder = self.create_encoder();
+        let indices: Vec<usize> = indices.iter().map(|&i| i as usize).collect();
+        graph_edit::remove_edges(&mut self.edges, &indices);
+        let replaced = self.upload_edges(&mut encoder, 0);
+        self.finish_edit(encoder, replaced);
+    }
+
+    /// Replaces all the edges.
+    pub fn set_edges(&mut self, edges: &[EdgeEntity]) {
+        let mut encoder = self.create_encoder();
+        self.edges = edges.to_vec();
+        let replaced = self.upload_edges(&mut encoder, 0);
+        self.finish_edit(encoder, replaced);
+    }
+
+    /// Overwrites the bubbles from `first` on in place, for when they were moved on the cpu.
+    pub fn write_nodes(&self, first: u32, bubbles: &[BubbleGpuEntity]) {
+        assert!(first as usize + bubbles.len() <= self.bubble_count as usize);
+        let offset = (size_of::<BubbleGpuEntity>() * first as usize) as u64;
+        self.queue.write_buffer(&self.bubble_buffer.buffer, offset, bytemuck::cast_slice(bubbles));
+    }
+
+    /// Encodes `n` iterations of repulsion, pull and position into a single submission.
+    /// Nothing is read back, the results stay in the bubble buffer until `read_bubbles` is called.
+    pub fn step(&mut self, n: u32) {
+        let mut encoder = self.create_encoder();
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+            pass.set_bind_group(0, &self.bind_group, &[]);
+
+            let workgroup_count = self.bubble_count.div_ceil(self.workgroup_size);
+
+            // every dispatch sees the writes of the previous one, so the iterations can just be chained
+            for _ in 0..n {
+                pass.set_pipeline(&self.compute_repulsion_pipeline);
+                pass.dispatch_workgroups(workgroup_count, 1, 1);
+
+                pass.set_pipeline(&self.compute_pull_pipeline);
+                pass.dispatch_workgroups(workgroup_count, 1, 1);
+
+                pass.set_pipeline(&self.compute_position_pipeline);
+                pass.dispatch_workgroups(workgroup_count, 1, 1);
+            }
+
+            // a single workgroup reduces all the positions
+            pass.set_pipeline(&self.compute_bounds_pipeline);
+            pass.dispatch_workgroups(1, 1, 1);
+        }
+
+        // the staging buffer can't be written while it's mapped, those bounds are skipped
+        let read_bounds = !self.bounds_in_flight;
+        if read_bounds {
+            let size = size_of::<BoundsEntity>() as u64;
+            encoder.copy_buffer_to_buffer(&self.bounds_buffer, 0, &self.bounds_staging_buffer, 0, size);
+        }
+        self.queue.submit(Some(encoder.finish()));
+
+        if read_bounds {
+            self.bounds_in_flight = true;
+            let mapped = self.bounds_mapped.clone();
+            let failed = self.bounds_failed.clone();
+            self.bounds_staging_buffer.slice(..).map_async(wgpu::MapMode::Read, move |result| match result {
+                Ok(()) => mapped.store(true, Ordering::Release),
+                Err(error) => {
+                    eprintln!("couldn't read the bounds back: {}", error);
+                    failed.store(true, Ordering::Release);
+                }
+            });
+        }
+    }
+
+    /// The bounds of all the bubbles, as `(min, max)`, once a step's copy of them has arrived.
+    /// It doesn't wait for the gpu, so it's `None` most frames and the bounds are a few steps old.
+    pub fn poll_bounds(&mut self) -> Option<(Vector2, Vector2)> {
+        self.device.poll(wgpu::Maintain::Poll);
+        if self.bounds_failed.swap(false, Ordering::Acquire) {
+            self.bounds_in_flight = false;
+            return None;
+        }
+        if !self.bounds_mapped.swap(false, Ordering::Acquire) {
+            return None;
+        }
+        let bounds = {
+            let data = self.bounds_staging_buffer.slice(..).get_mapped_range();
+            *bytemuck::from_bytes::<BoundsEntity>(&data)
+        };
+        self.bounds_staging_buffer.unmap();
+        self.bounds_in_flight = false;
+        Some((Vector2 { x: bounds[0], y: bounds[1] }, Vector2 { x: bounds[2], y: bounds[3] }))
+    }
+
+    // the buffers the viewer draws from, they're always in sync with the last step
+    // they're replaced when they grow, so don't hold on to them across an edit
+    pub fn bubble_buffer(&self) -> &Buffer {
+        &self.bubble_buffer.buffer
+    }
+
+    pub fn edge_buffer(&self) -> &Buffer {
+        &self.edge_buffer.buffer
+    }
+
+    pub fn bubble_count(&self) -> u32 {
+        self.bubble_count
+    }
+
+    pub fn edge_count(&self) -> u32 {
+        self.edge_count
+    }
+
+    /// Copies the bubbles back to the cpu, it waits for all the submitted steps to finish.
+    pub async fn read_bubbles(&self) -> Vec<BubbleGpuEntity> {
+        let size = (size_of::<BubbleGpuEntity>() * self.bubble_count as usize) as u64;
+        if size == 0 {
+            return vec![];
+        }
+        let mut encoder = self.create_encoder();
+        encoder.copy_buffer_to_buffer(&self.bubble_buffer.buffer, 0, &self.staging_buffer.buffer, 0, size);
+        self.queue.submit(Some(encoder.finish()));
+        self.get_compute_result(size).await
+    }
+}
@@ -0,0 +1,99 @@
+/// The two bubbles an edge connects, so the removals below work on the gpu entities and on `Edge` alike.
+pub trait EdgeEnds {
+    fn ends(&self) -> (usize, usize);
+    fn set_ends(&mut self, from: usize, to: usize);
+}
+
+/// The indices from highest to lowest without duplicates, the order `Vec::swap_remove` can use them in.
+pub fn removal_order(indices: &[usize]) -> Vec<usize> {
+    let mut order = indices.to_vec();
+    order.sort_unstable_by(|a, b| b.cmp(a));
+    order.dedup();
+    order
+}
+
+/// Removes the edges like `Vec::swap_remove` does, highest index first.
+pub fn remove_edges<E>(edges: &mut Vec<E>, indices: &[usize]) {
+    for i in removal_order(indices) {
+        edges.swap_remove(i);
+    }
+}
+
+/// Removes the nodes like `Vec::swap_remove` does, highest index first, together with every edge touching them.
+/// The nodes themselves aren't touched: `move_node(from, to)` is called whenever the last node takes a freed slot,
+/// and the new node count is returned, so the caller can move its own data and truncate it.
+pub fn remove_nodes<E: EdgeEnds>(
+    node_count: usize,
+    edges: &mut Vec<E>,
+    indices: &[usize],
+    mut move_node: impl FnMut(usize, usize),
+) -> usize {
+    let mut removed = vec![false; node_count];
+    for &i in indices {
+        removed[i] = true;
+    }
+    let touching: Vec<usize> = edges
+        .iter()
+        .enumerate()
+        .filter(|(_, e)| {
+            let (from, to) = e.ends();
+            removed[from] || removed[to]
+        })
+        .map(|(i, _)| i)
+        .collect();
+    remove_edges(edges, &touching);
+
+    // the node every slot holds, by its index before the removal
+    let mut held: Vec<usize> = (0..node_count).collect();
+    let mut count = node_count;
+    for i in removal_order(indices) {
+        let last = count - 1;
+        if i != last {
+            move_node(last, i);
+            held[i] = held[last];
+        }
+        count -= 1;
+    }
+
+    // the edges left only touch the nodes that stayed, they're renumbered in one go
+    let mut new_index = vec![0; node_count];
+    for (slot, &node) in held[..count].iter().enumerate() {
+        new_index[node] = slot;
+    }
+    for edge in edges.iter_mut() {
+        let (from, to) = edge.ends();
+        edge.set_ends(new_index[from], new_index[to]);
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_graph::graph;
+
+    #[test]
+    fn remove_nodes_keeps_the_edges_between_the_same_bubbles() {
+        let (mut bubbles, mut edges) = graph(6, &[(0, 1), (1, 2), (2, 5), (3, 4), (4, 5), (0, 5)]);
+        let count = remove_nodes(bubbles.len(), &mut edges, &[1, 3], |from, to| bubbles.swap(from, to));
+        bubbles.truncate(count);
+
+        assert_eq!(count, 4);
+        let mut labels: Vec<&str> = bubbles.iter().map(|b| b.label.as_str()).collect();
+        labels.sort_unstable();
+        assert_eq!(labels, ["0", "2", "4", "5"]);
+        let mut ends: Vec<(&str, &str)> = edges
+            .iter()
+            .map(|e| (bubbles[e.from].label.as_str(), bubbles[e.to].label.as_str()))
+            .collect();
+        ends.sort_unstable();
+        assert_eq!(ends, [("0", "5"), ("2", "5"), ("4", "5")]);
+    }
+
+    #[test]
+    fn remove_edges_swaps_the_last_one_in() {
+        let mut edges = vec![0, 1, 2, 3, 4];
+        remove_edges(&mut edges, &[1, 3, 1]);
+        assert_eq!(edges, [0, 4, 2]);
+    }
+}
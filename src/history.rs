@@ -0,0 +1,355 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::bubble::Bubble;
+use crate::edge::Edge;
+use crate::graph_edit::{self, removal_order};
+use crate::math::Vector2;
+use crate::mesh::Mesh;
+
+// the oldest commands are forgotten past either limit, so a long session or a big removal can't hold on to much memory
+const HISTORY_COMMAND_LIMIT: usize = 200;
+const HISTORY_ENTITY_LIMIT: usize = 100_000;
+
+/// What's kept of a bubble to bring it back, the meshes are shared with the other bubbles anyway.
+#[derive(Clone)]
+pub struct BubbleData {
+    pub position: Vector2,
+    pub size: f32,
+    pub label: String,
+    pub pinned: bool,
+    pub attributes: BTreeMap<String, f32>,
+}
+
+impl BubbleData {
+    fn of(bubble: &Bubble) -> Self {
+        BubbleData {
+            position: bubble.position,
+            size: bubble.size,
+            label: bubble.label.clone(),
+            pinned: bubble.pinned,
+            attributes: bubble.attributes.clone(),
+        }
+    }
+
+    fn to_bubble(&self) -> Bubble {
+        Bubble {
+            position: self.position,
+            size: self.size,
+            v: Vector2::new(),
+            a: Vector2::new(),
+            meshes: [Mesh::default(), Mesh::default(), Mesh::default()],
+            label: self.label.clone(),
+            pinned: self.pinned,
+            attributes: self.attributes.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct EdgeData {
+    pub from: usize,
+    pub to: usize,
+    pub weight: f32,
+}
+
+impl EdgeData {
+    fn of(edge: &Edge) -> Self {
+        EdgeData {
+            from: edge.from,
+            to: edge.to,
+            weight: edge.weight,
+        }
+    }
+
+    fn to_edge(self) -> Edge {
+        Edge {
+            from: self.from,
+            to: self.to,
+            weight: self.weight,
+            pull_force: 0.0,
+            mesh: Mesh::default(),
+            route: vec![],
+        }
+    }
+}
+
+/// An edit of the graph that knows how to take itself back.
+pub enum Command {
+    AddBubble(BubbleData),
+    AddEdge(EdgeData),
+    // everything that went, with the index it had at the time, in the order it went:
+    // the edges first, then the bubbles, each one swap-removed like `graph_edit` does
+    Remove {
+        edges: Vec<(usize, EdgeData)>,
+        bubbles: Vec<(usize, BubbleData)>,
+    },
+    SetPinned {
+        indices: Vec<usize>,
+        pinned: bool,
+        previous: Vec<bool>,
+    },
+    // the bubbles dragged together, where each one was and where it ended up
+    Move {
+        indices: Vec<usize>,
+        from: Vec<Vector2>,
+        to: Vec<Vector2>,
+    },
+}
+
+impl Command {
+    /// Removes the edges and the bubbles, and the edges left touching the bubbles.
+    pub fn remove(bubbles: &[Bubble], edges: &[Edge], bubble_indices: &[usize], edge_indices: &[usize]) -> Self {
+        // the removal is played through on a copy of the edges to know which ones go and in what order
+        let mut remaining: Vec<EdgeData> = edges.iter().map(EdgeData::of).collect();
+        let mut removed_edges = vec![];
+        for i in removal_order(edge_indices) {
+            removed_edges.push((i, remaining.swap_remove(i)));
+        }
+
+        let mut removed = vec![false; bubbles.len()];
+        for &i in bubble_indices {
+            removed[i] = true;
+        }
+        let touching: Vec<usize> = remaining
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| removed[e.from] || removed[e.to])
+            .map(|(i, _)| i)
+            .collect();
+        for i in removal_order(&touching) {
+            removed_edges.push((i, remaining.swap_remove(i)));
+        }
+
+        // from the highest index down, so every bubble is still in its place when it's removed
+        let removed_bubbles = removal_order(bubble_indices)
+            .into_iter()
+            .map(|i| (i, BubbleData::of(&bubbles[i])))
+            .collect();
+
+        Command::Remove {
+            edges: removed_edges,
+            bubbles: removed_bubbles,
+        }
+    }
+
+    /// Pins the bubbles, or unpins them if they're all pinned already.
+    pub fn toggle_pinned(bubbles: &[Bubble], indices: &[usize]) -> Self {
+        let previous: Vec<bool> = indices.iter().map(|&i| bubbles[i].pinned).collect();
+        Command::SetPinned {
+            indices: indices.to_vec(),
+            pinned: !previous.iter().all(|&pinned| pinned),
+            previous,
+        }
+    }
+
+    // how many bubbles and edges the command holds on to
+    fn cost(&self) -> usize {
+        match self {
+            Command::Remove { edges, bubbles } => edges.len() + bubbles.len(),
+            Command::SetPinned { indices, .. } | Command::Move { indices, .. } => indices.len(),
+            _ => 1,
+        }
+    }
+
+    fn apply(&self, bubbles: &mut Vec<Bubble>, edges: &mut Vec<Edge>) {
+        match self {
+            Command::AddBubble(data) => bubbles.push(data.to_bubble()),
+            Command::AddEdge(data) => edges.push(data.to_edge()),
+            Command::Remove {
+                edges: removed_edges,
+                bubbles: removed_bubbles,
+            } => {
+                for &(i, _) in removed_edges.iter() {
+                    edges.swap_remove(i);
+                }
+                let indices: Vec<usize> = removed_bubbles.iter().map(|&(i, _)| i).collect();
+                let bubble_count = graph_edit::remove_nodes(bubbles.len(), edges, &indices, |from, to| {
+                    bubbles.swap(from, to);
+                });
+                bubbles.truncate(bubble_count);
+            }
+            Command::SetPinned { indices, pinned, .. } => {
+                for &i in indices.iter() {
+                    bubbles[i].pinned = *pinned;
+                }
+            }
+            Command::Move { indices, to, .. } => {
+                for (&i, &position) in indices.iter().zip(to.iter()) {
+                    bubbles[i].position = position;
+                    bubbles[i].v = Vector2::new();
+                }
+            }
+        }
+    }
+
+    fn revert(&self, bubbles: &mut Vec<Bubble>, edges: &mut Vec<Edge>) {
+        match self {
+            Command::AddBubble(_) => {
+                bubbles.pop();
+            }
+            Command::AddEdge(_) => {
+                edges.pop();
+            }
+            Command::Remove {
+                edges: removed_edges,
+                bubbles: removed_bubbles,
+            } => {
+                // every swap-remove is undone the other way round: the removed one goes back to the end
+                // and trades places with the one that took its slot
+                let bubble_count = bubbles.len();
+                // the bubble every slot holds, by its index before the revert, `None` for the ones coming back
+                let mut held: Vec<Option<usize>> = (0..bubble_count).map(Some).collect();
+                for (i, data) in removed_bubbles.iter().rev() {
+                    let last = bubbles.len();
+                    bubbles.push(data.to_bubble());
+                    held.push(None);
+                    if *i != last {
+                        bubbles.swap(*i, last);
+                        held.swap(*i, last);
+                    }
+                }
+                let mut new_index = vec![0; bubble_count];
+                for (slot, node) in held.iter().enumerate() {
+                    if let Some(node) = node {
+                        new_index[*node] = slot;
+                    }
+                }
+                for edge in edges.iter_mut() {
+                    edge.from = new_index[edge.from];
+                    edge.to = new_index[edge.to];
+                }
+                for (i, data) in removed_edges.iter().rev() {
+                    let last = edges.len();
+                    edges.push(data.to_edge());
+                    if *i != last {
+                        edges.swap(*i, last);
+                    }
+                }
+            }
+            Command::SetPinned { indices, previous, .. } => {
+                for (&i, &pinned) in indices.iter().zip(previous.iter()) {
+                    bubbles[i].pinned = pinned;
+                }
+            }
+            Command::Move { indices, from, .. } => {
+                for (&i, &position) in indices.iter().zip(from.iter()) {
+                    bubbles[i].position = position;
+                    bubbles[i].v = Vector2::new();
+                }
+            }
+        }
+    }
+}
+
+/// The edits that can be undone and redone.
+pub struct History {
+    undo_stack: VecDeque<Command>,
+    redo_stack: Vec<Command>,
+    // the cost of everything in the undo stack
+    entity_count: usize,
+}
+
+impl History {
+    pub fn new() -> Self {
+        History {
+            undo_stack: VecDeque::new(),
+            redo_stack: vec![],
+            entity_count: 0,
+        }
+    }
+
+    /// Carries the command out and remembers it.
+    pub fn execute(&mut self, command: Command, bubbles: &mut Vec<Bubble>, edges: &mut Vec<Edge>) {
+        command.apply(bubbles, edges);
+        self.record(command);
+    }
+
+    /// Remembers a command that's already been carried out, like a drag that moved the bubble as it went.
+    /// Whatever was undone can't be redone after a new edit.
+    pub fn record(&mut self, command: Command) {
+        self.redo_stack.clear();
+        self.push_undo(command);
+    }
+
+    pub fn undo(&mut self, bubbles: &mut Vec<Bubble>, edges: &mut Vec<Edge>) -> bool {
+        match self.undo_stack.pop_back() {
+            Some(command) => {
+                self.entity_count -= command.cost();
+                command.revert(bubbles, edges);
+                self.redo_stack.push(command);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn redo(&mut self, bubbles: &mut Vec<Bubble>, edges: &mut Vec<Edge>) -> bool {
+        match self.redo_stack.pop() {
+            Some(command) => {
+                command.apply(bubbles, edges);
+                self.push_undo(command);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn push_undo(&mut self, command: Command) {
+        self.entity_count += command.cost();
+        self.undo_stack.push_back(command);
+        // the latest command is always kept, however big it is
+        while self.undo_stack.len() > 1
+            && (self.undo_stack.len() > HISTORY_COMMAND_LIMIT || self.entity_count > HISTORY_ENTITY_LIMIT)
+        {
+            if let Some(oldest) = self.undo_stack.pop_front() {
+                self.entity_count -= oldest.cost();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_graph::graph;
+
+    // the bubbles by label and the edges by the labels they connect, in their order
+    fn snapshot(bubbles: &[Bubble], edges: &[Edge]) -> (Vec<String>, Vec<(String, String)>) {
+        (
+            bubbles.iter().map(|b| b.label.clone()).collect(),
+            edges
+                .iter()
+                .map(|e| (bubbles[e.from].label.clone(), bubbles[e.to].label.clone()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn undoing_a_removal_puts_everything_back_in_place() {
+        let (mut bubbles, mut edges) = graph(6, &[(0, 1), (1, 2), (2, 5), (3, 4), (4, 5), (0, 5)]);
+        let before = snapshot(&bubbles, &edges);
+        let mut history = History::new();
+
+        let command = Command::remove(&bubbles, &edges, &[1, 3], &[4]);
+        history.execute(command, &mut bubbles, &mut edges);
+        assert_eq!(bubbles.len(), 4);
+        assert_eq!(edges.len(), 2);
+        let after = snapshot(&bubbles, &edges);
+
+        assert!(history.undo(&mut bubbles, &mut edges));
+        assert_eq!(snapshot(&bubbles, &edges), before);
+        assert!(history.redo(&mut bubbles, &mut edges));
+        assert_eq!(snapshot(&bubbles, &edges), after);
+    }
+
+    #[test]
+    fn undoing_an_addition_takes_it_back() {
+        let (mut bubbles, mut edges) = graph(2, &[]);
+        let mut history = History::new();
+        history.execute(Command::AddEdge(EdgeData { from: 0, to: 1, weight: 1.0 }), &mut bubbles, &mut edges);
+        assert_eq!(edges.len(), 1);
+        assert!(history.undo(&mut bubbles, &mut edges));
+        assert!(edges.is_empty());
+        assert!(!history.undo(&mut bubbles, &mut edges));
+    }
+}
@@ -0,0 +1,151 @@
+use crate::adjacency::Adjacency;
+use crate::bubble::Bubble;
+use crate::edge::Edge;
+use crate::math::Vector2;
+use crate::stress::{self, graph_distances, EDGE_LENGTH};
+
+// every node is solved to the whole graph, so past this many bubbles it's left to the stress majorization
+pub const KAMADA_KAWAI_LIMIT: usize = 500;
+// a bubble is settled once the gradient of the energy at it is below this, in simulation units
+const TOLERANCE: f32 = 0.01 * EDGE_LENGTH;
+// how many times a bubble is picked, per bubble, and how many Newton steps it takes each time
+const MAX_MOVES_PER_BUBBLE: usize = 20;
+const MAX_NEWTON_STEPS: usize = 50;
+
+/// Kamada-Kawai: springs between every pair of bubbles as long as their hop distance, minimised
+/// one bubble at a time with Newton-Raphson, always the one the energy pulls on hardest.
+/// It starts from `Bubble::position`, so it refines the layout it's given, and the pinned bubbles stay put.
+/// The energy is the stress with 1 / d² weights, so past `KAMADA_KAWAI_LIMIT` bubbles `stress::stress_majorization` takes over.
+pub fn kamada_kawai(bubbles: &mut [Bubble], edges: &[Edge]) {
+    let n = bubbles.len();
+    if n > KAMADA_KAWAI_LIMIT {
+        stress::stress_majorization(bubbles, edges);
+        return;
+    }
+    if n < 2 {
+        return;
+    }
+    let adjacency = Adjacency::new(n, edges.iter().map(|e| (e.from, e.to, e.weight)));
+    let sources: Vec<usize> = (0..n).collect();
+    let lengths = graph_distances(&adjacency, &sources);
+
+    let mut positions: Vec<Vector2> = bubbles.iter().map(|b| b.position).collect();
+    let mut gradients: Vec<Vector2> = (0..n).map(|m| gradient(&positions, &lengths, m)).collect();
+
+    for _ in 0..MAX_MOVES_PER_BUBBLE * n {
+        let m = match (0..n)
+            .filter(|&i| !bubbles[i].pinned)
+            .max_by(|&a, &b| gradients[a].len().total_cmp(&gradients[b].len()))
+        {
+            Some(m) if gradients[m].len() >= TOLERANCE => m,
+            _ => break,
+        };
+
+        let old = positions[m];
+        for _ in 0..MAX_NEWTON_STEPS {
+            let g = gradient(&positions, &lengths, m);
+            if g.len() < TOLERANCE {
+                break;
+            }
+            let (xx, xy, yy) = hessian(&positions, &lengths, m);
+            let determinant = xx * yy - xy * xy;
+            if determinant.abs() < f32::EPSILON {
+                break;
+            }
+            // the 2x2 system hessian * step = -gradient
+            positions[m] = positions[m].add(&Vector2 {
+                x: (xy * g.y - yy * g.x) / determinant,
+                y: (xy * g.x - xx * g.y) / determinant,
+            });
+        }
+
+        // only the terms with m changed in the other bubbles' gradients
+        for i in (0..n).filter(|&i| i != m) {
+            let before = spring_force(&positions[i], &old, lengths[i][m]);
+            let after = spring_force(&positions[i], &positions[m], lengths[i][m]);
+            gradients[i] = gradients[i].sub(&before).add(&after);
+        }
+        gradients[m] = gradient(&positions, &lengths, m);
+    }
+
+    for (bubble, position) in bubbles.iter_mut().zip(positions) {
+        bubble.position = position;
+        bubble.v = Vector2::new();
+    }
+}
+
+// the gradient of the energy of the spring from `q` to `p`, at `p`, with the stiffness 1 / hops²
+fn spring_force(p: &Vector2, q: &Vector2, length: f32) -> Vector2 {
+    let d = p.sub(q);
+    let distance = d.len();
+    if distance == 0.0 {
+        return Vector2::new();
+    }
+    let stiffness = (EDGE_LENGTH / length).powi(2);
+    d.mul_s(stiffness * (1.0 - length / distance))
+}
+
+fn gradient(positions: &[Vector2], lengths: &[Vec<f32>], m: usize) -> Vector2 {
+    let mut sum = Vector2::new();
+    for (i, q) in positions.iter().enumerate() {
+        if i != m {
+            sum = sum.add(&spring_force(&positions[m], q, lengths[m][i]));
+        }
+    }
+    sum
+}
+
+// the second derivatives at m, as (xx, xy, yy)
+fn hessian(positions: &[Vector2], lengths: &[Vec<f32>], m: usize) -> (f32, f32, f32) {
+    let (mut xx, mut xy, mut yy) = (0.0, 0.0, 0.0);
+    for (i, q) in positions.iter().enumerate() {
+        let d = positions[m].sub(q);
+        let distance = d.len();
+        if i == m || distance == 0.0 {
+            continue;
+        }
+        let length = lengths[m][i];
+        let stiffness = (EDGE_LENGTH / length).powi(2);
+        let cubed = distance * distance * distance;
+        xx += stiffness * (1.0 - length * d.y * d.y / cubed);
+        xy += stiffness * length * d.x * d.y / cubed;
+        yy += stiffness * (1.0 - length * d.x * d.x / cubed);
+    }
+    (xx, xy, yy)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout_quality::LayoutQuality;
+    use crate::test_graph::{cycle, graph};
+
+    #[test]
+    fn kamada_kawai_opens_a_ring_up() {
+        let (mut bubbles, edges) = cycle(8);
+        let before = LayoutQuality::evaluate(&bubbles, &edges).stress;
+        kamada_kawai(&mut bubbles, &edges);
+        let after = LayoutQuality::evaluate(&bubbles, &edges).stress;
+        assert!(after < 0.5 * before, "the stress went from {} to {}", before, after);
+        assert!(after < 0.05);
+    }
+
+    #[test]
+    fn the_springs_get_the_hop_distances() {
+        // a path of three lies straight, the ends two edges apart
+        let (mut bubbles, edges) = graph(3, &[(0, 1), (1, 2)]);
+        kamada_kawai(&mut bubbles, &edges);
+        let length = |a: usize, b: usize| bubbles[a].position.sub(&bubbles[b].position).len();
+        assert!((length(0, 1) - EDGE_LENGTH).abs() < 0.05 * EDGE_LENGTH);
+        assert!((length(0, 2) - 2.0 * EDGE_LENGTH).abs() < 0.05 * EDGE_LENGTH);
+    }
+
+    #[test]
+    fn the_pinned_bubbles_stay_put() {
+        let (mut bubbles, edges) = cycle(6);
+        bubbles[0].pinned = true;
+        let pinned_at = bubbles[0].position;
+        kamada_kawai(&mut bubbles, &edges);
+        assert_eq!((bubbles[0].position.x, bubbles[0].position.y), (pinned_at.x, pinned_at.y));
+    }
+}
@@ -0,0 +1,264 @@
+use std::f32::consts::PI;
+
+use serde_json::{json, Value};
+
+use crate::adjacency::Adjacency;
+use crate::bubble::Bubble;
+use crate::collision::count_overlaps;
+use crate::edge::Edge;
+use crate::math::Vector2;
+
+// the crossings are counted pair by pair, past this many edges they're left out
+const MAX_CROSSING_EDGES: usize = 10_000;
+// the stress and the neighbourhoods are worked out for every bubble up to this many,
+// past it they're estimated from `SAMPLED_BUBBLES` spread evenly over the bubbles
+const MAX_EXACT_BUBBLES: usize = 2_000;
+const SAMPLED_BUBBLES: usize = 200;
+
+/// How readable a layout is, worked out from the positions of the bubbles and the edges between them.
+/// The stress needs a search from every bubble, past `MAX_EXACT_BUBBLES` it and the neighbourhoods are estimated from a sample.
+pub struct LayoutQuality {
+    pub bubble_count: usize,
+    pub edge_count: usize,
+    // pairs of edges crossing each other, the ones sharing a bubble don't count,
+    // `None` past `MAX_CROSSING_EDGES` edges
+    pub edge_crossings: Option<usize>,
+    // how far the distances in the layout are from the hop distances, 0 when they match up to a scale
+    pub stress: f32,
+    pub edge_length_mean: f32,
+    // the standard deviation of the edge lengths over their mean, 0 when they're all as long
+    pub edge_length_variation: f32,
+    // pairs of bubbles drawn over each other
+    pub overlaps: usize,
+    // the smallest angle between the edges of a bubble over the angle they'd make spread evenly, averaged, 1 is best
+    pub angular_resolution: f32,
+    pub min_angle_degrees: f32,
+    // how many of a bubble's neighbours in the graph are also its nearest bubbles in the layout, averaged, 1 is best
+    pub neighbourhood_preservation: f32,
+}
+
+impl LayoutQuality {
+    pub fn evaluate(bubbles: &[Bubble], edges: &[Edge]) -> Self {
+        let adjacency = Adjacency::new(bubbles.len(), edges.iter().map(|e| (e.from, e.to, e.weight)));
+        let positions: Vec<Vector2> = bubbles.iter().map(|b| b.position).collect();
+        let (edge_length_mean, edge_length_variation) = edge_lengths(&positions, edges);
+        let (angular_resolution, min_angle) = angular_resolution(&positions, &adjacency);
+        LayoutQuality {
+            bubble_count: bubbles.len(),
+            edge_count: edges.len(),
+            edge_crossings: edge_crossings(&positions, edges),
+            stress: stress(&positions, &adjacency),
+            edge_length_mean,
+            edge_length_variation,
+            overlaps: count_overlaps(bubbles, 0.0),
+            angular_resolution,
+            min_angle_degrees: min_angle.to_degrees(),
+            neighbourhood_preservation: neighbourhood_preservation(&positions, &adjacency),
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "bubble_count": self.bubble_count,
+            "edge_count": self.edge_count,
+            "edge_crossings": self.edge_crossings,
+            "stress": self.stress,
+            "edge_length_mean": self.edge_length_mean,
+            "edge_length_variation": self.edge_length_variation,
+            "overlaps": self.overlaps,
+            "angular_resolution": self.angular_resolution,
+            "min_angle_degrees": self.min_angle_degrees,
+            "neighbourhood_preservation": self.neighbourhood_preservation,
+        })
+    }
+}
+
+fn edge_crossings(positions: &[Vector2], edges: &[Edge]) -> Option<usize> {
+    if edges.len() > MAX_CROSSING_EDGES {
+        return None;
+    }
+    let mut crossings = 0;
+    for (i, a) in edges.iter().enumerate() {
+        for b in edges[i + 1..].iter() {
+            let shares_a_bubble = a.from == b.from || a.from == b.to || a.to == b.from || a.to == b.to;
+            if !shares_a_bubble
+                && segments_cross(&positions[a.from], &positions[a.to], &positions[b.from], &positions[b.to])
+            {
+                crossings += 1;
+            }
+        }
+    }
+    Some(crossings)
+}
+
+// whether the segments p1-p2 and q1-q2 cross, each one has the ends of the other on both of its sides
+fn segments_cross(p1: &Vector2, p2: &Vector2, q1: &Vector2, q2: &Vector2) -> bool {
+    let side = |a: &Vector2, b: &Vector2, c: &Vector2| (b.x - a.x) * (c.y - a.y) - (b.y - a.y) * (c.x - a.x);
+    side(p1, p2, q1) * side(p1, p2, q2) < 0.0 && side(q1, q2, p1) * side(q1, q2, p2) < 0.0
+}
+
+// the stress with the weights at 1 / d², after the layout is scaled to fit the hop distances best,
+// and over the number of pairs so it stays between 0 and 1 whatever the size of the graph
+// the pairs in different components are left out
+// Σ((s·x - d) / d)² is s²·Σx²/d² - 2s·Σx/d + Σ1, so only those sums are kept, and the best scale is Σx/d over Σx²/d²
+// on big graphs it's the pairs of the sampled bubbles with all the others
+fn stress(positions: &[Vector2], adjacency: &Adjacency) -> f32 {
+    let exact = positions.len() <= MAX_EXACT_BUBBLES;
+    let mut pair_count = 0usize;
+    let mut x_over_d = 0.0f64;
+    let mut x2_over_d2 = 0.0f64;
+    for i in sampled_bubbles(positions.len()) {
+        let hops = adjacency.hop_distances(&[i], u32::MAX);
+        // every pair once when they're all there
+        let first = if exact { i + 1 } else { 0 };
+        for (j, hop) in hops.iter().enumerate().skip(first).filter(|&(j, _)| j != i) {
+            if let Some(d) = hop {
+                let ratio = positions[i].sub(&positions[j]).len() as f64 / *d as f64;
+                pair_count += 1;
+                x_over_d += ratio;
+                x2_over_d2 += ratio * ratio;
+            }
+        }
+    }
+    if pair_count == 0 {
+        return 0.0;
+    }
+    let scale = if x2_over_d2 > 0.0 { x_over_d / x2_over_d2 } else { 1.0 };
+    let total = scale * scale * x2_over_d2 - 2.0 * scale * x_over_d + pair_count as f64;
+    (total.max(0.0) / pair_count as f64) as f32
+}
+
+fn edge_lengths(positions: &[Vector2], edges: &[Edge]) -> (f32, f32) {
+    if edges.is_empty() {
+        return (0.0, 0.0);
+    }
+    let lengths: Vec<f32> = edges.iter().map(|e| positions[e.from].sub(&positions[e.to]).len()).collect();
+    let mean = lengths.iter().sum::<f32>() / lengths.len() as f32;
+    let variance = lengths.iter().map(|l| (l - mean).powi(2)).sum::<f32>() / lengths.len() as f32;
+    let variation = if mean > 0.0 { variance.sqrt() / mean } else { 0.0 };
+    (mean, variation)
+}
+
+// over the bubbles with two edges or more, the average score and the smallest angle anywhere, in radians
+fn angular_resolution(positions: &[Vector2], adjacency: &Adjacency) -> (f32, f32) {
+    let mut score_sum = 0.0;
+    let mut scored = 0;
+    let mut min_angle = 2.0 * PI;
+    for (i, p) in positions.iter().enumerate() {
+        let mut angles: Vec<f32> = adjacency
+            .neighbours_of(i)
+            .filter(|&(j, _, _)| j != i)
+            .map(|(j, _, _)| {
+                let d = positions[j].sub(p);
+                d.y.atan2(d.x)
+            })
+            .collect();
+        if angles.len() < 2 {
+            continue;
+        }
+        angles.sort_by(|a, b| a.total_cmp(b));
+        // the gap from the last one round to the first one closes the circle
+        let wrap_around = angles[0] + 2.0 * PI - angles[angles.len() - 1];
+        let smallest = angles.windows(2).map(|w| w[1] - w[0]).fold(wrap_around, f32::min);
+        score_sum += smallest / (2.0 * PI / angles.len() as f32);
+        scored += 1;
+        min_angle = min_angle.min(smallest);
+    }
+    if scored == 0 {
+        return (1.0, 0.0);
+    }
+    (score_sum / scored as f32, min_angle)
+}
+
+// for each bubble with edges, the Jaccard similarity of its neighbours and as many of its nearest bubbles in the layout,
+// on big graphs for the sampled bubbles only
+fn neighbourhood_preservation(positions: &[Vector2], adjacency: &Adjacency) -> f32 {
+    let mut similarity_sum = 0.0;
+    let mut counted = 0;
+    for i in sampled_bubbles(positions.len()) {
+        let p = &positions[i];
+        let mut neighbours: Vec<usize> = adjacency.neighbours_of(i).map(|(j, _, _)| j).filter(|&j| j != i).collect();
+        neighbours.sort_unstable();
+        neighbours.dedup();
+        if neighbours.is_empty() {
+            continue;
+        }
+        let mut by_distance: Vec<(f32, usize)> = positions
+            .iter()
+            .enumerate()
+            .filter(|&(j, _)| j != i)
+            .map(|(j, q)| (q.sub(p).sqrt_len(), j))
+            .collect();
+        // only the nearest ones are needed, and in no particular order
+        if neighbours.len() < by_distance.len() {
+            by_distance.select_nth_unstable_by(neighbours.len(), |a, b| a.0.total_cmp(&b.0));
+        }
+        let shared = by_distance
+            .iter()
+            .take(neighbours.len())
+            .filter(|(_, j)| neighbours.binary_search(j).is_ok())
+            .count();
+        // both sets have as many bubbles
+        similarity_sum += shared as f32 / (2 * neighbours.len() - shared) as f32;
+        counted += 1;
+    }
+    if counted == 0 {
+        return 1.0;
+    }
+    similarity_sum / counted as f32
+}
+
+// all the bubbles, or `SAMPLED_BUBBLES` of them spread evenly when there're too many
+fn sampled_bubbles(bubble_count: usize) -> Vec<usize> {
+    if bubble_count <= MAX_EXACT_BUBBLES {
+        (0..bubble_count).collect()
+    } else {
+        (0..SAMPLED_BUBBLES).map(|k| k * bubble_count / SAMPLED_BUBBLES).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_graph::graph;
+
+    fn place(bubbles: &mut [Bubble], positions: &[(f32, f32)]) {
+        for (bubble, &(x, y)) in bubbles.iter_mut().zip(positions.iter()) {
+            bubble.position = Vector2 { x, y };
+        }
+    }
+
+    #[test]
+    fn the_diagonals_of_a_square_cross_once() {
+        let (mut bubbles, edges) = graph(4, &[(0, 2), (1, 3), (0, 1), (1, 2)]);
+        place(&mut bubbles, &[(0.0, 0.0), (500.0, 0.0), (500.0, 500.0), (0.0, 500.0)]);
+        assert_eq!(LayoutQuality::evaluate(&bubbles, &edges).edge_crossings, Some(1));
+    }
+
+    #[test]
+    fn a_path_in_a_straight_line_has_no_stress() {
+        let (mut bubbles, edges) = graph(4, &[(0, 1), (1, 2), (2, 3)]);
+        place(&mut bubbles, &[(0.0, 0.0), (300.0, 0.0), (600.0, 0.0), (900.0, 0.0)]);
+        let straight = LayoutQuality::evaluate(&bubbles, &edges);
+        assert!(straight.stress < 1e-6);
+        assert_eq!(straight.edge_crossings, Some(0));
+
+        // folded back so the ends meet, the hop distances don't fit any scale
+        place(&mut bubbles, &[(0.0, 0.0), (300.0, 0.0), (300.0, 300.0), (0.0, 300.0)]);
+        let folded = LayoutQuality::evaluate(&bubbles, &edges);
+        assert!(folded.stress > 0.01);
+    }
+
+    #[test]
+    fn a_big_straight_path_is_scored_from_a_sample() {
+        let n = 3 * MAX_EXACT_BUBBLES;
+        let links: Vec<(usize, usize)> = (1..n).map(|i| (i - 1, i)).collect();
+        let (mut bubbles, edges) = graph(n, &links);
+        for (i, bubble) in bubbles.iter_mut().enumerate() {
+            bubble.position = Vector2 { x: i as f32 * 300.0, y: 0.0 };
+        }
+        let quality = LayoutQuality::evaluate(&bubbles, &edges);
+        assert!(quality.stress < 1e-6);
+        assert_eq!(quality.neighbourhood_preservation, 1.0);
+    }
+}
@@ -1,16 +1,39 @@
+mod adjacency;
 mod bubble;
+mod camera;
+mod circular;
+mod collision;
 mod create_dataset;
 mod drawable;
 mod edge;
+mod forceatlas2;
 mod forcelayout;
+mod fruchterman_reingold;
 mod gpu_forcelayout;
+mod graph_edit;
+mod history;
 mod id_generator;
+mod kamada_kawai;
+mod layout_quality;
 mod math;
 mod mesh;
+mod metrics;
+mod multilevel;
+mod path;
 mod physics;
+mod picking;
+mod pivot_mds;
 mod project;
+mod selection;
 mod shape_builder;
-
+mod spectral;
+mod stress;
+mod sugiyama;
+#[cfg(test)]
+mod test_graph;
+mod tree;
+
+use adjacency::Adjacency;
 use bubble::Bubble;
 use edge::Edge;
 use forcelayout::*;
@@ -31,7 +54,7 @@ use physics::Physics;
 use shape_builder::ShapeBuilder;
 use wgpu::Device;
 use winit::dpi::PhysicalSize;
-use winit::event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent, DeviceEvent, MouseButton};
+use winit::event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent, DeviceEvent, ModifiersState, MouseButton, MouseScrollDelta};
 use winit::event_loop::{ControlFlow, EventLoop};
 use winit::window::Window;
 
@@ -41,14 +64,25 @@ use wgpu::{util::DeviceExt, BlendFactor, BlendOperation, BlendState, Buffer, Que
 use futures::executor::block_on;
 use std::{
     borrow::Borrow,
+    collections::BTreeMap,
     f64::consts,
     num::NonZeroI64,
     ops::{Range, Rem},
+    sync::Arc,
+    time::{Duration, Instant},
     usize,
 };
 
-use crate::gpu_forcelayout::GpuForcelayout;
-use crate::{math::Vector2, project::fit_into_view};
+use crate::collision::{count_overlaps, remove_overlaps, COLLISION_PADDING, OVERLAP_REMOVAL_ITERATIONS};
+use crate::forceatlas2::ForceAtlas2Settings;
+use crate::gpu_forcelayout::{GpuForcelayout, BUBBLE_PINNED};
+use crate::history::{BubbleData, Command, EdgeData, History};
+use crate::camera::Camera;
+use crate::layout_quality::LayoutQuality;
+use crate::math::Vector2;
+use crate::path::{shortest_path, GraphPath};
+use crate::picking::{bubbles_in_box, bubbles_in_lasso, pick_bubble, pick_edge};
+use crate::selection::Selection;
 
 //use log;
 
@@ -56,9 +90,9 @@ use crate::{math::Vector2, project::fit_into_view};
 #[derive(Copy, Clone)]
 struct Globals {
     resolution: [f32; 2],
-    scroll_offset: [f32; 2],
-    zoom: f32,
-    _pad1: f32,
+    camera_center: [f32; 2],
+    camera_scale: f32,
+    bubble_count: u32,
 }
 
 unsafe impl bytemuck::Pod for Globals {}
@@ -74,34 +108,39 @@ pub struct GpuVertex {
 unsafe impl bytemuck::Pod for GpuVertex {}
 unsafe impl bytemuck::Zeroable for GpuVertex {}
 
+// how one instance of a mesh looks, the transform comes from the simulation buffers
 #[repr(C)]
 #[derive(Copy, Clone)]
-pub struct Primitive {
+pub struct InstanceStyle {
     color: [f32; 4],
-    translate: [f32; 2],
-    z_index: i32,
     width: f32,
-    angle: f32,
-    scale: f32,
-    _pad1: i32,
-    _pad2: i32,
+    _pad1: f32,
+    _pad2: f32,
+    _pad3: f32,
 }
 
-impl Primitive {
-    const DEFAULT: Self = Primitive {
+impl InstanceStyle {
+    const DEFAULT: Self = InstanceStyle {
         color: [0.0; 4],
-        translate: [0.0; 2],
-        z_index: 0,
         width: 0.0,
-        angle: 0.0,
-        scale: 1.0,
-        _pad1: 0,
-        _pad2: 0,
+        _pad1: 0.0,
+        _pad2: 0.0,
+        _pad3: 0.0,
     };
 }
 
-unsafe impl bytemuck::Pod for Primitive {}
-unsafe impl bytemuck::Zeroable for Primitive {}
+unsafe impl bytemuck::Pod for InstanceStyle {}
+unsafe impl bytemuck::Zeroable for InstanceStyle {}
+
+// a point of the outlines drawn over the graph, in window pixels
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct OverlayVertex {
+    position: [f32; 2],
+    color: [f32; 4],
+}
+unsafe impl bytemuck::Pod for OverlayVertex {}
+unsafe impl bytemuck::Zeroable for OverlayVertex {}
 
 #[repr(C)]
 #[derive(Copy, Clone)]
@@ -114,22 +153,39 @@ unsafe impl bytemuck::Zeroable for BgPoint {}
 const DEFAULT_WINDOW_WIDTH: f32 = 800.0;
 const DEFAULT_WINDOW_HEIGHT: f32 = 800.0;
 
-fn get_draw_mesh_range<'a>(
-    mesh_range: &'a Vec<(&Mesh, &Range<u32>)>,
-    target_range: Range<u32>,
-) -> Vec<(&'a Mesh, Range<u32>)> {
-    let mut ret = vec![];
-    for range in mesh_range.iter() {
-        if !(range.1.start > target_range.end || range.1.end < target_range.start) {
-            ret.push((
-                range.0,
-                range.1.start.max(target_range.start) - target_range.start
-                    ..range.1.end.min(target_range.end) - target_range.start,
-            ));
-        }
-    }
-    ret
-}
+// the fill, the stroke and the velocity arrow
+const BUBBLE_MESH_COUNT: usize = 3;
+
+// the stroke of the selected bubbles and the selected edges
+const SELECTED_COLOR: [f32; 4] = [1.0, 0.9, 0.2, 1.0];
+const SELECTED_WIDTH: f32 = 3.0;
+// the shortest path between two selected bubbles
+const PATH_COLOR: [f32; 4] = [1.0, 0.45, 0.1, 1.0];
+const PATH_WIDTH: f32 = 3.0;
+// where the path between the selected bubbles is exported to, one label per line
+const PATH_EXPORT_FILE: &str = "path.txt";
+// the fills go from the first colour to the second as the metric they're coloured by goes up
+const METRIC_LOW_COLOR: [f32; 4] = [0.0, 0.5, 0.5, 1.0];
+const METRIC_HIGH_COLOR: [f32; 4] = [1.0, 0.85, 0.2, 1.0];
+// the range of sizes the bubbles are sized into by a metric
+const METRIC_MIN_SIZE: f32 = 60.0;
+const METRIC_MAX_SIZE: f32 = 400.0;
+const METRICS_EXPORT_FILE: &str = "metrics.json";
+// what's outside the focused neighbourhood keeps this share of its opacity
+const DIMMED_ALPHA: f32 = 0.15;
+const FOCUSED_EDGE_WIDTH: f32 = 2.0;
+// the stroke of the pinned bubbles
+const PINNED_COLOR: [f32; 4] = [0.3, 0.6, 1.0, 1.0];
+
+// how far the cursor can move between press and release for it to still be a click, in pixels
+const CLICK_DISTANCE: f32 = 4.0;
+// how soon the second click has to follow the first for a double click
+const DOUBLE_CLICK_TIME: Duration = Duration::from_millis(400);
+// how often the hover reads the simulated positions back at most, in between it picks from the last ones read
+const HOVER_READBACK_INTERVAL: Duration = Duration::from_millis(250);
+// the zoom for one notch of the mouse wheel, and per pixel of a pinch
+const WHEEL_ZOOM_FACTOR: f32 = 1.25;
+const PINCH_ZOOM_SPEED: f32 = 0.01;
 
 /// Creates a texture that uses MSAA and fits a given swap chain
 fn create_multisampled_framebuffer(
@@ -177,26 +233,335 @@ fn draw_mesh<'a, 'b, 'c, 'd>(
     )
 }
 
-fn create_forcelayout_instance(bubbles: &Vec<Bubble>, edges: &Vec<Edge>) -> gpu_forcelayout::GpuForcelayout {
-    let mut bubble_physics_entities: Vec<BubbleGpuEntity> = vec![];
-    let mut edge_entities: Vec<EdgeEntity> = vec![];
-    for bubble in bubbles.iter() {
-        bubble_physics_entities.push(BubbleGpuEntity {
-            m: bubble.get_m(),
+fn bubble_entity(bubble: &Bubble) -> BubbleGpuEntity {
+    BubbleGpuEntity {
+        m: bubble.get_m(),
+        flags: if bubble.pinned { BUBBLE_PINNED } else { 0 },
+        p: [bubble.position.x, bubble.position.y],
+        v: [bubble.v.x, bubble.v.y],
+        a: [bubble.a.x, bubble.a.y],
+    }
+}
+
+fn bubble_entities(bubbles: &[Bubble]) -> Vec<BubbleGpuEntity> {
+    bubbles.iter().map(bubble_entity).collect()
+}
+
+fn edge_entities(edges: &[Edge]) -> Vec<EdgeEntity> {
+    edges
+        .iter()
+        .map(|edge| EdgeEntity {
+            from: edge.from as u32,
+            to: edge.to as u32,
+            // the simulation pulls every edge the same, like `forcelayout` does
+            weight: 1.0,
             _pad1: 0.0,
-            p: [bubble.position.x, bubble.position.y],
-            v: [bubble.v.x, bubble.v.y],
-            a: [bubble.a.x, bubble.a.y],
-        });
+        })
+        .collect()
+}
+
+fn create_forcelayout_instance(device: &Arc<Device>, queue: &Arc<Queue>, bubbles: &[Bubble], edges: &[Edge]) -> gpu_forcelayout::GpuForcelayout {
+    let bubble_physics_entities = bubble_entities(bubbles);
+    let edge_entities = edge_entities(edges);
+
+    let gpu_forcelayout_instance =
+        gpu_forcelayout::GpuForcelayout::with_device(device.clone(), queue.clone(), bubble_physics_entities, edge_entities);
+
+    gpu_forcelayout_instance
+}
+
+// brings the simulation in line with the cpu copy of the graph after an undo or a redo, those can put bubbles
+// back in the middle, the bubbles were read back before it so none of them loses its place
+fn sync_forcelayout_instance(gpu_forcelayout: &mut GpuForcelayout, bubbles: &[Bubble], edges: &[Edge]) {
+    let gpu_bubble_count = gpu_forcelayout.bubble_count() as usize;
+    if bubbles.len() > gpu_bubble_count {
+        gpu_forcelayout.add_nodes(&bubble_entities(&bubbles[gpu_bubble_count..]));
+    } else if bubbles.len() < gpu_bubble_count {
+        let tail: Vec<u32> = (bubbles.len() as u32..gpu_bubble_count as u32).collect();
+        gpu_forcelayout.remove_nodes(&tail);
+    }
+    gpu_forcelayout.write_nodes(0, &bubble_entities(bubbles));
+    gpu_forcelayout.set_edges(&edge_entities(edges));
+}
+
+// the simulation lives on the gpu, this brings the cpu copy of the bubbles up to date before it's edited
+fn read_bubbles_back(gpu_forcelayout: &GpuForcelayout, bubbles: &mut [Bubble]) {
+    let result = block_on(gpu_forcelayout.read_bubbles());
+    for (e, b) in result.iter().zip(bubbles.iter_mut()) {
+        b.a.x = e.a[0];
+        b.a.y = e.a[1];
+
+        b.v.x = e.v[0];
+        b.v.y = e.v[1];
+
+        b.position.x = e.p[0];
+        b.position.y = e.p[1];
+    }
+}
+
+// the styles are laid out the way the instances are drawn: every bubble mesh for all the bubbles, then the edges
+// `focus` holds the hop distances from the focused bubbles, everything out of reach is dimmed, it's empty when nothing is focused
+// the path between the selected bubbles stands out from everything else, and the fills can show a metric
+fn build_styles(
+    bubbles: &mut [Bubble],
+    edges: &mut [Edge],
+    selection: &Selection,
+    focus: &[Option<u32>],
+    path: Option<&GraphPath>,
+    colour_metric: Option<&str>,
+) -> Vec<InstanceStyle> {
+    let mut styles: Vec<InstanceStyle> = vec![];
+    for bubble in bubbles.iter_mut() {
+        bubble.update_mesh();
+    }
+    if let Some(name) = colour_metric {
+        let values = metrics::normalized(bubbles, name);
+        for (bubble, t) in bubbles.iter_mut().zip(values) {
+            let mut color = METRIC_LOW_COLOR;
+            for (c, high) in color.iter_mut().zip(METRIC_HIGH_COLOR.iter()) {
+                *c += (high - *c) * t;
+            }
+            bubble.meshes[0].material.color = color;
+        }
+    }
+    for bubble in bubbles.iter_mut().filter(|b| b.pinned) {
+        bubble.meshes[1].material.color = PINNED_COLOR;
+    }
+    for &i in selection.bubbles().iter() {
+        let bubble_edge_mesh = &mut bubbles[i].meshes[1];
+        bubble_edge_mesh.material.color = SELECTED_COLOR;
+        bubble_edge_mesh.width = SELECTED_WIDTH;
+    }
+    let on_path = |i: usize| path.is_some_and(|p| p.bubbles.contains(&i));
+    if !focus.is_empty() {
+        for (i, bubble) in bubbles.iter_mut().enumerate() {
+            if focus[i].is_none() && !on_path(i) {
+                for mesh in bubble.meshes.iter_mut() {
+                    mesh.material.color[3] *= DIMMED_ALPHA;
+                }
+            }
+        }
+    }
+    if let Some(path) = path {
+        // the two ends keep the selection's colour
+        for &i in path.bubbles.iter().filter(|i| !selection.contains_bubble(**i)) {
+            let bubble_edge_mesh = &mut bubbles[i].meshes[1];
+            bubble_edge_mesh.material.color = PATH_COLOR;
+            bubble_edge_mesh.width = PATH_WIDTH;
+        }
+    }
+    for i in 0..BUBBLE_MESH_COUNT {
+        for bubble in bubbles.iter() {
+            styles.push(bubble.meshes[i].get_style());
+        }
+    }
+    for edge in edges.iter_mut() {
+        edge.update_mesh();
+    }
+    for &i in selection.edges().iter() {
+        edges[i].mesh.material.color = SELECTED_COLOR;
+        edges[i].mesh.width = SELECTED_WIDTH;
+    }
+    if !focus.is_empty() {
+        // an edge stands out when it stays inside the neighbourhood
+        for edge in edges.iter_mut() {
+            if focus[edge.from].is_some() && focus[edge.to].is_some() || on_path(edge.from) && on_path(edge.to) {
+                edge.mesh.width = edge.mesh.width.max(FOCUSED_EDGE_WIDTH);
+            } else {
+                edge.mesh.material.color[3] *= DIMMED_ALPHA;
+            }
+        }
+    }
+    if let Some(path) = path {
+        for &i in path.edges.iter() {
+            edges[i].mesh.material.color = PATH_COLOR;
+            edges[i].mesh.width = PATH_WIDTH;
+        }
     }
     for edge in edges.iter() {
-        edge_entities.push([edge.from as u32, edge.to as u32, 0, 0]);
+        let mut style = edge.mesh.get_style();
+        // a routed edge is drawn along its route instead, see `route_vertices`
+        if !edge.route.is_empty() {
+            style.color[3] = 0.0;
+        }
+        styles.push(style);
     }
+    styles
+}
 
-    let gpu_forcelayout_instance =
-        gpu_forcelayout::GpuForcelayout::new(bubble_physics_entities, edge_entities);
+// the routed edges as line segments in window pixels, in the colour their style gave them,
+// they start and end at the outline of their bubbles since they're drawn over them
+fn route_vertices(bubbles: &[Bubble], edges: &[Edge], camera: &Camera) -> Vec<OverlayVertex> {
+    // where the line from the bubble's centre to `p` leaves the bubble
+    let outline = |bubble: &Bubble, p: &Vector2| {
+        let d = p.sub(&bubble.position);
+        let length = d.len();
+        if length > bubble.radius() {
+            bubble.position.add(&d.mul_s(bubble.radius() / length))
+        } else {
+            bubble.position
+        }
+    };
+    let mut vertices = vec![];
+    for edge in edges.iter().filter(|e| !e.route.is_empty()) {
+        let start = outline(&bubbles[edge.from], &edge.route[0]);
+        let end = outline(&bubbles[edge.to], &edge.route[edge.route.len() - 1]);
+        let points: Vec<Vector2> = std::iter::once(start)
+            .chain(edge.route.iter().copied())
+            .chain(std::iter::once(end))
+            .collect();
+        for pair in points.windows(2) {
+            for p in pair {
+                let p = camera.to_screen(p);
+                vertices.push(OverlayVertex {
+                    position: [p.x, p.y],
+                    color: edge.mesh.material.color,
+                });
+            }
+        }
+    }
+    vertices
+}
 
-    gpu_forcelayout_instance
+// works out the metric unless the bubbles hold it for the graph as it is already
+fn ensure_metric(bubbles: &mut [Bubble], edges: &[Edge], fresh_metrics: &mut [bool], metric: usize) {
+    if !fresh_metrics[metric] {
+        metrics::compute(bubbles, edges, metrics::ALL[metric]);
+        fresh_metrics[metric] = true;
+    }
+}
+
+// the neighbourhood the focus mode highlights: the hovered bubble's, or else the selection's
+fn focus_distances(scene: &SceneParams, hovered: Option<usize>, selection: &Selection, bubbles: &[Bubble], edges: &[Edge]) -> Vec<Option<u32>> {
+    let sources = match hovered {
+        Some(i) => vec![i],
+        None => selection.bubbles().to_vec(),
+    };
+    if !scene.focus_mode || sources.is_empty() {
+        return vec![];
+    }
+    let adjacency = Adjacency::new(bubbles.len(), edges.iter().map(|e| (e.from, e.to, e.weight)));
+    adjacency.hop_distances(&sources, scene.focus_hops)
+}
+
+// the shortest path between the two selected bubbles, when exactly two are selected and they're connected
+fn selected_path(selection: &Selection, bubbles: &[Bubble], edges: &[Edge]) -> Option<GraphPath> {
+    match *selection.bubbles() {
+        [from, to] => {
+            let adjacency = Adjacency::new(bubbles.len(), edges.iter().map(|e| (e.from, e.to, e.weight)));
+            shortest_path(&adjacency, from, to)
+        }
+        _ => None,
+    }
+}
+
+fn create_style_buffer(
+    device: &Device,
+    bubbles: &mut [Bubble],
+    edges: &mut [Edge],
+    selection: &Selection,
+    focus: &[Option<u32>],
+    path: Option<&GraphPath>,
+    colour_metric: Option<&str>,
+) -> Buffer {
+    let styles = build_styles(bubbles, edges, selection, focus, path, colour_metric);
+    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Styles"),
+        // a binding can't be empty
+        contents: bytemuck::cast_slice(if styles.is_empty() { &[InstanceStyle::DEFAULT] } else { &styles[..] }),
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+    })
+}
+
+// only the first bubble and the first edge carry meshes, they're drawn for all the others
+fn ensure_meshes(bubbles: &mut [Bubble], edges: &mut [Edge], id: &mut id_generator::IdGenerator, builder: &mut ShapeBuilder, device: &Device) {
+    if let Some(bubble) = bubbles.first_mut() {
+        if bubble.meshes[0].vbo.is_none() {
+            bubble.generate_mesh(id, builder);
+            for mesh in bubble.meshes.iter_mut() {
+                mesh.create_buffer_and_upload(device);
+            }
+        }
+    }
+    if let Some(edge) = edges.first_mut() {
+        if edge.mesh.vbo.is_none() {
+            edge.generate_mesh(id, builder);
+            edge.mesh.create_buffer_and_upload(device);
+        }
+    }
+}
+
+// what the fit command frames
+enum Fit {
+    All,
+    Selection,
+}
+
+// points the camera at the bubbles, the whole of each one included
+fn frame_bubbles(camera: &mut Camera, bubbles: &[Bubble], indices: &[usize]) {
+    let mut min = Vector2 { x: f32::MAX, y: f32::MAX };
+    let mut max = Vector2 { x: f32::MIN, y: f32::MIN };
+    for &i in indices {
+        let p = bubbles[i].position;
+        let r = bubbles[i].radius();
+        min = Vector2 { x: min.x.min(p.x - r), y: min.y.min(p.y - r) };
+        max = Vector2 { x: max.x.max(p.x + r), y: max.y.max(p.y + r) };
+    }
+    camera.frame(&min, &max);
+}
+
+// a bubble being dragged around, the simulation doesn't move it until it's let go
+// grabbing a selected bubble drags the whole selection along
+struct Drag {
+    indices: Vec<usize>,
+    // from the cursor to the centre of each bubble
+    offsets: Vec<Vector2>,
+    from: Vec<Vector2>,
+    moved: bool,
+}
+
+// a region being drawn from empty space, the bubbles inside it are selected when the button is let go
+struct Marquee {
+    lasso: bool,
+    // shift adds to the selection instead of replacing it
+    additive: bool,
+    // in screen pixels: the two corners of the box, or the outline of the lasso so far
+    points: Vec<Vector2>,
+}
+
+// the lasso takes a new point once the cursor is this far from the last one, in pixels
+const LASSO_STEP: f32 = 4.0;
+const MARQUEE_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+
+fn create_bind_group(
+    device: &Device,
+    layout: &wgpu::BindGroupLayout,
+    globals_ubo: &Buffer,
+    styles: &Buffer,
+    gpu_forcelayout: &GpuForcelayout,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Bind group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: globals_ubo.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: gpu_forcelayout.bubble_buffer().as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: styles.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: gpu_forcelayout.edge_buffer().as_entire_binding(),
+            },
+        ],
+    })
 }
 
 struct SwapChainDescriptor {
@@ -207,14 +572,151 @@ struct SwapChainDescriptor {
     present_mode: wgpu::PresentMode,
 }
 
+// how long the layout runs before it's evaluated from the command line, when `--steps` doesn't say
+const DEFAULT_EVALUATION_STEPS: u32 = 1000;
+
+fn arg_value<'a>(args: &'a [String], name: &str) -> Option<&'a String> {
+    args.iter().position(|arg| arg == name).and_then(|i| args.get(i + 1))
+}
+
+fn parse_arg<T: std::str::FromStr>(args: &[String], name: &str) -> Option<T> {
+    arg_value(args, name).map(|value| value.parse().unwrap_or_else(|_| panic!("{} takes a number", name)))
+}
+
+// the forceatlas2 parameters can be set from the command line, the same flags as in Gephi's panel
+fn forceatlas2_settings(args: &[String]) -> ForceAtlas2Settings {
+    let defaults = ForceAtlas2Settings::DEFAULT;
+    let flag = |name: &str| args.iter().any(|arg| arg == name);
+    ForceAtlas2Settings {
+        scaling_ratio: parse_arg(args, "--scaling").unwrap_or(defaults.scaling_ratio),
+        gravity: parse_arg(args, "--gravity").unwrap_or(defaults.gravity),
+        strong_gravity: flag("--strong-gravity"),
+        lin_log: flag("--lin-log"),
+        edge_weight_influence: parse_arg(args, "--edge-weight-influence").unwrap_or(defaults.edge_weight_influence),
+        dissuade_hubs: flag("--dissuade-hubs"),
+        prevent_overlap: flag("--prevent-overlap"),
+        jitter_tolerance: parse_arg(args, "--jitter-tolerance").unwrap_or(defaults.jitter_tolerance),
+        iterations: parse_arg(args, "--iterations").unwrap_or(defaults.iterations),
+    }
+}
+
+// the layout that comes after `mode` when switching with the keyboard
+fn next_layout_mode(mode: LayoutMode, forceatlas2: ForceAtlas2Settings) -> LayoutMode {
+    let i = LayoutMode::ALL.iter().position(|m| m.name() == mode.name()).unwrap();
+    match LayoutMode::ALL[(i + 1) % LayoutMode::ALL.len()] {
+        LayoutMode::ForceAtlas2(_) => LayoutMode::ForceAtlas2(forceatlas2),
+        next => next,
+    }
+}
+
+// the miserables, or with `--generate N` that many random bubbles, up to about 20000, in stars of `--group-size M`,
+// a single star by default
+fn load_dataset(args: &[String]) -> (Vec<Bubble>, Vec<Edge>) {
+    match parse_arg::<usize>(args, "--generate") {
+        Some(bubble_count) => {
+            let group_size = parse_arg(args, "--group-size").unwrap_or(bubble_count);
+            create_dataset::create_generated_dataset(bubble_count, group_size.max(1))
+        }
+        None => create_dataset::create_dataset_from_file().unwrap(),
+    }
+}
+
+// lays the dataset out without a window and prints how good the layout is as json, for comparing settings from scripts
+fn print_layout_report(args: &[String], mode: LayoutMode, steps: u32, gpu: bool, root: Option<&str>) {
+    let (mut bubbles, mut edges) = load_dataset(args);
+    // the tree layouts can be rooted at a bubble by its label
+    let mode = mode.with_root(root.map(|label| {
+        bubbles
+            .iter()
+            .position(|b| b.label == label)
+            .unwrap_or_else(|| panic!("no bubble is labelled {}", label))
+    }));
+    // the steps are the force simulation's, with none it's the starting positions that are evaluated,
+    // it runs on the gpu with --gpu and on the cpu otherwise
+    if mode == LayoutMode::Multilevel && gpu {
+        multilevel::multilevel(&mut bubbles, &mut edges, |bubbles, edges, steps| {
+            let mut gpu_forcelayout = GpuForcelayout::new(bubble_entities(bubbles), edge_entities(edges));
+            gpu_forcelayout.step(steps);
+            read_bubbles_back(&gpu_forcelayout, bubbles);
+        });
+        remove_overlaps(&mut bubbles, COLLISION_PADDING, OVERLAP_REMOVAL_ITERATIONS);
+    } else if mode != LayoutMode::Force {
+        layout(mode, &mut bubbles, &mut edges);
+    } else if steps > 0 {
+        if gpu {
+            let mut gpu_forcelayout = GpuForcelayout::new(bubble_entities(&bubbles), edge_entities(&edges));
+            gpu_forcelayout.step(steps);
+            read_bubbles_back(&gpu_forcelayout, &mut bubbles);
+        } else {
+            for _ in 0..steps {
+                forcelayout(&mut bubbles, &mut edges);
+            }
+        }
+        // the simulation is over, what it left is finished off the way the other layouts are
+        remove_overlaps(&mut bubbles, COLLISION_PADDING, OVERLAP_REMOVAL_ITERATIONS);
+    }
+    let report = LayoutQuality::evaluate(&bubbles, &edges).to_json();
+    println!("{}", serde_json::to_string_pretty(&report).unwrap());
+}
+
 fn main() {
     env_logger::init();
+
+    // forcelayout [--generate N [--group-size M]] --evaluate [--layout NAME] [--steps N] [--gpu] [--root LABEL] [forceatlas2 flags]
+    let args: Vec<String> = std::env::args().collect();
+    let forceatlas2 = forceatlas2_settings(&args);
+    if args.iter().any(|arg| arg == "--evaluate") {
+        let steps = parse_arg(&args, "--steps").unwrap_or(DEFAULT_EVALUATION_STEPS);
+        let names: Vec<&str> = LayoutMode::ALL.iter().map(|mode| mode.name()).collect();
+        let mode = match arg_value(&args, "--layout").map(|name| LayoutMode::from_name(name)) {
+            None => LayoutMode::Force,
+            Some(Some(LayoutMode::ForceAtlas2(_))) => LayoutMode::ForceAtlas2(forceatlas2),
+            Some(Some(mode)) => mode,
+            Some(None) => panic!("--layout takes one of {}", names.join(", ")),
+        };
+        print_layout_report(&args, mode, steps, args.iter().any(|arg| arg == "--gpu"), arg_value(&args, "--root").map(String::as_str));
+        return;
+    }
+
     println!("== wgpu example ==");
     println!("Controls:");
     println!("  Arrow keys: scrolling");
     println!("  PgUp/PgDown: zoom in/out");
+    println!("  c: toggle fitting the view to the bubbles as they move");
+    println!("  wheel/pinch: zoom toward the cursor");
+    println!("  middle/right-drag, two-finger scroll: pan");
+    println!("  double-click: zoom to a bubble and its neighbours");
+    println!("  f/shift+f: fit all/fit the selection");
     println!("  b: toggle drawing the background");
     println!("  a/z: increase/decrease the stroke width");
+    println!("  o: remove the remaining overlaps");
+    println!("  [/]: fewer/more simulation steps per frame");
+    println!("  click: select a bubble or an edge, add a bubble on empty space");
+    println!("  shift-drag: link two bubbles with an edge");
+    println!("  delete: remove the selection");
+    println!("  drag: move a bubble, or all the selected ones");
+    println!("  shift-click: add to or take out of the selection");
+    println!("  drag on empty space: box select, with shift: add to the selection");
+    println!("  alt-drag on empty space: lasso select");
+    println!("  p: pin/unpin the selected bubbles");
+    println!("  ctrl+z/ctrl+y: undo/redo");
+    println!("  h: toggle dimming all but the neighbourhood of the hovered or selected bubbles");
+    println!("  +/-: more/fewer hops in the highlighted neighbourhood");
+    println!("  select two bubbles: highlight the shortest path between them");
+    println!("  e: export the highlighted path as labels to {}", PATH_EXPORT_FILE);
+    println!("  m: colour the bubbles by degree, weighted degree, betweenness, closeness, eigenvector, pagerank or nothing");
+    println!("  s: size the bubbles by the metric they're coloured by");
+    println!("  x: export the metrics to {}", METRICS_EXPORT_FILE);
+    let layout_names: Vec<&str> = LayoutMode::ALL.iter().map(|mode| mode.name()).collect();
+    println!("  l: switch between the {} layouts", layout_names.join(", "));
+    println!("  the sugiyama layout points the edges down, from their first bubble to their second");
+    println!("  the tree layouts hang from the first selected bubble, `--root LABEL` when evaluating");
+    println!("  the circular and arc-diagram layouts keep the dataset's groups together, or else the communities they find");
+    println!("  q: print the quality of the layout as json, `--evaluate [--layout NAME] [--steps N]` does it without a window");
+    println!("  --gpu runs the force simulation and refines the multilevel layout on the gpu when evaluating, the window always does");
+    println!("  --scaling, --gravity, --strong-gravity, --lin-log, --edge-weight-influence, --dissuade-hubs, --prevent-overlap,");
+    println!("  --jitter-tolerance and --iterations set up forceatlas2");
+    println!("  --generate N [--group-size M] starts from N random bubbles in stars of M instead of the dataset");
 
     // Number of samples for anti-aliasing
     // Set to 1 to disable
@@ -237,11 +739,10 @@ fn main() {
     let size = window.inner_size();
 
     let mut scene = SceneParams {
-        target_zoom: 1.0,
-        zoom: 1.0,
-        target_scroll: vector(0.0, 0.0),
-        scroll: vector(0.0, 0.0),
-        show_points: false,
+        camera: Camera::new(Vector2 {
+            x: size.width as f32,
+            y: size.height as f32,
+        }),
         stroke_width: 1.0,
         target_stroke_width: 1.0,
         draw_background: true,
@@ -250,6 +751,34 @@ fn main() {
         size_changed: true,
         need_reset: false,
         need_update_gpu: false,
+        need_remove_overlaps: false,
+        steps_per_frame: 1,
+        modifiers: ModifiersState::empty(),
+        mouse_down_position: None,
+        pending_press: None,
+        pending_click: None,
+        pending_link: None,
+        need_delete: false,
+        need_toggle_pinned: false,
+        need_undo: false,
+        need_redo: false,
+        need_update_styles: false,
+        need_update_hover: false,
+        need_export_path: false,
+        need_size_by_metric: false,
+        need_compute_metrics: false,
+        need_export_metrics: false,
+        need_report_layout: false,
+        need_apply_layout: false,
+        layout_mode: LayoutMode::Force,
+        forceatlas2,
+        colour_metric: None,
+        focus_mode: false,
+        focus_hops: 1,
+        pan_start: None,
+        last_click: None,
+        pending_zoom_to_bubble: None,
+        pending_fit: None,
     };
 
     // create an instance
@@ -280,6 +809,9 @@ fn main() {
         None,
     ))
     .unwrap();
+    // the force layout runs on the same device, so it's shared with it
+    let device = Arc::new(device);
+    let queue = Arc::new(queue);
 
     let config = surface
         .get_default_config(&adapter, size.width, size.height)
@@ -291,39 +823,16 @@ fn main() {
     let mut id = id_generator::IdGenerator::new();
     let mut shape_generator = ShapeBuilder::new();
 
-    // let mut bubbles = create_dataset::create_bubbles(bubble_count);
-    // let mut edges = create_dataset::create_edges(bubbles.len(), group_size);
-
-    let (mut bubbles, mut edges) = create_dataset::create_dataset_from_file().unwrap();
+    let (mut bubbles, mut edges) = load_dataset(&args);
 
-    // up to about 20000
-    let bubble_count = 5000;
-    let group_size = bubble_count as usize / 1;
+    ensure_meshes(&mut bubbles, &mut edges, &mut id, &mut shape_generator, &device);
 
-    for bubble in bubbles.first_mut() {
-        bubble.generate_mesh(&mut id, &mut shape_generator);
-        for mesh in bubble.meshes.iter_mut() {
-            mesh.create_buffer_and_upload(&device);
-        }
-    }
-
-    for edge in edges.first_mut() {
-        edge.generate_mesh(&mut id, &mut shape_generator);
-        edge.mesh.create_buffer_and_upload(&device);
-    }
 
 
-
-    let mut gpu_forcelayout_instance = create_forcelayout_instance(&bubbles, &edges);
+    let mut gpu_forcelayout_instance = create_forcelayout_instance(&device, &queue, &bubbles, &edges);
 
     // end init
 
-    let primitive_count = bubble_count as usize * bubbles[0].meshes.len() + edges.len();
-    let mut primitives: Vec<Primitive> = Vec::with_capacity(primitive_count);
-    for _ in 0..primitive_count {
-        primitives.push(Primitive::DEFAULT.clone());
-    }
-
     let bg_vbo = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
         label: None,
         contents: bytemuck::cast_slice(&bg_geometry.vertices),
@@ -336,22 +845,8 @@ fn main() {
         usage: wgpu::BufferUsages::INDEX,
     });
 
-    let primitive_item_size = std::mem::size_of::<Primitive>() as u32;
-    let primitive_group_item_count = (device.limits().max_uniform_buffer_binding_size
-        / primitive_item_size)
-        .min(primitive_count as u32);
-    let prim_group_buffer_byte_size = (primitive_group_item_count * primitive_item_size) as u64;
-    let prim_group_count =
-        (primitive_count as f32 / primitive_group_item_count as f32).ceil() as u32;
     let globals_buffer_byte_size = std::mem::size_of::<Globals>() as u64;
 
-    let prims_ubo = device.create_buffer(&wgpu::BufferDescriptor {
-        label: Some("Prims ubo"),
-        size: prim_group_buffer_byte_size,
-        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-        mapped_at_creation: false,
-    });
-
     let globals_ubo = device.create_buffer(&wgpu::BufferDescriptor {
         label: Some("Globals ubo"),
         size: globals_buffer_byte_size,
@@ -359,15 +854,40 @@ fn main() {
         mapped_at_creation: false,
     });
 
-    let vs_module =
-        &device.create_shader_module(wgpu::include_spirv!("./../shaders/geometry.vert.spv"));
+    let mut selection = Selection::default();
+    let mut history = History::new();
+    let mut drag: Option<Drag> = None;
+    let mut marquee: Option<Marquee> = None;
+    let mut hovered: Option<usize> = None;
+    // which of `metrics::ALL` the bubbles hold for the graph as it is, they're worked out when they're needed
+    let mut fresh_metrics = [false; metrics::ALL.len()];
+    let mut hover_read_back_at = Instant::now();
+    let mut path: Option<GraphPath> = None;
+    let mut style_buffer = create_style_buffer(&device, &mut bubbles, &mut edges, &selection, &[], None, None);
+
+    let bubble_vs_module =
+        &device.create_shader_module(wgpu::include_spirv!("./../shaders/bubble.vert.spv"));
+    let edge_vs_module =
+        &device.create_shader_module(wgpu::include_spirv!("./../shaders/edge.vert.spv"));
     let fs_module =
         &device.create_shader_module(wgpu::include_spirv!("./../shaders/geometry.frag.spv"));
+    let overlay_vs_module =
+        &device.create_shader_module(wgpu::include_spirv!("./../shaders/overlay.vert.spv"));
     let bg_vs_module =
         &device.create_shader_module(wgpu::include_spirv!("./../shaders/background.vert.spv"));
     let bg_fs_module =
         &device.create_shader_module(wgpu::include_spirv!("./../shaders/background.frag.spv"));
 
+    let storage_bind_group_layout_entry = |binding: u32, min_binding_size: u64| wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::VERTEX,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only: true },
+            has_dynamic_offset: false,
+            min_binding_size: wgpu::BufferSize::new(min_binding_size),
+        },
+        count: None,
+    };
     let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
         label: Some("Bind group layout"),
         entries: &[
@@ -382,36 +902,19 @@ fn main() {
                 },
                 count: None,
             },
-            wgpu::BindGroupLayoutEntry {
-                binding: 1,
-                visibility: wgpu::ShaderStages::VERTEX,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: wgpu::BufferSize::new(prim_group_buffer_byte_size),
-                },
-                count: None,
-            },
-        ],
-    });
-    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-        label: Some("Bind group"),
-        layout: &bind_group_layout,
-        entries: &[
-            wgpu::BindGroupEntry {
-                binding: 0,
-                resource: globals_ubo.as_entire_binding(),
-            },
-            wgpu::BindGroupEntry {
-                binding: 1,
-                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding{
-                    buffer: &prims_ubo,
-                    offset: 0,
-                    size: None,
-                }),
-            },
+            // the simulation buffers, they're read straight from the force layout
+            storage_bind_group_layout_entry(1, std::mem::size_of::<BubbleGpuEntity>() as u64),
+            storage_bind_group_layout_entry(2, std::mem::size_of::<InstanceStyle>() as u64),
+            storage_bind_group_layout_entry(3, std::mem::size_of::<EdgeEntity>() as u64),
         ],
     });
+    let mut bind_group = create_bind_group(
+        &device,
+        &bind_group_layout,
+        &globals_ubo,
+        &style_buffer,
+        &gpu_forcelayout_instance,
+    );
 
     let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
         bind_group_layouts: &[&bind_group_layout],
@@ -440,7 +943,7 @@ fn main() {
     let mut render_pipeline_descriptor = wgpu::RenderPipelineDescriptor {
         layout: Some(&pipeline_layout),
         vertex: wgpu::VertexState {
-            module: &vs_module,
+            module: bubble_vs_module,
             entry_point: "main",
             buffers: &[wgpu::VertexBufferLayout {
                 array_stride: std::mem::size_of::<GpuVertex>() as u64,
@@ -465,16 +968,14 @@ fn main() {
             }],
         },
         fragment: Some(wgpu::FragmentState {
-            module: &fs_module,
+            module: fs_module,
             entry_point: "main",
             targets: &[Some(wgpu::ColorTargetState {
                 format: wgpu::TextureFormat::Bgra8UnormSrgb,
                 // color_blend: blend_state,
                 // alpha_blend: blend_state,
-                blend: Some(BlendState {
-                    color: wgpu::BlendComponent::REPLACE,
-                    alpha: wgpu::BlendComponent::OVER
-                }),
+                // blended, so the dimmed bubbles and edges fade into the background
+                blend: Some(BlendState::ALPHA_BLENDING),
                 write_mask: wgpu::ColorWrites::ALL,
             })],
         }),
@@ -494,7 +995,49 @@ fn main() {
         multiview: None,
     };
 
-    let render_pipeline = device.create_render_pipeline(&render_pipeline_descriptor);
+    let bubble_pipeline = device.create_render_pipeline(&render_pipeline_descriptor);
+
+    render_pipeline_descriptor.vertex.module = edge_vs_module;
+    let edge_pipeline = device.create_render_pipeline(&render_pipeline_descriptor);
+
+    let overlay_vertex_attributes = [
+        wgpu::VertexAttribute {
+            offset: 0,
+            format: wgpu::VertexFormat::Float32x2,
+            shader_location: 0,
+        },
+        wgpu::VertexAttribute {
+            offset: 8,
+            format: wgpu::VertexFormat::Float32x4,
+            shader_location: 1,
+        },
+    ];
+    let overlay_vertex_buffers = [wgpu::VertexBufferLayout {
+        array_stride: std::mem::size_of::<OverlayVertex>() as u64,
+        step_mode: wgpu::VertexStepMode::Vertex,
+        attributes: &overlay_vertex_attributes,
+    }];
+    let overlay_pipeline_descriptor = wgpu::RenderPipelineDescriptor {
+        vertex: wgpu::VertexState {
+            module: overlay_vs_module,
+            entry_point: "main",
+            buffers: &overlay_vertex_buffers,
+        },
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::LineStrip,
+            ..render_pipeline_descriptor.primitive
+        },
+        ..render_pipeline_descriptor.clone()
+    };
+    let overlay_pipeline = device.create_render_pipeline(&overlay_pipeline_descriptor);
+    // the routed edges are separate segments drawn the same way
+    let route_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::LineList,
+            ..overlay_pipeline_descriptor.primitive
+        },
+        ..overlay_pipeline_descriptor.clone()
+    });
 
     // TODO: this isn't what we want: we'd need the equivalent of VK_POLYGON_MODE_LINE,
     // but it doesn't seem to be exposed by wgpu?
@@ -503,7 +1046,7 @@ fn main() {
     let bg_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
         layout: Some(&pipeline_layout),
         vertex: wgpu::VertexState {
-            module: &bg_vs_module,
+            module: bg_vs_module,
             entry_point: "main",
             buffers: &[wgpu::VertexBufferLayout {
                 array_stride: std::mem::size_of::<Point>() as u64,
@@ -516,7 +1059,7 @@ fn main() {
             }],
         },
         fragment: Some(wgpu::FragmentState {
-            module: &bg_fs_module,
+            module: bg_fs_module,
             entry_point: "main",
             targets: &[Some(wgpu::ColorTargetState {
                 format: wgpu::TextureFormat::Bgra8UnormSrgb,
@@ -570,153 +1113,457 @@ fn main() {
     let mut frame_count: f32 = 0.0;
 
     event_loop.run(move |event, _, control_flow| {
-        if update_inputs(event, control_flow, &mut scene, &mut bubbles, &mut id, &mut shape_generator, &device) {
+        if update_inputs(event, control_flow, &mut scene) {
             // keep polling inputs.
             return;
         }
 
-        if scene.need_reset {
-            scene.need_reset = false;
-            for b in &mut bubbles {
-                let random_vec2 = Vector2 {
-                    x: rand::random(),
-                    y: rand::random(),
-                };
-                b.position = random_vec2.add_s(-0.5).mul_s(100.0);
-            }
+        let need_write_bubbles = scene.need_reset || scene.need_remove_overlaps || scene.need_apply_layout;
+        let need_edit = scene.pending_press.is_some()
+            || scene.pending_click.is_some()
+            || scene.pending_link.is_some()
+            || scene.need_delete
+            || scene.need_toggle_pinned
+            || scene.need_undo
+            || scene.need_redo
+            || scene.pending_fit.is_some()
+            || scene.pending_zoom_to_bubble.is_some()
+            || scene.need_size_by_metric;
+        if need_write_bubbles || need_edit {
+            read_bubbles_back(&gpu_forcelayout_instance, &mut bubbles);
         }
 
-        if scene.need_update_gpu {
-            scene.need_update_gpu = false;
-            gpu_forcelayout_instance = create_forcelayout_instance(&bubbles, &edges);
-        }
-
-        // do forcelayout
-
-        // cpu force layout
-        // forcelayout(&mut bubbles, &mut edges);
-        
-        // gpu force layout
-        let result = block_on(gpu_forcelayout_instance.compute());
+        if need_edit {
+            if let Some(cursor) = scene.pending_press.take() {
+                match pick_bubble(&bubbles, &scene.camera, &cursor) {
+                    // shift-dragging from a bubble links it, see `pending_link`
+                    Some(_) if scene.modifiers.shift() => {}
+                    Some(index) => {
+                        let indices = if selection.contains_bubble(index) {
+                            selection.bubbles().to_vec()
+                        } else {
+                            vec![index]
+                        };
+                        let world_cursor = scene.camera.to_world(&cursor);
+                        let from: Vec<Vector2> = indices.iter().map(|&i| bubbles[i].position).collect();
+                        drag = Some(Drag {
+                            offsets: from.iter().map(|p| p.sub(&world_cursor)).collect(),
+                            indices,
+                            from,
+                            moved: false,
+                        });
+                    }
+                    None => {
+                        marquee = Some(Marquee {
+                            lasso: scene.modifiers.alt(),
+                            additive: scene.modifiers.shift(),
+                            points: vec![cursor, cursor],
+                        });
+                    }
+                }
+            }
 
-        // read layout data back from gpu
-        for (e, b) in result.iter().zip(&mut bubbles) {
-            b.a.x = e.a[0];
-            b.a.y = e.a[1];
+            if let Some(cursor) = scene.pending_click.take() {
+                // a bubble wins over an edge running underneath it, and a click on nothing adds a bubble
+                // with shift, the bubble or the edge goes in or out of the selection
+                let shift = scene.modifiers.shift();
+                if let Some(i) = pick_bubble(&bubbles, &scene.camera, &cursor) {
+                    if shift {
+                        selection.toggle_bubble(i);
+                    } else {
+                        selection.select_bubble(i);
+                    }
+                } else if let Some(i) = pick_edge(&edges, &bubbles, &scene.camera, &cursor) {
+                    if shift {
+                        selection.toggle_edge(i);
+                    } else {
+                        selection.select_edge(i);
+                    }
+                } else if !shift {
+                    selection.clear();
+                    let bubble = BubbleData {
+                        position: scene.camera.to_world(&cursor),
+                        size: 100.0,
+                        label: String::from("added"),
+                        pinned: false,
+                        attributes: BTreeMap::new(),
+                    };
+                    history.execute(Command::AddBubble(bubble), &mut bubbles, &mut edges);
+                    gpu_forcelayout_instance.add_nodes(&bubble_entities(&bubbles[bubbles.len() - 1..]));
+                    fresh_metrics = [false; metrics::ALL.len()];
+                    scene.need_update_gpu = true;
+                }
+                scene.need_update_styles = true;
+            }
 
-            b.v.x = e.v[0];
-            b.v.y = e.v[1];
+            if let Some((start, end)) = scene.pending_link.take() {
+                let from = pick_bubble(&bubbles, &scene.camera, &start);
+                let to = pick_bubble(&bubbles, &scene.camera, &end);
+                if let (Some(from), Some(to)) = (from, to) {
+                    let exists = edges
+                        .iter()
+                        .any(|e| (e.from, e.to) == (from, to) || (e.from, e.to) == (to, from));
+                    if from != to && !exists {
+                        let edge = EdgeData { from, to, weight: 1.0 };
+                        history.execute(Command::AddEdge(edge), &mut bubbles, &mut edges);
+                        gpu_forcelayout_instance.add_edges(&edge_entities(&edges[edges.len() - 1..]));
+                        fresh_metrics = [false; metrics::ALL.len()];
+                        scene.need_update_gpu = true;
+                    }
+                }
+            }
 
-            b.position.x = e.p[0];
-            b.position.y = e.p[1];
-        }
-        for edge in edges.iter_mut() {
-            edge.position_from.set(&(&bubbles[edge.from]).position);
-            edge.position_to.set(&(&bubbles[edge.to]).position);
-        }
+            if scene.need_delete {
+                scene.need_delete = false;
+                if !selection.is_empty() {
+                    let command = Command::remove(&bubbles, &edges, selection.bubbles(), selection.edges());
+                    history.execute(command, &mut bubbles, &mut edges);
+                    // the simulation removes them in the same order, the bubbles left keep moving where they are
+                    let edge_indices: Vec<u32> = selection.edges().iter().map(|&i| i as u32).collect();
+                    let bubble_indices: Vec<u32> = selection.bubbles().iter().map(|&i| i as u32).collect();
+                    gpu_forcelayout_instance.remove_edges(&edge_indices);
+                    gpu_forcelayout_instance.remove_nodes(&bubble_indices);
+                    fresh_metrics = [false; metrics::ALL.len()];
+                    selection.clear();
+                    drag = None;
+                    scene.need_update_gpu = true;
+                }
+            }
 
-        // update mesh
-        for bubble in bubbles.iter_mut() {
-            bubble.update_mesh();
-        }
-        for edge in edges.iter_mut() {
-            edge.update_mesh();
-        }
+            if scene.need_toggle_pinned {
+                scene.need_toggle_pinned = false;
+                if !selection.bubbles().is_empty() {
+                    let command = Command::toggle_pinned(&bubbles, selection.bubbles());
+                    history.execute(command, &mut bubbles, &mut edges);
+                    gpu_forcelayout_instance.write_nodes(0, &bubble_entities(&bubbles));
+                    scene.need_update_gpu = true;
+                }
+            }
 
-        {
-            // fit into window
-            let first_bubble = &bubbles[0];
-            let mut min = first_bubble.position.clone();
-            let mut max = min.clone();
-            for b in &bubbles {
-                let p = &b.position;
-                if p.x <= min.x {
-                    min.x = p.x;
+            if let Some(fit) = scene.pending_fit.take() {
+                let indices: Vec<usize> = match fit {
+                    Fit::All => (0..bubbles.len()).collect(),
+                    // a selected edge brings both its ends along
+                    Fit::Selection => selection
+                        .bubbles()
+                        .iter()
+                        .copied()
+                        .chain(selection.edges().iter().flat_map(|&i| vec![edges[i].from, edges[i].to]))
+                        .collect(),
+                };
+                if !indices.is_empty() {
+                    scene.camera.auto_fit = false;
+                    frame_bubbles(&mut scene.camera, &bubbles, &indices);
                 }
-                if p.y <= min.y {
-                    min.y = p.y;
+            }
+
+            // the bubble is framed together with its neighbours
+            if let Some(cursor) = scene.pending_zoom_to_bubble.take() {
+                if let Some(i) = pick_bubble(&bubbles, &scene.camera, &cursor) {
+                    let mut indices = vec![i];
+                    for edge in edges.iter() {
+                        if edge.from == i {
+                            indices.push(edge.to);
+                        } else if edge.to == i {
+                            indices.push(edge.from);
+                        }
+                    }
+                    scene.camera.auto_fit = false;
+                    frame_bubbles(&mut scene.camera, &bubbles, &indices);
                 }
-                if p.x >= max.x {
-                    max.x = p.x;
+            }
+
+            // the indices the selection and the drag hold may not mean the same bubbles and edges any more
+            if scene.need_undo {
+                scene.need_undo = false;
+                if history.undo(&mut bubbles, &mut edges) {
+                    sync_forcelayout_instance(&mut gpu_forcelayout_instance, &bubbles, &edges);
+                    fresh_metrics = [false; metrics::ALL.len()];
+                    selection.clear();
+                    drag = None;
+                    scene.need_update_gpu = true;
                 }
-                if p.y >= max.y {
-                    max.y = p.y;
+            }
+
+            if scene.need_redo {
+                scene.need_redo = false;
+                if history.redo(&mut bubbles, &mut edges) {
+                    sync_forcelayout_instance(&mut gpu_forcelayout_instance, &bubbles, &edges);
+                    fresh_metrics = [false; metrics::ALL.len()];
+                    selection.clear();
+                    drag = None;
+                    scene.need_update_gpu = true;
                 }
             }
-            let bubble_rect = math::Rect {
-                origin: min.clone(),
-                width: max.x - min.x,
-                height: max.y - min.y,
-            };
 
-            let half_width = 0.5 * scene.window_size.width as f32;
-            let half_height = 0.5 * scene.window_size.height as f32;
+            // by the metric the fills show, or the degree when they show none
+            if scene.need_size_by_metric {
+                scene.need_size_by_metric = false;
+                let metric = scene.colour_metric.unwrap_or(0);
+                let name = metrics::ALL[metric];
+                ensure_metric(&mut bubbles, &edges, &mut fresh_metrics, metric);
+                metrics::size_by(&mut bubbles, name, METRIC_MIN_SIZE, METRIC_MAX_SIZE);
+                println!("sized the bubbles by {}", name);
+                gpu_forcelayout_instance.write_nodes(0, &bubble_entities(&bubbles));
+                scene.need_update_gpu = true;
+            }
+        }
 
-            let padding = 100.0;
+        if let Some(current) = &mut drag {
+            if let Some(start) = scene.mouse_down_position {
+                let cursor = Vector2 {
+                    x: scene.cursor_position.0,
+                    y: scene.cursor_position.1,
+                };
+                current.moved |= cursor.sub(&start).len() > CLICK_DISTANCE;
+                if current.moved {
+                    let world_cursor = scene.camera.to_world(&cursor);
+                    for (&i, offset) in current.indices.iter().zip(current.offsets.iter()) {
+                        let bubble = &mut bubbles[i];
+                        bubble.position = world_cursor.add(offset);
+                        bubble.v = Vector2::new();
+                        // held where the cursor is, the simulation would pull it away otherwise
+                        let mut entity = bubble_entity(bubble);
+                        entity.flags |= BUBBLE_PINNED;
+                        gpu_forcelayout_instance.write_nodes(i as u32, &[entity]);
+                    }
+                }
+            } else {
+                // let go, the bubbles are only pinned from now on if they were before
+                if current.moved {
+                    for &i in current.indices.iter() {
+                        gpu_forcelayout_instance.write_nodes(i as u32, &[bubble_entity(&bubbles[i])]);
+                    }
+                    history.record(Command::Move {
+                        indices: current.indices.clone(),
+                        from: current.from.clone(),
+                        to: current.indices.iter().map(|&i| bubbles[i].position).collect(),
+                    });
+                }
+                drag = None;
+            }
+        }
 
-            let view_rect = math::Rect {
-                origin: Vector2 {
-                    x: -half_width + padding,
-                    y: -half_height + padding,
-                },
-                width: scene.window_size.width as f32 - 2.0 * padding,
-                height: scene.window_size.height as f32 - 2.0 * padding,
+        if let Some(current) = &mut marquee {
+            let cursor = Vector2 {
+                x: scene.cursor_position.0,
+                y: scene.cursor_position.1,
             };
+            if scene.mouse_down_position.is_some() {
+                if !current.lasso {
+                    current.points[1] = cursor;
+                } else if current.points.last().is_some_and(|last| cursor.sub(last).len() >= LASSO_STEP) {
+                    current.points.push(cursor);
+                }
+            } else {
+                // a release close to where it started is a click, it's been handled as one
+                let start = current.points[0];
+                let dragged = current.points.iter().any(|p| p.sub(&start).len() > CLICK_DISTANCE);
+                if dragged {
+                    read_bubbles_back(&gpu_forcelayout_instance, &mut bubbles);
+                    let inside = if current.lasso {
+                        bubbles_in_lasso(&bubbles, &scene.camera, &current.points)
+                    } else {
+                        bubbles_in_box(&bubbles, &scene.camera, &current.points[0], &current.points[1])
+                    };
+                    if !current.additive {
+                        selection.clear();
+                    }
+                    selection.add_bubbles(inside);
+                    scene.need_update_styles = true;
+                }
+                marquee = None;
+            }
+        }
 
-            // let view_rect = math::Rect {
-            //     origin: Vector2 {
-            //         x: -1.0,
-            //         y: -1.0,
-            //     },
-            //     width: 1.0,
-            //     height: scene.window_size.height as f32 * 0.125
-            // };
-
+        if scene.need_reset {
+            scene.need_reset = false;
             for b in &mut bubbles {
-                let new_pos = fit_into_view(&b.position, &bubble_rect, &view_rect);
-                let d = new_pos.sub(&b.position);
-                for m in &mut b.meshes {
-                    m.position =  [m.position[0] + d.x, m.position[1] + d.y];
+                let random_vec2 = Vector2 {
+                    x: rand::random(),
+                    y: rand::random(),
+                };
+                b.position = random_vec2.add_s(-0.5).mul_s(100.0);
+            }
+            if edges.iter().any(|e| !e.route.is_empty()) {
+                for edge in edges.iter_mut() {
+                    edge.route.clear();
                 }
+                scene.need_update_styles = true;
             }
+        }
 
+        if scene.need_apply_layout {
+            scene.need_apply_layout = false;
+            scene.need_update_styles = true;
+            // the simulation moves the bubbles away from any route
             for edge in edges.iter_mut() {
-                // it's a hack here, and I know the maintenance sucks
-                let bubble_from_mesh_pos = &(&bubbles[edge.from]).meshes[0].position;
-                let bubble_to_mesh_pos = &(&bubbles[edge.to]).meshes[0].position;
+                edge.route.clear();
+            }
+            if scene.layout_mode != LayoutMode::Force {
+                let started = Instant::now();
+                if scene.layout_mode == LayoutMode::Multilevel {
+                    // every level gets a simulation of its own on the gpu
+                    multilevel::multilevel(&mut bubbles, &mut edges, |bubbles, edges, steps| {
+                        let mut gpu_forcelayout = create_forcelayout_instance(&device, &queue, bubbles, edges);
+                        gpu_forcelayout.step(steps);
+                        read_bubbles_back(&gpu_forcelayout, bubbles);
+                    });
+                    remove_overlaps(&mut bubbles, COLLISION_PADDING, OVERLAP_REMOVAL_ITERATIONS);
+                } else {
+                    let mode = scene.layout_mode.with_root(selection.bubbles().first().copied());
+                    layout(mode, &mut bubbles, &mut edges);
+                }
+                println!("{} layout took {:?}", scene.layout_mode.name(), started.elapsed());
+            }
+        }
 
-                edge.position_from = Vector2 {
-                    x: bubble_from_mesh_pos[0],
-                    y: bubble_from_mesh_pos[1],
-                };
-                edge.position_to = Vector2 {
-                    x: bubble_to_mesh_pos[0],
-                    y: bubble_to_mesh_pos[1],
+        if scene.need_remove_overlaps {
+            scene.need_remove_overlaps = false;
+            let overlap_count = count_overlaps(&bubbles, COLLISION_PADDING);
+            remove_overlaps(&mut bubbles, COLLISION_PADDING, OVERLAP_REMOVAL_ITERATIONS);
+            println!("removed {} overlaps", overlap_count);
+        }
+
+        // the focus follows the cursor from bubble to bubble, but not while something is being dragged
+        if scene.need_update_hover {
+            scene.need_update_hover = false;
+            if scene.focus_mode && scene.mouse_down_position.is_none() && scene.pan_start.is_none() {
+                // reading back waits for the gpu, and only the force simulation moves the bubbles away from the cpu copy
+                if scene.layout_mode == LayoutMode::Force && hover_read_back_at.elapsed() >= HOVER_READBACK_INTERVAL {
+                    read_bubbles_back(&gpu_forcelayout_instance, &mut bubbles);
+                    hover_read_back_at = Instant::now();
+                }
+                let cursor = Vector2 {
+                    x: scene.cursor_position.0,
+                    y: scene.cursor_position.1,
                 };
-                edge.update_mesh();
+                let picked = pick_bubble(&bubbles, &scene.camera, &cursor);
+                if picked != hovered {
+                    hovered = picked;
+                    scene.need_update_styles = true;
+                }
+            }
+        }
+
+        if need_write_bubbles {
+            let gpu_bubble_count = gpu_forcelayout_instance.bubble_count() as usize;
+            gpu_forcelayout_instance.write_nodes(0, &bubble_entities(&bubbles[..gpu_bubble_count]));
+        }
+
+        if scene.need_update_gpu {
+            scene.need_update_gpu = false;
+            // the instances moved around and the simulation buffers may have grown
+            ensure_meshes(&mut bubbles, &mut edges, &mut id, &mut shape_generator, &device);
+            // the hovered index may mean another bubble now
+            hovered = None;
+            let focus = focus_distances(&scene, hovered, &selection, &bubbles, &edges);
+            path = selected_path(&selection, &bubbles, &edges);
+            // only the metric the bubbles are coloured by, the others wait until they're needed
+            if let Some(metric) = scene.colour_metric {
+                ensure_metric(&mut bubbles, &edges, &mut fresh_metrics, metric);
             }
+            style_buffer = create_style_buffer(
+                &device,
+                &mut bubbles,
+                &mut edges,
+                &selection,
+                &focus,
+                path.as_ref(),
+                scene.colour_metric.map(|i| metrics::ALL[i]),
+            );
+            scene.need_update_styles = false;
+            bind_group = create_bind_group(
+                &device,
+                &bind_group_layout,
+                &globals_ubo,
+                &style_buffer,
+                &gpu_forcelayout_instance,
+            );
         }
 
-        // update gpu primitives
-        let l = bubbles.len();
-        let ml = bubbles[0].meshes.len();
-        let mut bubble_ranges = vec![];
-        let edge_range = (l * ml) as u32..primitive_count as u32;
+        if scene.need_compute_metrics {
+            scene.need_compute_metrics = false;
+            if let Some(metric) = scene.colour_metric {
+                ensure_metric(&mut bubbles, &edges, &mut fresh_metrics, metric);
+            }
+        }
 
-        for i in 0..ml {
-            bubble_ranges.push((i * l) as u32..(i * l + l) as u32);
-            for j in 0..l {
-                primitives[j + i * l] = bubbles[j].meshes[i].get_uniform_buffer();
+        if scene.need_update_styles {
+            scene.need_update_styles = false;
+            let focus = focus_distances(&scene, hovered, &selection, &bubbles, &edges);
+            path = selected_path(&selection, &bubbles, &edges);
+            let colour_metric = scene.colour_metric.map(|i| metrics::ALL[i]);
+            let styles = build_styles(&mut bubbles, &mut edges, &selection, &focus, path.as_ref(), colour_metric);
+            if !styles.is_empty() {
+                queue.write_buffer(&style_buffer, 0, bytemuck::cast_slice(&styles));
             }
         }
-        for (edge, i) in edges.iter_mut().zip(edge_range.clone()) {
-            primitives[i as usize] = edge.mesh.get_uniform_buffer();
+
+        if scene.need_report_layout {
+            scene.need_report_layout = false;
+            read_bubbles_back(&gpu_forcelayout_instance, &mut bubbles);
+            let report = LayoutQuality::evaluate(&bubbles, &edges).to_json();
+            println!("{}", serde_json::to_string_pretty(&report).unwrap());
+        }
+
+        if scene.need_export_metrics {
+            scene.need_export_metrics = false;
+            if fresh_metrics.contains(&false) {
+                metrics::compute_all(&mut bubbles, &edges);
+                fresh_metrics = [true; metrics::ALL.len()];
+            }
+            let report: Vec<serde_json::Value> = bubbles
+                .iter()
+                .map(|b| serde_json::json!({ "label": b.label, "attributes": b.attributes }))
+                .collect();
+            let contents = serde_json::to_string_pretty(&report).unwrap();
+            match std::fs::write(METRICS_EXPORT_FILE, contents) {
+                Ok(()) => println!("exported the metrics of {} bubbles to {}", bubbles.len(), METRICS_EXPORT_FILE),
+                Err(error) => println!("couldn't export the metrics to {}: {}", METRICS_EXPORT_FILE, error),
+            }
+        }
+
+        if scene.need_export_path {
+            scene.need_export_path = false;
+            match &path {
+                Some(path) => {
+                    let labels: Vec<&str> = path.bubbles.iter().map(|&i| bubbles[i].label.as_str()).collect();
+                    println!("path: {}", labels.join(" -> "));
+                    let mut contents = labels.join("\n");
+                    contents.push('\n');
+                    match std::fs::write(PATH_EXPORT_FILE, contents) {
+                        Ok(()) => println!("exported {} labels to {}", labels.len(), PATH_EXPORT_FILE),
+                        Err(error) => println!("couldn't export the path to {}: {}", PATH_EXPORT_FILE, error),
+                    }
+                }
+                None => println!("select two connected bubbles to export the path between them"),
+            }
         }
+
+        // do forcelayout
+
+        // cpu force layout
+        // forcelayout(&mut bubbles, &mut edges);
+        
+        // gpu force layout, the other layouts only have their bounds worked out
+        let steps = if scene.layout_mode == LayoutMode::Force { scene.steps_per_frame } else { 0 };
+        gpu_forcelayout_instance.step(steps);
+
+        // nothing is read back, the bubbles are drawn straight from the simulation buffers
         // end do forcelayout
 
+        // only the bounds come back, for the camera to fit them
+        let bounds = gpu_forcelayout_instance.poll_bounds();
+        scene.camera.update(bounds);
+
         if scene.size_changed {
             scene.size_changed = false;
             let physical = scene.window_size;
+            scene.camera.set_resolution(Vector2 {
+                x: physical.width as f32,
+                y: physical.height as f32,
+            });
             swap_chain_desc.width = physical.width;
             swap_chain_desc.height = physical.height;
             // swap_chain = device.create_swap_chain(&surface, &swap_chain_desc);
@@ -790,9 +1637,9 @@ fn main() {
                     scene.window_size.width as f32,
                     scene.window_size.height as f32,
                 ],
-                zoom: scene.zoom,
-                scroll_offset: scene.scroll.to_array(),
-                _pad1: 0.0
+                camera_center: [scene.camera.center().x, scene.camera.center().y],
+                camera_scale: scene.camera.scale(),
+                bubble_count: gpu_forcelayout_instance.bubble_count(),
             }]),
         );
 
@@ -829,92 +1676,117 @@ fn main() {
         queue.submit(Some(encoder.finish()));
         // }
 
-        let mut mesh_range = vec![];
-        let bubble_meshes = &bubbles[0].meshes;
-        for (mesh, range) in bubble_meshes.iter().zip(&bubble_ranges) {
-            mesh_range.push((mesh, range));
-        }
-
-        let edge_mesh = &edges[0].mesh;
-        mesh_range.push((&edge_mesh, &edge_range));
-
-        for group_index in 0..prim_group_count {
-            let i = group_index;
-            let n = primitive_group_item_count;
-            let mut st = (i * n) as usize;
-            let mut ed = ((i + 1) * n) as usize;
-            st = st.min(primitives.len());
-            ed = ed.min(primitives.len());
-
-            let primitive_slice = &primitives[st..ed];
-            queue.write_buffer(&prims_ubo, 0, bytemuck::cast_slice(primitive_slice));
-
-            let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                label: Some("Encoder"),
-            });
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Encoder"),
+        });
 
-            {
-                let color_attachment = if let Some(msaa_target) = &multisampled_render_target {
-                    wgpu::RenderPassColorAttachment {
-                        view: msaa_target,
-                        ops: wgpu::Operations {
-                            load: wgpu::LoadOp::Load,
-                            store: true,
-                        },
-                        resolve_target: Some(&frame_view),
-                    }
+        // the outline of the box or the lasso, closed back onto its first point
+        let overlay_vertices: Vec<OverlayVertex> = match &marquee {
+            Some(current) => {
+                let points = if current.lasso {
+                    current.points.clone()
                 } else {
-                    wgpu::RenderPassColorAttachment {
-                        view: &frame_view,
-                        ops: wgpu::Operations {
-                            load: wgpu::LoadOp::Load,
-                            store: true,
-                        },
-                        resolve_target: None,
-                    }
+                    let (a, b) = (current.points[0], current.points[1]);
+                    vec![a, Vector2 { x: b.x, y: a.y }, b, Vector2 { x: a.x, y: b.y }]
                 };
-                let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                    label: None,
-                    color_attachments: &[Some(color_attachment)],
-                    depth_stencil_attachment: Some(
-                        wgpu::RenderPassDepthStencilAttachment {
-                            view: depth_texture_view.as_ref().unwrap(),
-                            depth_ops: Some(wgpu::Operations {
-                                load: wgpu::LoadOp::Clear(0.0),
-                                store: true,
-                            }),
-                            stencil_ops: Some(wgpu::Operations {
-                                load: wgpu::LoadOp::Clear(0),
-                                store: true,
-                            }),
-                        },
-                    ),
-                });
-
-                pass.set_pipeline(&render_pipeline);
-                pass.set_bind_group(0, &bind_group, &[]);
+                points
+                    .iter()
+                    .chain(points.first())
+                    .map(|p| OverlayVertex {
+                        position: [p.x, p.y],
+                        color: MARQUEE_COLOR,
+                    })
+                    .collect()
+            }
+            None => vec![],
+        };
+        let overlay_vbo = (!overlay_vertices.is_empty()).then(|| {
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Overlay"),
+                contents: bytemuck::cast_slice(&overlay_vertices),
+                usage: wgpu::BufferUsages::VERTEX,
+            })
+        });
+        let route_vertices = route_vertices(&bubbles, &edges, &scene.camera);
+        let route_vbo = (!route_vertices.is_empty()).then(|| {
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Routes"),
+                contents: bytemuck::cast_slice(&route_vertices),
+                usage: wgpu::BufferUsages::VERTEX,
+            })
+        });
 
-                // todo: how to loop over the stereo array?
+        {
+            let color_attachment = if let Some(msaa_target) = &multisampled_render_target {
+                wgpu::RenderPassColorAttachment {
+                    view: msaa_target,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    },
+                    resolve_target: Some(&frame_view),
+                }
+            } else {
+                wgpu::RenderPassColorAttachment {
+                    view: &frame_view,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    },
+                    resolve_target: None,
+                }
+            };
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: None,
+                color_attachments: &[Some(color_attachment)],
+                depth_stencil_attachment: Some(
+                    wgpu::RenderPassDepthStencilAttachment {
+                        view: depth_texture_view.as_ref().unwrap(),
+                        depth_ops: Some(wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(0.0),
+                            store: true,
+                        }),
+                        stencil_ops: Some(wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(0),
+                            store: true,
+                        }),
+                    },
+                ),
+            });
 
-                let target_mesh_ranges = get_draw_mesh_range(&mesh_range, st as u32..ed as u32);
+            pass.set_bind_group(0, &bind_group, &[]);
 
-                for (mesh, range) in target_mesh_ranges {
-                    draw_mesh(&mesh, &mut pass, &range);
+            // one instanced draw per bubble mesh, the instances of mesh i are i * l..(i + 1) * l
+            let l = gpu_forcelayout_instance.bubble_count();
+            // every bubble (and every edge) has the same meshes, the first one's are drawn for all of them
+            if let Some(bubble) = bubbles.first() {
+                pass.set_pipeline(&bubble_pipeline);
+                for (i, mesh) in bubble.meshes.iter().enumerate() {
+                    let i = i as u32;
+                    draw_mesh(mesh, &mut pass, &(i * l..(i + 1) * l));
                 }
+            }
 
-                // let bubble_meshes = &bubbles[0].meshes;
-                // for (mesh, range) in bubble_meshes.iter().zip(&bubble_ranges) {
-                //     draw_mesh(& mesh, &mut pass, &range);
-                // }
-
-                // let edge_mesh = &edges[0].mesh;
-                // draw_mesh(& edge_mesh, &mut pass, &edge_range);
+            if let Some(edge) = edges.first() {
+                pass.set_pipeline(&edge_pipeline);
+                draw_mesh(&edge.mesh, &mut pass, &(0..gpu_forcelayout_instance.edge_count()));
             }
 
-            queue.submit(Some(encoder.finish()));
+            if let Some(route_vbo) = &route_vbo {
+                pass.set_pipeline(&route_pipeline);
+                pass.set_vertex_buffer(0, route_vbo.slice(..));
+                pass.draw(0..route_vertices.len() as u32, 0..1);
+            }
 
+            if let Some(overlay_vbo) = &overlay_vbo {
+                pass.set_pipeline(&overlay_pipeline);
+                pass.set_vertex_buffer(0, overlay_vbo.slice(..));
+                pass.draw(0..overlay_vertices.len() as u32, 0..1);
+            }
         }
 
+        queue.submit(Some(encoder.finish()));
+
         frame_count += 1.0;
         frame.present();
     });
@@ -955,11 +1827,7 @@ impl FillVertexConstructor<BgPoint> for Custom {
 }
 
 struct SceneParams {
-    target_zoom: f32,
-    zoom: f32,
-    target_scroll: Vector,
-    scroll: Vector,
-    show_points: bool,
+    camera: Camera,
     stroke_width: f32,
     target_stroke_width: f32,
     draw_background: bool,
@@ -968,18 +1836,55 @@ struct SceneParams {
     size_changed: bool,
     need_reset: bool,
     need_update_gpu: bool,
+    need_remove_overlaps: bool,
+    steps_per_frame: u32,
+    modifiers: ModifiersState,
+    // where the left button went down, a release close to it is a click
+    mouse_down_position: Option<Vector2>,
+    // the edits are carried out in the event loop, where the bubbles can be read back to find what's under the cursor
+    pending_press: Option<Vector2>,
+    pending_click: Option<Vector2>,
+    pending_link: Option<(Vector2, Vector2)>,
+    need_delete: bool,
+    need_toggle_pinned: bool,
+    need_undo: bool,
+    need_redo: bool,
+    need_update_styles: bool,
+    need_update_hover: bool,
+    need_export_path: bool,
+    need_size_by_metric: bool,
+    need_compute_metrics: bool,
+    need_export_metrics: bool,
+    need_report_layout: bool,
+    need_apply_layout: bool,
+    // the gpu simulation only runs in `LayoutMode::Force`, the other layouts are applied once on the cpu and left alone
+    layout_mode: LayoutMode,
+    forceatlas2: ForceAtlas2Settings,
+    // which of `metrics::ALL` the fills are coloured by
+    colour_metric: Option<usize>,
+    // dims everything but the neighbourhood of the hovered or selected bubbles, `focus_hops` deep
+    focus_mode: bool,
+    focus_hops: u32,
+    // where the cursor was last seen while the middle or right button is down
+    pan_start: Option<Vector2>,
+    // when and where the last click was, for telling double clicks
+    last_click: Option<(Instant, Vector2)>,
+    pending_zoom_to_bubble: Option<Vector2>,
+    pending_fit: Option<Fit>,
+}
+
+fn window_center(scene: &SceneParams) -> Vector2 {
+    Vector2 {
+        x: 0.5 * scene.window_size.width as f32,
+        y: 0.5 * scene.window_size.height as f32,
+    }
 }
 
 fn update_inputs(
     event: Event<()>,
     control_flow: &mut ControlFlow,
     scene: &mut SceneParams,
-    bubbles: &mut Vec<Bubble>,
-    id: &mut id_generator::IdGenerator,
-    builder: &mut ShapeBuilder,
-    device: &Device,
 ) -> bool {
-    let mut mouse_position = Vector2::new();
     match event {
         Event::MainEventsCleared => {
             return false;
@@ -1000,6 +1905,58 @@ fn update_inputs(
             ..
         } => {
             scene.cursor_position = (position.x as f32, position.y as f32);
+            scene.need_update_hover = true;
+            // the view follows the cursor right away while it's dragged
+            if let Some(last) = scene.pan_start {
+                let cursor = Vector2 {
+                    x: scene.cursor_position.0,
+                    y: scene.cursor_position.1,
+                };
+                scene.camera.pan(&cursor.sub(&last), true);
+                scene.pan_start = Some(cursor);
+            }
+        }
+        Event::WindowEvent {
+            event: WindowEvent::MouseInput {
+                state,
+                button: MouseButton::Middle | MouseButton::Right,
+                ..
+            },
+            ..
+        } => {
+            scene.pan_start = match state {
+                ElementState::Pressed => Some(Vector2 {
+                    x: scene.cursor_position.0,
+                    y: scene.cursor_position.1,
+                }),
+                ElementState::Released => None,
+            };
+        }
+        Event::WindowEvent {
+            event: WindowEvent::MouseWheel { delta, .. },
+            ..
+        } => {
+            let cursor = Vector2 {
+                x: scene.cursor_position.0,
+                y: scene.cursor_position.1,
+            };
+            match delta {
+                MouseScrollDelta::LineDelta(_, y) => {
+                    scene.camera.zoom_at(&cursor, WHEEL_ZOOM_FACTOR.powf(y));
+                }
+                // trackpads report pixels: two fingers pan, and a pinch comes as a scroll with ctrl held
+                MouseScrollDelta::PixelDelta(delta) => {
+                    if scene.modifiers.ctrl() {
+                        scene.camera.zoom_at(&cursor, (delta.y as f32 * PINCH_ZOOM_SPEED).exp());
+                    } else {
+                        let delta = Vector2 {
+                            x: delta.x as f32,
+                            y: delta.y as f32,
+                        };
+                        scene.camera.pan(&delta, false);
+                    }
+                }
+            }
         }
         Event::WindowEvent {
             event: WindowEvent::Resized(size),
@@ -1026,25 +1983,70 @@ fn update_inputs(
                 return false;
             }
             VirtualKeyCode::PageDown => {
-                scene.target_zoom *= 0.8;
+                scene.camera.zoom_at(&window_center(scene), 0.8);
             }
             VirtualKeyCode::PageUp => {
-                scene.target_zoom *= 1.25;
+                scene.camera.zoom_at(&window_center(scene), 1.25);
+            }
+            VirtualKeyCode::M => {
+                scene.colour_metric = match scene.colour_metric {
+                    None => Some(0),
+                    Some(i) if i + 1 < metrics::ALL.len() => Some(i + 1),
+                    Some(_) => None,
+                };
+                println!("colouring by {}", scene.colour_metric.map_or("nothing", |i| metrics::ALL[i]));
+                scene.need_compute_metrics = true;
+                scene.need_update_styles = true;
+            }
+            VirtualKeyCode::S => {
+                scene.need_size_by_metric = true;
+            }
+            VirtualKeyCode::L => {
+                scene.layout_mode = next_layout_mode(scene.layout_mode, scene.forceatlas2);
+                println!("layout: {}", scene.layout_mode.name());
+                scene.need_apply_layout = true;
+            }
+            VirtualKeyCode::Q => {
+                scene.need_report_layout = true;
+            }
+            VirtualKeyCode::X => {
+                scene.need_export_metrics = true;
+            }
+            VirtualKeyCode::E => {
+                scene.need_export_path = true;
+            }
+            VirtualKeyCode::H => {
+                scene.focus_mode = !scene.focus_mode;
+                scene.need_update_styles = true;
+            }
+            VirtualKeyCode::Equals | VirtualKeyCode::Plus | VirtualKeyCode::NumpadAdd => {
+                scene.focus_hops += 1;
+                scene.need_update_styles = true;
+            }
+            VirtualKeyCode::Minus | VirtualKeyCode::NumpadSubtract => {
+                scene.focus_hops = scene.focus_hops.saturating_sub(1).max(1);
+                scene.need_update_styles = true;
+            }
+            VirtualKeyCode::C => {
+                scene.camera.auto_fit = !scene.camera.auto_fit;
+            }
+            VirtualKeyCode::F => {
+                scene.pending_fit = Some(if scene.modifiers.shift() { Fit::Selection } else { Fit::All });
             }
             VirtualKeyCode::Left => {
-                scene.target_scroll.x -= 50.0 / scene.target_zoom;
+                scene.camera.pan(&Vector2 { x: 50.0, y: 0.0 }, false);
             }
             VirtualKeyCode::Right => {
-                scene.target_scroll.x += 50.0 / scene.target_zoom;
+                scene.camera.pan(&Vector2 { x: -50.0, y: 0.0 }, false);
             }
             VirtualKeyCode::Up => {
-                scene.target_scroll.y -= 50.0 / scene.target_zoom;
+                scene.camera.pan(&Vector2 { x: 0.0, y: 50.0 }, false);
             }
             VirtualKeyCode::Down => {
-                scene.target_scroll.y += 50.0 / scene.target_zoom;
+                scene.camera.pan(&Vector2 { x: 0.0, y: -50.0 }, false);
             }
             VirtualKeyCode::P => {
-                scene.show_points = !scene.show_points;
+                scene.need_toggle_pinned = true;
             }
             VirtualKeyCode::B => {
                 scene.draw_background = !scene.draw_background;
@@ -1052,61 +2054,92 @@ fn update_inputs(
             VirtualKeyCode::A => {
                 scene.target_stroke_width /= 0.8;
             }
+            VirtualKeyCode::Z if scene.modifiers.ctrl() && scene.modifiers.shift() => {
+                scene.need_redo = true;
+            }
+            VirtualKeyCode::Z if scene.modifiers.ctrl() => {
+                scene.need_undo = true;
+            }
+            VirtualKeyCode::Y if scene.modifiers.ctrl() => {
+                scene.need_redo = true;
+            }
             VirtualKeyCode::Z => {
                 scene.target_stroke_width *= 0.8;
             }
             VirtualKeyCode::Space => {
                 scene.need_reset = true;
-                scene.need_update_gpu = true;
+            }
+            VirtualKeyCode::O => {
+                scene.need_remove_overlaps = true;
+            }
+            VirtualKeyCode::RBracket => {
+                scene.steps_per_frame += 1;
+            }
+            VirtualKeyCode::LBracket => {
+                scene.steps_per_frame = scene.steps_per_frame.saturating_sub(1).max(1);
+            }
+            VirtualKeyCode::Delete | VirtualKeyCode::Back => {
+                scene.need_delete = true;
             }
             _key => {}
         },
-        Event::WindowEvent { 
+        Event::WindowEvent {
+            event: WindowEvent::ModifiersChanged(modifiers),
+            ..
+        } => {
+            scene.modifiers = modifiers;
+        }
+        Event::WindowEvent {
             event: WindowEvent::MouseInput {
-                state: ElementState::Pressed,
+                state,
                 button: MouseButton::Left,
                 ..
             },
             ..
         } => {
-            let mut bubble = Bubble {
-                position: mouse_position.clone(),
-                size: 100.0,
-                // size: 100.0,
-                v: Vector2{x: 0.0, y: 0.0},
-                a: Vector2{x: 0.0, y: 0.0},
-                meshes: [Mesh::default(), Mesh::default(), Mesh::default()],
-                label: String::from("added"),
+            let cursor = Vector2 {
+                x: scene.cursor_position.0,
+                y: scene.cursor_position.1,
             };
-            bubble.generate_mesh(id, builder);
-            for mesh in bubble.meshes.iter_mut() {
-                mesh.create_buffer_and_upload(&device);
+            match state {
+                ElementState::Pressed => {
+                    scene.mouse_down_position = Some(cursor);
+                    // what's under the cursor decides between dragging bubbles and drawing a selection box
+                    scene.pending_press = Some(cursor);
+                }
+                ElementState::Released => {
+                    // shift-dragging from one bubble to another links them
+                    if let Some(start) = scene.mouse_down_position.take() {
+                        if cursor.sub(&start).len() <= CLICK_DISTANCE {
+                            let now = Instant::now();
+                            let is_double_click = scene.last_click.is_some_and(|(time, position)| {
+                                now.duration_since(time) <= DOUBLE_CLICK_TIME
+                                    && cursor.sub(&position).len() <= CLICK_DISTANCE
+                            });
+                            // the second click of a double click isn't a click of its own,
+                            // or a double click on nothing would add a second bubble on top of the first
+                            if is_double_click {
+                                scene.pending_zoom_to_bubble = Some(cursor);
+                                scene.last_click = None;
+                            } else {
+                                scene.pending_click = Some(cursor);
+                                scene.last_click = Some((now, cursor));
+                            }
+                        } else if scene.modifiers.shift() {
+                            scene.pending_link = Some((start, cursor));
+                        }
+                    }
+                }
             }
-            bubbles.push(bubble);
-            scene.need_update_gpu = true;
-        },
-        Event::WindowEvent { 
-            event: WindowEvent::CursorMoved {
-                position,
-                ..
-            },
-            ..
-        } => {
-            mouse_position.x = position.x as f32;
-            mouse_position.y = position.y as f32;
         },
         _evt => {
             //println!("{:?}", _evt);
         }
     }
-    //println!(" -- zoom: {}, scroll: {:?}", scene.target_zoom, scene.target_scroll);
-
-    scene.zoom += (scene.target_zoom - scene.zoom) / 3.0;
-    scene.scroll = scene.scroll + (scene.target_scroll - scene.scroll) / 3.0;
     scene.stroke_width =
         scene.stroke_width + (scene.target_stroke_width - scene.stroke_width) / 5.0;
 
     *control_flow = ControlFlow::Poll;
 
-    return true;
+    true
 }
@@ -0,0 +1,311 @@
+use std::collections::{BinaryHeap, VecDeque};
+
+use crate::adjacency::Adjacency;
+use crate::bubble::Bubble;
+use crate::edge::Edge;
+use crate::path::Candidate;
+
+// the names the metrics are stored under in `Bubble::attributes`
+pub const DEGREE: &str = "degree";
+pub const WEIGHTED_DEGREE: &str = "weighted_degree";
+pub const BETWEENNESS: &str = "betweenness";
+pub const CLOSENESS: &str = "closeness";
+pub const EIGENVECTOR: &str = "eigenvector";
+pub const PAGERANK: &str = "pagerank";
+pub const ALL: [&str; 6] = [DEGREE, WEIGHTED_DEGREE, BETWEENNESS, CLOSENESS, EIGENVECTOR, PAGERANK];
+
+// the iterative metrics stop once a step changes the scores by less than this, summed over all the bubbles
+const TOLERANCE: f32 = 1e-6;
+const MAX_ITERATIONS: usize = 100;
+const PAGERANK_DAMPING: f32 = 0.85;
+
+/// Works out every metric and stores them in the bubbles' attributes, over whatever was stored before.
+/// The distances are hops, or `1 / weight` when the weights differ, like `path::shortest_path` has them.
+pub fn compute_all(bubbles: &mut [Bubble], edges: &[Edge]) {
+    let adjacency = Adjacency::new(bubbles.len(), edges.iter().map(|e| (e.from, e.to, e.weight)));
+    let (betweenness, closeness) = betweenness_and_closeness(&adjacency);
+    let scores = [
+        degree(&adjacency),
+        weighted_degree(&adjacency),
+        betweenness,
+        closeness,
+        eigenvector(&adjacency),
+        pagerank(&adjacency),
+    ];
+    for (name, values) in ALL.iter().zip(scores.iter()) {
+        store(bubbles, name, values);
+    }
+}
+
+/// Works out just the one metric of `ALL` and stores it like `compute_all` does, the others are left as they were.
+pub fn compute(bubbles: &mut [Bubble], edges: &[Edge], name: &str) {
+    let adjacency = Adjacency::new(bubbles.len(), edges.iter().map(|e| (e.from, e.to, e.weight)));
+    let values = match name {
+        DEGREE => degree(&adjacency),
+        WEIGHTED_DEGREE => weighted_degree(&adjacency),
+        BETWEENNESS => betweenness_and_closeness(&adjacency).0,
+        CLOSENESS => betweenness_and_closeness(&adjacency).1,
+        EIGENVECTOR => eigenvector(&adjacency),
+        PAGERANK => pagerank(&adjacency),
+        _ => return,
+    };
+    store(bubbles, name, &values);
+}
+
+fn store(bubbles: &mut [Bubble], name: &str, values: &[f32]) {
+    for (bubble, &value) in bubbles.iter_mut().zip(values.iter()) {
+        bubble.attributes.insert(String::from(name), value);
+    }
+}
+
+pub fn degree(adjacency: &Adjacency) -> Vec<f32> {
+    (0..adjacency.bubble_count())
+        .map(|i| (adjacency.offsets[i + 1] - adjacency.offsets[i]) as f32)
+        .collect()
+}
+
+pub fn weighted_degree(adjacency: &Adjacency) -> Vec<f32> {
+    (0..adjacency.bubble_count())
+        .map(|i| adjacency.neighbours_of(i).map(|(_, weight, _)| weight).sum())
+        .collect()
+}
+
+/// Brandes' betweenness, normalised by the number of pairs the others make, and the closeness,
+/// scaled down by the share of the graph a bubble can reach so the small components don't come out on top.
+/// Both come from the same single-source searches, one from every bubble.
+pub fn betweenness_and_closeness(adjacency: &Adjacency) -> (Vec<f32>, Vec<f32>) {
+    let n = adjacency.bubble_count();
+    let weighted = adjacency.weights.windows(2).any(|w| w[0] != w[1]);
+    let mut betweenness = vec![0.0; n];
+    let mut closeness = vec![0.0; n];
+    for (source, source_closeness) in closeness.iter_mut().enumerate() {
+        let search = if weighted {
+            weighted_search(adjacency, source)
+        } else {
+            hop_search(adjacency, source)
+        };
+
+        let reached = search.order.len();
+        let distance_sum: f32 = search.order.iter().map(|&i| search.distances[i]).sum();
+        if reached > 1 && distance_sum > 0.0 {
+            let reached_share = (reached - 1) as f32 / (n - 1) as f32;
+            *source_closeness = (reached - 1) as f32 / distance_sum * reached_share;
+        }
+
+        // the dependencies are accumulated from the furthest bubble back to the source
+        let mut dependency = vec![0.0; n];
+        for &w in search.order.iter().rev() {
+            for &v in search.predecessors[w].iter() {
+                dependency[v] += search.path_counts[v] / search.path_counts[w] * (1.0 + dependency[w]);
+            }
+            if w != source {
+                betweenness[w] += dependency[w];
+            }
+        }
+    }
+    // every pair was counted from both of its ends
+    if n > 2 {
+        let pair_count = ((n - 1) * (n - 2)) as f32;
+        for b in betweenness.iter_mut() {
+            *b /= pair_count;
+        }
+    }
+    (betweenness, closeness)
+}
+
+/// The principal eigenvector of the weighted adjacency matrix, by power iteration, scaled to a length of one.
+/// Every step keeps the previous scores too, so it converges on bipartite graphs where the plain iteration flips back and forth.
+pub fn eigenvector(adjacency: &Adjacency) -> Vec<f32> {
+    let n = adjacency.bubble_count();
+    let mut scores = vec![1.0 / (n as f32).sqrt(); n];
+    for _ in 0..MAX_ITERATIONS {
+        let mut next = scores.clone();
+        for (i, score) in next.iter_mut().enumerate() {
+            *score += adjacency
+                .neighbours_of(i)
+                .map(|(j, weight, _)| weight * scores[j])
+                .sum::<f32>();
+        }
+        let length = next.iter().map(|s| s * s).sum::<f32>().sqrt();
+        if length == 0.0 {
+            return next;
+        }
+        next.iter_mut().for_each(|s| *s /= length);
+        let change: f32 = next.iter().zip(scores.iter()).map(|(a, b)| (a - b).abs()).sum();
+        scores = next;
+        if change < TOLERANCE * n as f32 {
+            break;
+        }
+    }
+    scores
+}
+
+/// PageRank with the edges followed both ways in proportion to their weight.
+/// The rank of a bubble without edges is spread over everyone.
+pub fn pagerank(adjacency: &Adjacency) -> Vec<f32> {
+    let n = adjacency.bubble_count();
+    if n == 0 {
+        return vec![];
+    }
+    let out_weights = weighted_degree(adjacency);
+    let mut ranks = vec![1.0 / n as f32; n];
+    for _ in 0..MAX_ITERATIONS {
+        let dangling: f32 = (0..n).filter(|&i| out_weights[i] <= 0.0).map(|i| ranks[i]).sum();
+        let base = (1.0 - PAGERANK_DAMPING + PAGERANK_DAMPING * dangling) / n as f32;
+        let mut next = vec![base; n];
+        for (i, &rank) in ranks.iter().enumerate() {
+            if out_weights[i] > 0.0 {
+                for (j, weight, _) in adjacency.neighbours_of(i) {
+                    next[j] += PAGERANK_DAMPING * rank * weight / out_weights[i];
+                }
+            }
+        }
+        let change: f32 = next.iter().zip(ranks.iter()).map(|(a, b)| (a - b).abs()).sum();
+        ranks = next;
+        if change < TOLERANCE {
+            break;
+        }
+    }
+    ranks
+}
+
+/// The values of a metric scaled into 0..1 over the bubbles, zero for the bubbles it hasn't been computed for.
+pub fn normalized(bubbles: &[Bubble], name: &str) -> Vec<f32> {
+    let values: Vec<f32> = bubbles
+        .iter()
+        .map(|b| b.attributes.get(name).copied().unwrap_or(0.0))
+        .collect();
+    let min = values.iter().copied().fold(f32::MAX, f32::min);
+    let max = values.iter().copied().fold(f32::MIN, f32::max);
+    let range = max - min;
+    values
+        .iter()
+        .map(|v| if range > 0.0 { (v - min) / range } else { 0.0 })
+        .collect()
+}
+
+/// Sizes the bubbles from `min_size` to `max_size` by a metric.
+pub fn size_by(bubbles: &mut [Bubble], name: &str, min_size: f32, max_size: f32) {
+    let values = normalized(bubbles, name);
+    for (bubble, value) in bubbles.iter_mut().zip(values) {
+        bubble.size = min_size + (max_size - min_size) * value;
+    }
+}
+
+// what a single-source search leaves for Brandes' accumulation
+struct Search {
+    // the reached bubbles, closest first
+    order: Vec<usize>,
+    distances: Vec<f32>,
+    // how many shortest paths lead to each bubble, and the bubbles right before it on them
+    path_counts: Vec<f32>,
+    predecessors: Vec<Vec<usize>>,
+}
+
+impl Search {
+    fn new(n: usize, source: usize) -> Self {
+        let mut search = Search {
+            order: vec![],
+            distances: vec![f32::INFINITY; n],
+            path_counts: vec![0.0; n],
+            predecessors: vec![vec![]; n],
+        };
+        search.distances[source] = 0.0;
+        search.path_counts[source] = 1.0;
+        search
+    }
+}
+
+fn hop_search(adjacency: &Adjacency, source: usize) -> Search {
+    let mut search = Search::new(adjacency.bubble_count(), source);
+    let mut queue = VecDeque::new();
+    queue.push_back(source);
+    while let Some(v) = queue.pop_front() {
+        search.order.push(v);
+        for (w, _, _) in adjacency.neighbours_of(v) {
+            if search.distances[w].is_infinite() {
+                search.distances[w] = search.distances[v] + 1.0;
+                queue.push_back(w);
+            }
+            if search.distances[w] == search.distances[v] + 1.0 {
+                search.path_counts[w] += search.path_counts[v];
+                search.predecessors[w].push(v);
+            }
+        }
+    }
+    search
+}
+
+fn weighted_search(adjacency: &Adjacency, source: usize) -> Search {
+    let mut search = Search::new(adjacency.bubble_count(), source);
+    let mut settled = vec![false; adjacency.bubble_count()];
+    let mut heap = BinaryHeap::new();
+    heap.push(Candidate { distance: 0.0, bubble: source });
+    while let Some(Candidate { distance, bubble: v }) = heap.pop() {
+        if settled[v] {
+            continue;
+        }
+        settled[v] = true;
+        search.order.push(v);
+        for (w, weight, _) in adjacency.neighbours_of(v) {
+            if weight <= 0.0 || settled[w] {
+                continue;
+            }
+            let through_v = distance + 1.0 / weight;
+            if through_v < search.distances[w] {
+                search.distances[w] = through_v;
+                search.path_counts[w] = search.path_counts[v];
+                search.predecessors[w] = vec![v];
+                heap.push(Candidate { distance: through_v, bubble: w });
+            } else if through_v == search.distances[w] {
+                search.path_counts[w] += search.path_counts[v];
+                search.predecessors[w].push(v);
+            }
+        }
+    }
+    search
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_graph::graph;
+
+    fn adjacency(n: usize, edges: &[(usize, usize)]) -> Adjacency {
+        Adjacency::new(n, edges.iter().map(|&(from, to)| (from, to, 1.0)))
+    }
+
+    fn assert_close(actual: &[f32], expected: &[f32]) {
+        assert_eq!(actual.len(), expected.len());
+        for (a, e) in actual.iter().zip(expected.iter()) {
+            assert!((a - e).abs() < 1e-4, "{:?} isn't {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn betweenness_and_closeness_of_a_path() {
+        let (betweenness, closeness) = betweenness_and_closeness(&adjacency(4, &[(0, 1), (1, 2), (2, 3)]));
+        assert_close(&betweenness, &[0.0, 2.0 / 3.0, 2.0 / 3.0, 0.0]);
+        assert_close(&closeness, &[0.5, 0.75, 0.75, 0.5]);
+    }
+
+    #[test]
+    fn the_centre_of_a_star_is_on_every_path() {
+        let star = adjacency(5, &[(0, 1), (0, 2), (0, 3), (0, 4)]);
+        let (betweenness, _) = betweenness_and_closeness(&star);
+        assert_close(&betweenness, &[1.0, 0.0, 0.0, 0.0, 0.0]);
+        assert_close(&degree(&star), &[4.0, 1.0, 1.0, 1.0, 1.0]);
+
+        let ranks = pagerank(&star);
+        assert!((ranks.iter().sum::<f32>() - 1.0).abs() < 1e-4);
+        assert!(ranks[1..].iter().all(|&rank| rank < ranks[0]));
+    }
+
+    #[test]
+    fn compute_stores_only_the_one_metric() {
+        let (mut bubbles, edges) = graph(3, &[(0, 1), (1, 2)]);
+        compute(&mut bubbles, &edges, BETWEENNESS);
+        assert_eq!(bubbles[1].attributes.get(BETWEENNESS), Some(&1.0));
+        assert!(bubbles.iter().all(|b| b.attributes.len() == 1));
+    }
+}
@@ -0,0 +1,215 @@
+use std::collections::BTreeMap;
+
+use crate::adjacency::Adjacency;
+use crate::bubble::Bubble;
+use crate::edge::Edge;
+use crate::forcelayout::forcelayout;
+use crate::math::Vector2;
+use crate::mesh::Mesh;
+use crate::metrics;
+use crate::spectral::hashed_values;
+
+// the coarsening stops at this many bubbles, or once a level hardly shrinks the graph
+const COARSEST_SIZE: usize = 32;
+const MIN_REDUCTION: f32 = 0.9;
+// force steps on the coarsest graph, and at every level on the way back up
+pub const COARSEST_STEPS: u32 = 500;
+pub const REFINE_STEPS: u32 = 100;
+// the bubbles are spread this far around the one they were merged into, as a share of its radius
+const PROLONG_JITTER: f32 = 0.5;
+
+// a coarser copy of the graph, with the bubble every bubble of the level below it was merged into
+struct Level {
+    parents: Vec<usize>,
+    bubbles: Vec<Bubble>,
+    edges: Vec<Edge>,
+}
+
+/// Multilevel layout, after Walshaw: the graph is coarsened by matching every bubble with its heaviest neighbour
+/// until it's small, the coarsest graph is laid out, and every level is refined from the positions of the one above it.
+/// `refine` runs the given number of force steps, on the cpu with `cpu_refine` or on the gpu simulation.
+/// The pinned bubbles stay put, a coarse bubble holding one stays with it.
+pub fn multilevel(bubbles: &mut [Bubble], edges: &mut [Edge], mut refine: impl FnMut(&mut [Bubble], &mut [Edge], u32)) {
+    let mut levels: Vec<Level> = vec![];
+    loop {
+        let (fine_bubbles, fine_edges) = match levels.last() {
+            Some(level) => (&level.bubbles[..], &level.edges[..]),
+            None => (&bubbles[..], &edges[..]),
+        };
+        if fine_bubbles.len() <= COARSEST_SIZE {
+            break;
+        }
+        let level = coarsen(fine_bubbles, fine_edges);
+        if level.bubbles.len() as f32 > MIN_REDUCTION * fine_bubbles.len() as f32 {
+            break;
+        }
+        levels.push(level);
+    }
+
+    match levels.last_mut() {
+        Some(coarsest) => refine(&mut coarsest.bubbles, &mut coarsest.edges, COARSEST_STEPS),
+        None => {
+            refine(bubbles, edges, COARSEST_STEPS);
+            return;
+        }
+    }
+    for l in (0..levels.len()).rev() {
+        let (finer, coarser) = levels.split_at_mut(l);
+        let coarse = &coarser[0];
+        match finer.last_mut() {
+            Some(fine) => {
+                prolong(&mut fine.bubbles, &coarse.parents, &coarse.bubbles);
+                refine(&mut fine.bubbles, &mut fine.edges, REFINE_STEPS);
+            }
+            None => {
+                prolong(bubbles, &coarse.parents, &coarse.bubbles);
+                refine(bubbles, edges, REFINE_STEPS);
+            }
+        }
+    }
+}
+
+/// Refines a level with `forcelayout`, one step at a time, on the finest levels of big graphs only the near bubbles push.
+pub fn cpu_refine(bubbles: &mut [Bubble], edges: &mut [Edge], steps: u32) {
+    for _ in 0..steps {
+        forcelayout(bubbles, edges);
+    }
+}
+
+fn coarsen(bubbles: &[Bubble], edges: &[Edge]) -> Level {
+    let n = bubbles.len();
+    let adjacency = Adjacency::new(n, edges.iter().map(|e| (e.from, e.to, e.weight)));
+
+    // the bubbles with the fewest edges pick first, so the leaves of a star don't all lose out to one of them
+    let degrees = metrics::degree(&adjacency);
+    let mut order: Vec<usize> = (0..n).collect();
+    order.sort_by(|&a, &b| degrees[a].total_cmp(&degrees[b]));
+
+    let mut parents = vec![usize::MAX; n];
+    let mut groups: Vec<Vec<usize>> = vec![];
+    for &i in order.iter() {
+        if parents[i] != usize::MAX {
+            continue;
+        }
+        let partner = adjacency
+            .neighbours_of(i)
+            .filter(|&(j, _, _)| j != i && parents[j] == usize::MAX && !(bubbles[i].pinned && bubbles[j].pinned))
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(j, _, _)| j);
+        parents[i] = groups.len();
+        let mut group = vec![i];
+        if let Some(j) = partner {
+            parents[j] = groups.len();
+            group.push(j);
+        }
+        groups.push(group);
+    }
+
+    let coarse_bubbles = groups.iter().map(|group| merge(bubbles, group)).collect();
+
+    // the edges inside a group go, the ones between two groups add up
+    let mut weights: BTreeMap<(usize, usize), f32> = BTreeMap::new();
+    for edge in edges {
+        let (a, b) = (parents[edge.from], parents[edge.to]);
+        if a != b {
+            *weights.entry((a.min(b), a.max(b))).or_insert(0.0) += edge.weight;
+        }
+    }
+    let coarse_edges = weights
+        .into_iter()
+        .map(|((from, to), weight)| Edge {
+            from,
+            to,
+            weight,
+            pull_force: 0.0,
+            mesh: Mesh::default(),
+            route: vec![],
+        })
+        .collect();
+
+    Level {
+        parents,
+        bubbles: coarse_bubbles,
+        edges: coarse_edges,
+    }
+}
+
+// one bubble as big as the group put together, where its pinned bubble is or else in its middle
+fn merge(bubbles: &[Bubble], group: &[usize]) -> Bubble {
+    let pinned = group.iter().copied().find(|&i| bubbles[i].pinned);
+    let position = match pinned {
+        Some(i) => bubbles[i].position,
+        None => group
+            .iter()
+            .fold(Vector2::new(), |sum, &i| sum.add(&bubbles[i].position))
+            .mul_s(1.0 / group.len() as f32),
+    };
+    Bubble {
+        position,
+        size: group.iter().map(|&i| bubbles[i].size * bubbles[i].size).sum::<f32>().sqrt(),
+        v: Vector2::new(),
+        a: Vector2::new(),
+        meshes: [Mesh::default(), Mesh::default(), Mesh::default()],
+        label: String::new(),
+        pinned: pinned.is_some(),
+        attributes: BTreeMap::new(),
+    }
+}
+
+// every bubble starts around the coarse bubble it was merged into, spread the same way every time
+fn prolong(bubbles: &mut [Bubble], parents: &[usize], coarse: &[Bubble]) {
+    let offsets = hashed_values(2 * bubbles.len(), coarse.len());
+    for (i, (bubble, &parent)) in bubbles.iter_mut().zip(parents).enumerate() {
+        bubble.v = Vector2::new();
+        if bubble.pinned {
+            continue;
+        }
+        let jitter = Vector2 {
+            x: offsets[2 * i] as f32,
+            y: offsets[2 * i + 1] as f32,
+        };
+        bubble.position = coarse[parent]
+            .position
+            .add(&jitter.mul_s(PROLONG_JITTER * coarse[parent].radius()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_graph::grid;
+
+    #[test]
+    fn the_pinned_bubbles_stay_put_on_every_level() {
+        let (mut bubbles, mut edges) = grid(10, 6);
+        bubbles[17].pinned = true;
+        bubbles[42].pinned = true;
+        let pinned = |bubbles: &[Bubble]| -> Vec<(f32, f32)> {
+            [17, 42].iter().map(|&i| (bubbles[i].position.x, bubbles[i].position.y)).collect()
+        };
+        let pinned_at = pinned(&bubbles);
+        multilevel(&mut bubbles, &mut edges, cpu_refine);
+        assert_eq!(pinned(&bubbles), pinned_at);
+        assert!(bubbles.iter().all(|b| b.position.x.is_finite() && b.position.y.is_finite()));
+    }
+
+    #[test]
+    fn coarsening_merges_the_bubbles_in_pairs() {
+        let (bubbles, edges) = grid(10, 6);
+        let level = coarsen(&bubbles, &edges);
+        assert!(level.bubbles.len() >= bubbles.len() / 2);
+        assert!(level.bubbles.len() < bubbles.len());
+        assert!(level.parents.iter().all(|&parent| parent < level.bubbles.len()));
+    }
+
+    #[test]
+    fn the_layout_is_the_same_every_time() {
+        let run = || {
+            let (mut bubbles, mut edges) = grid(10, 6);
+            multilevel(&mut bubbles, &mut edges, cpu_refine);
+            bubbles.iter().map(|b| (b.position.x, b.position.y)).collect::<Vec<_>>()
+        };
+        assert_eq!(run(), run());
+    }
+}
+
@@ -0,0 +1,144 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, VecDeque};
+
+use crate::adjacency::Adjacency;
+
+/// A route through the graph, the bubbles from one end to the other and the edges between them.
+#[derive(Default)]
+pub struct GraphPath {
+    pub bubbles: Vec<usize>,
+    pub edges: Vec<usize>,
+}
+
+/// The shortest path from `from` to `to`, or `None` if they aren't connected.
+/// The edges are weighted when their weights differ, a heavier edge is a stronger tie and so a shorter one,
+/// otherwise every edge counts as one hop.
+pub fn shortest_path(adjacency: &Adjacency, from: usize, to: usize) -> Option<GraphPath> {
+    let weighted = adjacency.weights.windows(2).any(|w| w[0] != w[1]);
+    if weighted {
+        dijkstra(adjacency, from, to)
+    } else {
+        bfs(adjacency, from, to)
+    }
+}
+
+/// The path with the fewest hops.
+pub fn bfs(adjacency: &Adjacency, from: usize, to: usize) -> Option<GraphPath> {
+    // how every bubble was first reached, as (previous bubble, edge)
+    let mut reached_by: Vec<Option<(usize, usize)>> = vec![None; adjacency.bubble_count()];
+    let mut queue = VecDeque::new();
+    queue.push_back(from);
+    while let Some(i) = queue.pop_front() {
+        if i == to {
+            return Some(walk_back(&reached_by, from, to));
+        }
+        for (j, _, edge) in adjacency.neighbours_of(i) {
+            if j != from && reached_by[j].is_none() {
+                reached_by[j] = Some((i, edge));
+                queue.push_back(j);
+            }
+        }
+    }
+    None
+}
+
+/// The path with the smallest total length, an edge is `1 / weight` long and the ones without a positive weight are left out.
+pub fn dijkstra(adjacency: &Adjacency, from: usize, to: usize) -> Option<GraphPath> {
+    let mut distances = vec![f32::INFINITY; adjacency.bubble_count()];
+    let mut reached_by: Vec<Option<(usize, usize)>> = vec![None; adjacency.bubble_count()];
+    let mut heap = BinaryHeap::new();
+    distances[from] = 0.0;
+    heap.push(Candidate { distance: 0.0, bubble: from });
+    while let Some(Candidate { distance, bubble: i }) = heap.pop() {
+        if i == to {
+            return Some(walk_back(&reached_by, from, to));
+        }
+        // a stale entry, the bubble was reached by a shorter way since
+        if distance > distances[i] {
+            continue;
+        }
+        for (j, weight, edge) in adjacency.neighbours_of(i) {
+            if weight <= 0.0 {
+                continue;
+            }
+            let through_i = distance + 1.0 / weight;
+            if through_i < distances[j] {
+                distances[j] = through_i;
+                reached_by[j] = Some((i, edge));
+                heap.push(Candidate { distance: through_i, bubble: j });
+            }
+        }
+    }
+    None
+}
+
+fn walk_back(reached_by: &[Option<(usize, usize)>], from: usize, to: usize) -> GraphPath {
+    let mut path = GraphPath::default();
+    let mut i = to;
+    path.bubbles.push(i);
+    while i != from {
+        let (previous, edge) = reached_by[i].unwrap();
+        path.edges.push(edge);
+        path.bubbles.push(previous);
+        i = previous;
+    }
+    path.bubbles.reverse();
+    path.edges.reverse();
+    path
+}
+
+/// A bubble waiting in a `BinaryHeap` with how far it is, the heap pops the closest one first.
+pub struct Candidate {
+    pub distance: f32,
+    pub bubble: usize,
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.distance.total_cmp(&self.distance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 0 to 3 through 1 or through 2, the way through 2 is made of heavier edges
+    fn diamond() -> Adjacency {
+        Adjacency::new(5, [(0, 1, 1.0), (1, 3, 1.0), (0, 2, 4.0), (2, 3, 4.0)].iter().copied())
+    }
+
+    #[test]
+    fn the_heavier_edges_make_the_shorter_path() {
+        let path = shortest_path(&diamond(), 0, 3).unwrap();
+        assert_eq!(path.bubbles, [0, 2, 3]);
+        assert_eq!(path.edges, [2, 3]);
+    }
+
+    #[test]
+    fn bfs_counts_hops() {
+        let adjacency = Adjacency::new(4, [(0, 1, 1.0), (1, 2, 1.0), (2, 3, 1.0), (0, 3, 1.0)].iter().copied());
+        let path = shortest_path(&adjacency, 0, 2).unwrap();
+        assert_eq!(path.bubbles.len(), 3);
+        assert_eq!(shortest_path(&adjacency, 1, 3).unwrap().edges.len(), 2);
+    }
+
+    #[test]
+    fn there_is_no_path_to_a_bubble_on_its_own() {
+        assert!(shortest_path(&diamond(), 0, 4).is_none());
+    }
+}
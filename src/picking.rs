@@ -0,0 +1,85 @@
+use crate::bubble::Bubble;
+use crate::edge::Edge;
+use crate::camera::Camera;
+use crate::math::Vector2;
+
+// how far from an edge a click still picks it, in pixels
+const EDGE_PICK_DISTANCE: f32 = 4.0;
+
+/// The bubble under `cursor`, the one with the closest centre if they overlap.
+pub fn pick_bubble(bubbles: &[Bubble], camera: &Camera, cursor: &Vector2) -> Option<usize> {
+    let mut picked = None;
+    let mut picked_distance = f32::MAX;
+    for (i, bubble) in bubbles.iter().enumerate() {
+        let distance = camera.to_screen(&bubble.position).sub(cursor).len();
+        if distance <= camera.to_screen_length(bubble.radius()) && distance < picked_distance {
+            picked = Some(i);
+            picked_distance = distance;
+        }
+    }
+    picked
+}
+
+/// The edge passing closest to `cursor`, if any is within a few pixels of it.
+pub fn pick_edge(edges: &[Edge], bubbles: &[Bubble], camera: &Camera, cursor: &Vector2) -> Option<usize> {
+    let mut picked = None;
+    let mut picked_distance = EDGE_PICK_DISTANCE;
+    for (i, edge) in edges.iter().enumerate() {
+        let from = camera.to_screen(&bubbles[edge.from].position);
+        let to = camera.to_screen(&bubbles[edge.to].position);
+        let distance = distance_to_segment(cursor, &from, &to);
+        if distance <= picked_distance {
+            picked = Some(i);
+            picked_distance = distance;
+        }
+    }
+    picked
+}
+
+/// The bubbles with their centre inside the box spanned by the screen points `a` and `b`.
+pub fn bubbles_in_box(bubbles: &[Bubble], camera: &Camera, a: &Vector2, b: &Vector2) -> Vec<usize> {
+    let min = Vector2 { x: a.x.min(b.x), y: a.y.min(b.y) };
+    let max = Vector2 { x: a.x.max(b.x), y: a.y.max(b.y) };
+    (0..bubbles.len())
+        .filter(|&i| {
+            let p = camera.to_screen(&bubbles[i].position);
+            p.x >= min.x && p.x <= max.x && p.y >= min.y && p.y <= max.y
+        })
+        .collect()
+}
+
+/// The bubbles with their centre inside the lasso, a polygon in screen points that closes on itself.
+pub fn bubbles_in_lasso(bubbles: &[Bubble], camera: &Camera, lasso: &[Vector2]) -> Vec<usize> {
+    if lasso.len() < 3 {
+        return vec![];
+    }
+    (0..bubbles.len())
+        .filter(|&i| contains_point(lasso, &camera.to_screen(&bubbles[i].position)))
+        .collect()
+}
+
+// even-odd rule: a ray going right from `p` crosses the outline an odd number of times when it's inside
+fn contains_point(polygon: &[Vector2], p: &Vector2) -> bool {
+    let mut inside = false;
+    let mut j = polygon.len() - 1;
+    for i in 0..polygon.len() {
+        let (a, b) = (&polygon[i], &polygon[j]);
+        if (a.y > p.y) != (b.y > p.y) && p.x < (b.x - a.x) * (p.y - a.y) / (b.y - a.y) + a.x {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+fn distance_to_segment(p: &Vector2, a: &Vector2, b: &Vector2) -> f32 {
+    let ab = b.sub(a);
+    let ap = p.sub(a);
+    let length_squared = ab.sqrt_len();
+    let t = if length_squared > 0.0 {
+        ((ap.x * ab.x + ap.y * ab.y) / length_squared).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    p.sub(&a.add(&ab.mul_s(t))).len()
+}
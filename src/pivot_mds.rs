@@ -0,0 +1,132 @@
+use crate::adjacency::Adjacency;
+use crate::bubble::Bubble;
+use crate::edge::Edge;
+use crate::math::Vector2;
+use crate::spectral::{hashed_values, normalized, spread_apart};
+use crate::stress::{max_min_pivots, EDGE_LENGTH};
+use crate::tree::move_into_place;
+
+// Brandes and Pich found 50 pivots as good as any more
+const PIVOT_COUNT: usize = 50;
+// power iteration steps on the pivots' matrix at most, it's small so they're cheap
+const MAX_ITERATIONS: usize = 1000;
+const TOLERANCE: f64 = 1e-12;
+
+/// Pivot MDS (Brandes and Pich, 2006): classical MDS on the distances to a few pivots only,
+/// which takes a breadth first search from every pivot and a 2 x 2 eigenproblem's worth of work on top,
+/// so it's quick enough to start graphs of tens of thousands of bubbles from. The pinned bubbles stay put.
+pub fn pivot_mds(bubbles: &mut [Bubble], edges: &[Edge]) {
+    if bubbles.is_empty() {
+        return;
+    }
+    let positions = pivot_mds_positions(bubbles, edges);
+    move_into_place(bubbles, &positions);
+}
+
+/// The pivot MDS layout around the origin, in `EDGE_LENGTH` units, with the bubbles the pivots can't tell apart spread apart.
+pub fn pivot_mds_positions(bubbles: &[Bubble], edges: &[Edge]) -> Vec<Vector2> {
+    let n = bubbles.len();
+    let adjacency = Adjacency::new(n, edges.iter().map(|e| (e.from, e.to, e.weight)));
+    let (_, distances) = max_min_pivots(&adjacency, PIVOT_COUNT.min(n));
+    let k = distances.len();
+
+    // the squared distances double centred, a column for every pivot: c = -1/2 (d² - row mean - column mean + mean)
+    let mut columns: Vec<Vec<f64>> = distances
+        .iter()
+        .map(|row| row.iter().map(|&d| (d as f64) * (d as f64)).collect())
+        .collect();
+    let column_means: Vec<f64> = columns.iter().map(|column| column.iter().sum::<f64>() / n as f64).collect();
+    let row_means: Vec<f64> = (0..n)
+        .map(|i| columns.iter().map(|column| column[i]).sum::<f64>() / k as f64)
+        .collect();
+    let mean = column_means.iter().sum::<f64>() / k as f64;
+    for (column, column_mean) in columns.iter_mut().zip(column_means.iter()) {
+        for (c, row_mean) in column.iter_mut().zip(row_means.iter()) {
+            *c = -0.5 * (*c - row_mean - column_mean + mean);
+        }
+    }
+
+    // the two main axes are the eigenvectors of CᵀC with the largest eigenvalues, it's only pivots by pivots
+    let product: Vec<Vec<f64>> = columns
+        .iter()
+        .map(|a| columns.iter().map(|b| a.iter().zip(b).map(|(a, b)| a * b).sum()).collect())
+        .collect();
+    let mut axes: Vec<Vec<f64>> = vec![];
+    for axis in 0..2 {
+        let mut v = normalized(hashed_values(k, axis));
+        for _ in 0..MAX_ITERATIONS {
+            for previous in axes.iter() {
+                let along: f64 = v.iter().zip(previous).map(|(a, b)| a * b).sum();
+                for (x, p) in v.iter_mut().zip(previous) {
+                    *x -= along * p;
+                }
+            }
+            let next = normalized(product.iter().map(|row| row.iter().zip(v.iter()).map(|(a, b)| a * b).sum()).collect());
+            let settled = next.iter().zip(v.iter()).map(|(a, b)| a * b).sum::<f64>() > 1.0 - TOLERANCE;
+            v = next;
+            if settled {
+                break;
+            }
+        }
+        axes.push(v);
+    }
+
+    // every bubble is projected on the axes, through its row of C
+    let project = |i: usize, axis: &[f64]| -> f32 { columns.iter().zip(axis).map(|(column, a)| column[i] * a).sum::<f64>() as f32 };
+    let mut positions: Vec<Vector2> = (0..n)
+        .map(|i| Vector2 {
+            x: project(i, &axes[0]),
+            y: project(i, &axes[1]),
+        })
+        .collect();
+
+    // the projection keeps the shape but not the size, it's scaled so the edges are `EDGE_LENGTH` long on average
+    let lengths: Vec<f32> = edges
+        .iter()
+        .filter(|e| e.from != e.to)
+        .map(|e| positions[e.from].sub(&positions[e.to]).len())
+        .collect();
+    let mean_length = lengths.iter().sum::<f32>() / lengths.len().max(1) as f32;
+    if mean_length > 0.0 {
+        for p in positions.iter_mut() {
+            *p = p.mul_s(EDGE_LENGTH / mean_length);
+        }
+    }
+    spread_apart(&mut positions);
+    positions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_graph::grid;
+
+    #[test]
+    fn a_grid_keeps_its_neighbourhoods() {
+        let (width, height) = (10, 6);
+        let (bubbles, edges) = grid(width, height);
+        let positions = pivot_mds_positions(&bubbles, &edges);
+        for (i, p) in positions.iter().enumerate() {
+            let nearest = (0..positions.len())
+                .filter(|&j| j != i)
+                .min_by(|&a, &b| positions[a].sub(p).len().total_cmp(&positions[b].sub(p).len()))
+                .unwrap();
+            let (dx, dy) = ((i % width) as i64 - (nearest % width) as i64, (i / width) as i64 - (nearest / width) as i64);
+            // the corners get squeezed a little, the nearest can be a diagonal neighbour there
+            assert!(dx.abs() + dy.abs() <= 2, "the nearest to {} is {}", i, nearest);
+        }
+    }
+
+    #[test]
+    fn the_columns_stay_in_order() {
+        let (width, height) = (10, 6);
+        let (bubbles, edges) = grid(width, height);
+        let positions = pivot_mds_positions(&bubbles, &edges);
+        // the long side of the grid is the main axis, the columns come out in order along it
+        let column_x: Vec<f32> = (0..width)
+            .map(|x| (0..height).map(|y| positions[y * width + x].x).sum::<f32>() / height as f32)
+            .collect();
+        let steps: Vec<f32> = column_x.windows(2).map(|w| w[1] - w[0]).collect();
+        assert!(steps.iter().all(|&s| s > 0.0) || steps.iter().all(|&s| s < 0.0), "{:?}", steps);
+    }
+}
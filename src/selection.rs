@@ -0,0 +1,95 @@
+use std::collections::HashSet;
+
+/// What's selected in the viewer, as indices into the bubbles and the edges, each one at most once,
+/// in the order they were selected in. A removal moves the bubbles and the edges round, so the selection is cleared with it.
+#[derive(Default)]
+pub struct Selection {
+    bubbles: Vec<usize>,
+    edges: Vec<usize>,
+    // the same indices again, to look them up without going through the lists
+    bubble_set: HashSet<usize>,
+    edge_set: HashSet<usize>,
+}
+
+impl Selection {
+    pub fn bubbles(&self) -> &[usize] {
+        &self.bubbles
+    }
+
+    pub fn edges(&self) -> &[usize] {
+        &self.edges
+    }
+
+    pub fn clear(&mut self) {
+        self.bubbles.clear();
+        self.edges.clear();
+        self.bubble_set.clear();
+        self.edge_set.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bubbles.is_empty() && self.edges.is_empty()
+    }
+
+    pub fn select_bubble(&mut self, i: usize) {
+        self.clear();
+        self.add_bubbles(Some(i));
+    }
+
+    pub fn select_edge(&mut self, i: usize) {
+        self.clear();
+        self.toggle_edge(i);
+    }
+
+    /// Adds the bubble, or takes it out if it's selected already.
+    pub fn toggle_bubble(&mut self, i: usize) {
+        toggle(&mut self.bubbles, &mut self.bubble_set, i);
+    }
+
+    pub fn toggle_edge(&mut self, i: usize) {
+        toggle(&mut self.edges, &mut self.edge_set, i);
+    }
+
+    /// Adds the bubbles that aren't selected yet.
+    pub fn add_bubbles(&mut self, indices: impl IntoIterator<Item = usize>) {
+        for i in indices {
+            if self.bubble_set.insert(i) {
+                self.bubbles.push(i);
+            }
+        }
+    }
+
+    pub fn contains_bubble(&self, i: usize) -> bool {
+        self.bubble_set.contains(&i)
+    }
+}
+
+fn toggle(indices: &mut Vec<usize>, set: &mut HashSet<usize>, i: usize) {
+    if set.insert(i) {
+        indices.push(i);
+    } else {
+        set.remove(&i);
+        indices.retain(|&j| j != i);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn toggling_keeps_the_order_and_every_index_once() {
+        let mut selection = Selection::default();
+        selection.select_bubble(4);
+        selection.add_bubbles(vec![2, 4, 7, 2]);
+        selection.toggle_bubble(2);
+        selection.toggle_bubble(9);
+        assert_eq!(selection.bubbles(), [4, 7, 9]);
+        assert!(selection.contains_bubble(7));
+        assert!(!selection.contains_bubble(2));
+
+        selection.select_edge(3);
+        assert!(selection.bubbles().is_empty());
+        assert_eq!(selection.edges(), [3]);
+    }
+}
@@ -0,0 +1,236 @@
+use crate::adjacency::Adjacency;
+use crate::bubble::Bubble;
+use crate::edge::Edge;
+use crate::math::Vector2;
+use crate::stress::EDGE_LENGTH;
+use crate::tree::move_into_place;
+
+// power iteration steps for each eigenvector at most, and how close to 1 two steps in a row have to be to stop
+const MAX_ITERATIONS: usize = 1000;
+const TOLERANCE: f64 = 1e-9;
+// the room between two components side by side
+const COMPONENT_SPACING: f32 = EDGE_LENGTH;
+// how far `spread_apart` moves a bubble at most
+const SPREAD: f32 = 0.1 * EDGE_LENGTH;
+
+/// Spectral layout: the x and y of every bubble are its entries in the two eigenvectors of the graph Laplacian
+/// with the smallest eigenvalues past the trivial one, degree normalized as Koren has it.
+/// It's the same for the same graph every time. The pinned bubbles stay put.
+pub fn spectral(bubbles: &mut [Bubble], edges: &[Edge]) {
+    if bubbles.is_empty() {
+        return;
+    }
+    let positions = spectral_positions(bubbles, edges);
+    move_into_place(bubbles, &positions);
+}
+
+/// The spectral layout around the origin, scaled so the edges are `EDGE_LENGTH` long on average,
+/// with every component laid out on its own and the components in a row, the biggest first.
+/// The bubbles with the same neighbours would be in the same place, they're spread apart.
+pub fn spectral_positions(bubbles: &[Bubble], edges: &[Edge]) -> Vec<Vector2> {
+    let n = bubbles.len();
+    let adjacency = Adjacency::new(n, edges.iter().map(|e| (e.from, e.to, e.weight)));
+    let mut components = components(&adjacency);
+    components.sort_by_key(|component| std::cmp::Reverse(component.len()));
+
+    let mut positions = vec![Vector2::new(); n];
+    let mut left = 0.0;
+    for component in components.iter() {
+        let local = component_positions(&adjacency, component);
+        let mut min = Vector2 { x: f32::MAX, y: f32::MAX };
+        let mut max = Vector2 { x: f32::MIN, y: f32::MIN };
+        for (&v, p) in component.iter().zip(local.iter()) {
+            let r = bubbles[v].radius();
+            min = Vector2 { x: min.x.min(p.x - r), y: min.y.min(p.y - r) };
+            max = Vector2 { x: max.x.max(p.x + r), y: max.y.max(p.y + r) };
+        }
+        let offset = Vector2 {
+            x: left - min.x,
+            y: -0.5 * (min.y + max.y),
+        };
+        for (&v, p) in component.iter().zip(local.iter()) {
+            positions[v] = p.add(&offset);
+        }
+        left += max.x - min.x + COMPONENT_SPACING;
+    }
+
+    let center = positions
+        .iter()
+        .fold(Vector2::new(), |sum, p| sum.add(p))
+        .mul_s(1.0 / n as f32);
+    let mut positions: Vec<Vector2> = positions.iter().map(|p| p.sub(&center)).collect();
+    spread_apart(&mut positions);
+    positions
+}
+
+// the bubbles of every connected component
+fn components(adjacency: &Adjacency) -> Vec<Vec<usize>> {
+    let n = adjacency.bubble_count();
+    let mut reached = vec![false; n];
+    let mut components = vec![];
+    for start in 0..n {
+        if reached[start] {
+            continue;
+        }
+        reached[start] = true;
+        let mut component = vec![start];
+        let mut next = 0;
+        while next < component.len() {
+            let v = component[next];
+            next += 1;
+            for (w, _, _) in adjacency.neighbours_of(v) {
+                if !reached[w] {
+                    reached[w] = true;
+                    component.push(w);
+                }
+            }
+        }
+        components.push(component);
+    }
+    components
+}
+
+// the layout of one connected component, in the order of `component`
+fn component_positions(adjacency: &Adjacency, component: &[usize]) -> Vec<Vector2> {
+    let m = component.len();
+    match m {
+        1 => return vec![Vector2::new()],
+        2 => {
+            return vec![
+                Vector2 { x: -0.5 * EDGE_LENGTH, y: 0.0 },
+                Vector2 { x: 0.5 * EDGE_LENGTH, y: 0.0 },
+            ]
+        }
+        _ => {}
+    }
+    let mut local = vec![usize::MAX; adjacency.bubble_count()];
+    for (i, &v) in component.iter().enumerate() {
+        local[v] = i;
+    }
+    let neighbours: Vec<Vec<(usize, f64)>> = component
+        .iter()
+        .map(|&v| {
+            adjacency
+                .neighbours_of(v)
+                .filter(|&(w, _, _)| w != v)
+                .map(|(w, weight, _)| (local[w], weight as f64))
+                .collect()
+        })
+        .collect();
+    // a connected component has no bubble without a neighbour, but the weights can still add up to nothing
+    let degrees: Vec<f64> = neighbours
+        .iter()
+        .map(|ns| ns.iter().map(|&(_, weight)| weight).sum::<f64>())
+        .map(|degree| if degree > 0.0 { degree } else { 1.0 })
+        .collect();
+
+    // power iteration on (I + D⁻¹A) / 2, which has the eigenvectors of the degree normalized Laplacian
+    // in the opposite order, keeping every vector D-orthogonal to the ones found before it
+    let mut found = vec![vec![1.0 / (m as f64).sqrt(); m]];
+    for axis in 0..2 {
+        let mut u = normalized(hashed_values(m, axis));
+        for _ in 0..MAX_ITERATIONS {
+            for previous in found.iter() {
+                let along = d_dot(&u, previous, &degrees) / d_dot(previous, previous, &degrees);
+                for (x, p) in u.iter_mut().zip(previous) {
+                    *x -= along * p;
+                }
+            }
+            let next: Vec<f64> = (0..m)
+                .map(|i| {
+                    let average: f64 = neighbours[i].iter().map(|&(j, weight)| weight * u[j]).sum::<f64>() / degrees[i];
+                    0.5 * (u[i] + average)
+                })
+                .collect();
+            let next = normalized(next);
+            let settled = next.iter().zip(u.iter()).map(|(a, b)| a * b).sum::<f64>() > 1.0 - TOLERANCE;
+            u = next;
+            if settled {
+                break;
+            }
+        }
+        found.push(u);
+    }
+
+    let mut positions: Vec<Vector2> = (0..m)
+        .map(|i| Vector2 {
+            x: found[1][i] as f32,
+            y: found[2][i] as f32,
+        })
+        .collect();
+    let (length_sum, edge_count) = neighbours
+        .iter()
+        .enumerate()
+        .flat_map(|(i, ns)| ns.iter().map(move |&(j, _)| (i, j)))
+        .fold((0.0, 0), |(sum, count), (i, j)| (sum + positions[i].sub(&positions[j]).len(), count + 1));
+    let mean_length = length_sum / edge_count as f32;
+    if mean_length > 0.0 {
+        for p in positions.iter_mut() {
+            *p = p.mul_s(EDGE_LENGTH / mean_length);
+        }
+    }
+    positions
+}
+
+/// Moves every bubble a little, by the same amount every time, so the ones a starting layout put in the same place
+/// don't stay there: the force simulation has no direction to push them apart in.
+pub fn spread_apart(positions: &mut [Vector2]) {
+    let (spread_x, spread_y) = (hashed_values(positions.len(), 2), hashed_values(positions.len(), 3));
+    for (i, p) in positions.iter_mut().enumerate() {
+        *p = p.add(&Vector2 {
+            x: 2.0 * SPREAD * spread_x[i] as f32,
+            y: 2.0 * SPREAD * spread_y[i] as f32,
+        });
+    }
+}
+
+/// Numbers in [-0.5, 0.5] that are the same every time and not along any eigenvector in particular,
+/// a different run of them for every `salt`.
+pub fn hashed_values(m: usize, salt: usize) -> Vec<f64> {
+    (0..m)
+        .map(|i| {
+            let hash = ((4 * i + salt + 1) as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15) >> 40;
+            hash as f64 / (1u64 << 24) as f64 - 0.5
+        })
+        .collect()
+}
+
+fn d_dot(a: &[f64], b: &[f64], degrees: &[f64]) -> f64 {
+    a.iter().zip(b).zip(degrees).map(|((a, b), d)| a * b * d).sum()
+}
+
+pub fn normalized(u: Vec<f64>) -> Vec<f64> {
+    let length = u.iter().map(|x| x * x).sum::<f64>().sqrt();
+    if length > 0.0 {
+        u.into_iter().map(|x| x / length).collect()
+    } else {
+        u
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_graph::graph;
+
+    #[test]
+    fn a_path_comes_out_in_order() {
+        let pairs: Vec<(usize, usize)> = (0..9).map(|i| (i, i + 1)).collect();
+        let (bubbles, edges) = graph(10, &pairs);
+        let positions = spectral_positions(&bubbles, &edges);
+        let steps: Vec<f32> = positions.windows(2).map(|w| w[1].x - w[0].x).collect();
+        // the first eigenvector past the trivial one runs along the path, one way or the other
+        assert!(steps.iter().all(|&s| s > 0.0) || steps.iter().all(|&s| s < 0.0), "{:?}", steps);
+    }
+
+    #[test]
+    fn the_components_are_side_by_side() {
+        let (bubbles, edges) = graph(6, &[(0, 1), (1, 2), (2, 0), (3, 4), (4, 5), (5, 3)]);
+        let positions = spectral_positions(&bubbles, &edges);
+        let right_of = |a: &[usize], b: &[usize]| a.iter().all(|&i| b.iter().all(|&j| positions[i].x > positions[j].x));
+        assert!(right_of(&[3, 4, 5], &[0, 1, 2]) || right_of(&[0, 1, 2], &[3, 4, 5]));
+        // the same graph gives the same layout
+        let again = spectral_positions(&bubbles, &edges);
+        assert!(positions.iter().zip(again.iter()).all(|(a, b)| a.x == b.x && a.y == b.y));
+    }
+}
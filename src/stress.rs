@@ -0,0 +1,211 @@
+use crate::adjacency::Adjacency;
+use crate::bubble::Bubble;
+use crate::edge::Edge;
+use crate::math::Vector2;
+
+// how long an edge is meant to be, in simulation units, the graph distances are counted in it
+pub const EDGE_LENGTH: f32 = 60.0;
+// past this many bubbles, the distances to a few pivots stand in for the distances to all the others
+const FULL_STRESS_LIMIT: usize = 1000;
+const PIVOT_COUNT: usize = 100;
+const MAX_ITERATIONS: usize = 300;
+// it stops once the bubbles move less than this on average in an iteration, as a share of `EDGE_LENGTH`
+const TOLERANCE: f32 = 1e-3;
+
+// one term of the stress of a bubble: bubble `j` should be `distance` away, and it counts `weight` times
+struct Term {
+    j: usize,
+    distance: f32,
+    weight: f32,
+}
+
+/// Stress majorization (SMACOF): moves the bubbles until their distances match the hop distances in the graph.
+/// It starts from `Bubble::position`, so it refines the layout it's given, and leaves the pinned bubbles where they are.
+/// Up to `FULL_STRESS_LIMIT` bubbles every pair is taken into account, past it only the graph neighbours
+/// and a set of pivots standing in for the bubbles around them (the sparse stress model).
+pub fn stress_majorization(bubbles: &mut [Bubble], edges: &[Edge]) {
+    let n = bubbles.len();
+    if n < 2 {
+        return;
+    }
+    let adjacency = Adjacency::new(n, edges.iter().map(|e| (e.from, e.to, e.weight)));
+    let terms = if n <= FULL_STRESS_LIMIT {
+        full_terms(&adjacency)
+    } else {
+        pivot_terms(&adjacency, PIVOT_COUNT)
+    };
+
+    let mut positions: Vec<Vector2> = bubbles.iter().map(|b| b.position).collect();
+    for _ in 0..MAX_ITERATIONS {
+        let mut movement = 0.0;
+        for i in 0..n {
+            if bubbles[i].pinned {
+                continue;
+            }
+            // the weighted average of where each term would put the bubble, the localized majorization update
+            let mut sum = Vector2::new();
+            let mut weight_sum = 0.0;
+            for term in terms[i].iter() {
+                let d = positions[i].sub(&positions[term.j]);
+                let length = d.len();
+                if length > 0.0 {
+                    let target = positions[term.j].add(&d.mul_s(term.distance / length));
+                    sum = sum.add(&target.mul_s(term.weight));
+                    weight_sum += term.weight;
+                }
+            }
+            if weight_sum > 0.0 {
+                let next = sum.mul_s(1.0 / weight_sum);
+                movement += next.sub(&positions[i]).len();
+                positions[i] = next;
+            }
+        }
+        if movement / (n as f32) < TOLERANCE * EDGE_LENGTH {
+            break;
+        }
+    }
+
+    for (bubble, position) in bubbles.iter_mut().zip(positions) {
+        bubble.position = position;
+        bubble.v = Vector2::new();
+    }
+}
+
+/// The hop distances from `sources` to every bubble in `EDGE_LENGTH` units, the bubbles out of reach
+/// are put one hop further than the furthest one, so the components don't drift apart.
+pub fn graph_distances(adjacency: &Adjacency, sources: &[usize]) -> Vec<Vec<f32>> {
+    let hops: Vec<Vec<Option<u32>>> = sources
+        .iter()
+        .map(|&source| adjacency.hop_distances(&[source], u32::MAX))
+        .collect();
+    let diameter = hops.iter().flatten().flatten().copied().max().unwrap_or(0);
+    hops.iter()
+        .map(|row| {
+            row.iter()
+                .map(|hop| hop.unwrap_or(diameter + 1) as f32 * EDGE_LENGTH)
+                .collect()
+        })
+        .collect()
+}
+
+// every other bubble, weighted by 1 / d² so the close ones matter most
+fn full_terms(adjacency: &Adjacency) -> Vec<Vec<Term>> {
+    let n = adjacency.bubble_count();
+    let sources: Vec<usize> = (0..n).collect();
+    let distances = graph_distances(adjacency, &sources);
+    distances
+        .iter()
+        .enumerate()
+        .map(|(i, row)| {
+            row.iter()
+                .enumerate()
+                .filter(|&(j, _)| j != i)
+                .map(|(j, &distance)| Term {
+                    j,
+                    distance,
+                    weight: 1.0 / (distance * distance),
+                })
+                .collect()
+        })
+        .collect()
+}
+
+// the graph neighbours, and the pivots with the weight of the bubbles closest to them
+fn pivot_terms(adjacency: &Adjacency, pivot_count: usize) -> Vec<Vec<Term>> {
+    let n = adjacency.bubble_count();
+    let (pivots, distances) = max_min_pivots(adjacency, pivot_count.min(n));
+
+    // every bubble belongs to the region of its closest pivot
+    let mut region_sizes = vec![0.0; pivots.len()];
+    let closest_pivots = (0..n).map(|i| {
+        (0..pivots.len())
+            .min_by(|&a, &b| distances[a][i].total_cmp(&distances[b][i]))
+            .unwrap()
+    });
+    for closest in closest_pivots {
+        region_sizes[closest] += 1.0;
+    }
+
+    (0..n)
+        .map(|i| {
+            let mut terms: Vec<Term> = adjacency
+                .neighbours_of(i)
+                .filter(|&(j, _, _)| j != i)
+                .map(|(j, _, _)| Term {
+                    j,
+                    distance: EDGE_LENGTH,
+                    weight: 1.0 / (EDGE_LENGTH * EDGE_LENGTH),
+                })
+                .collect();
+            for (p, &pivot) in pivots.iter().enumerate() {
+                let distance = distances[p][i];
+                if pivot != i && distance > EDGE_LENGTH {
+                    terms.push(Term {
+                        j: pivot,
+                        distance,
+                        weight: region_sizes[p] / (distance * distance),
+                    });
+                }
+            }
+            terms
+        })
+        .collect()
+}
+
+/// Picks pivots spread over the graph, each one the bubble furthest from the ones picked before,
+/// and returns them with their distances to every bubble, see `graph_distances`.
+pub fn max_min_pivots(adjacency: &Adjacency, pivot_count: usize) -> (Vec<usize>, Vec<Vec<f32>>) {
+    let n = adjacency.bubble_count();
+    let mut pivots = vec![];
+    let mut distances: Vec<Vec<f32>> = vec![];
+    // how far every bubble is from its closest pivot so far
+    let mut closest = vec![f32::INFINITY; n];
+    let mut next = 0;
+    while pivots.len() < pivot_count {
+        pivots.push(next);
+        let row = graph_distances(adjacency, &[next]).remove(0);
+        for (c, &d) in closest.iter_mut().zip(row.iter()) {
+            *c = c.min(d);
+        }
+        distances.push(row);
+        next = (0..n).max_by(|&a, &b| closest[a].total_cmp(&closest[b])).unwrap();
+        if closest[next] == 0.0 {
+            break;
+        }
+    }
+    (pivots, distances)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout_quality::LayoutQuality;
+    use crate::test_graph::cycle;
+
+    #[test]
+    fn stress_majorization_opens_a_ring_up() {
+        let (mut bubbles, edges) = cycle(8);
+        let before = LayoutQuality::evaluate(&bubbles, &edges).stress;
+        stress_majorization(&mut bubbles, &edges);
+        let after = LayoutQuality::evaluate(&bubbles, &edges).stress;
+        assert!(after < 0.5 * before, "the stress went from {} to {}", before, after);
+        assert!(after < 0.05);
+    }
+
+    #[test]
+    fn the_pinned_bubbles_stay_put() {
+        let (mut bubbles, edges) = cycle(8);
+        bubbles[3].pinned = true;
+        let pinned_at = bubbles[3].position;
+        stress_majorization(&mut bubbles, &edges);
+        assert_eq!((bubbles[3].position.x, bubbles[3].position.y), (pinned_at.x, pinned_at.y));
+    }
+
+    #[test]
+    fn graph_distances_count_hops_in_edge_lengths() {
+        let adjacency = Adjacency::new(4, [(0, 1, 1.0), (1, 2, 1.0)].iter().copied());
+        let distances = graph_distances(&adjacency, &[0]);
+        assert_eq!(distances[0][..3], [0.0, EDGE_LENGTH, 2.0 * EDGE_LENGTH]);
+        assert_eq!(distances[0][3], 3.0 * EDGE_LENGTH);
+    }
+}
@@ -0,0 +1,555 @@
+use std::collections::HashSet;
+
+use crate::bubble::Bubble;
+use crate::edge::Edge;
+use crate::math::Vector2;
+use crate::stress::EDGE_LENGTH;
+
+// the room left between two bubbles side by side in a layer, and between two layers, past their radii
+const NODE_SPACING: f32 = 0.5 * EDGE_LENGTH;
+const LAYER_SPACING: f32 = EDGE_LENGTH;
+// the crossing minimisation sweeps down and up this many times in all, and keeps the best order it saw
+const ORDERING_SWEEPS: usize = 24;
+
+// the graph with every edge pointing one layer down, the long ones broken up by dummy nodes,
+// the bubbles are the first nodes and the dummies come after them
+struct LayeredGraph {
+    bubble_count: usize,
+    layers: Vec<Vec<usize>>,
+    // the neighbours of every node in the layer above it and in the layer below it
+    upper: Vec<Vec<usize>>,
+    lower: Vec<Vec<usize>>,
+    widths: Vec<f32>,
+    // the dummies of every edge, from its upper end down, and whether the edge was turned around
+    chains: Vec<(Vec<usize>, bool)>,
+}
+
+/// Sugiyama's layered layout for directed graphs: the cycles are broken by turning edges around,
+/// the bubbles are layered by the longest path from the sources, the crossings are cut down with median sweeps,
+/// and the x coordinates come from Brandes-Köpf. The edges from `from` to `to` point down.
+/// The edges spanning several layers get their bends in `Edge::route`, the pinned bubbles stay put.
+pub fn sugiyama(bubbles: &mut [Bubble], edges: &mut [Edge]) {
+    let n = bubbles.len();
+    if n == 0 {
+        return;
+    }
+    let reversed = reversed_edges(n, edges);
+    let layer_of = longest_path_layers(n, edges, &reversed);
+    let mut graph = LayeredGraph::new(bubbles, edges, &reversed, &layer_of);
+    minimise_crossings(&mut graph);
+    let x = brandes_koepf(&graph);
+
+    // every layer is as tall as its biggest bubble
+    let mut layer_ys = vec![];
+    let mut y = 0.0;
+    let mut previous_half_height = 0.0;
+    for layer in graph.layers.iter() {
+        let half_height = layer.iter().map(|&v| 0.5 * graph.widths[v]).fold(0.0, f32::max);
+        if !layer_ys.is_empty() {
+            y += previous_half_height + half_height + LAYER_SPACING;
+        }
+        layer_ys.push(y);
+        previous_half_height = half_height;
+    }
+    let mut node_layers = vec![0; graph.widths.len()];
+    for (i, layer) in graph.layers.iter().enumerate() {
+        for &v in layer {
+            node_layers[v] = i;
+        }
+    }
+    let position_of = |v: usize| Vector2 {
+        x: x[v],
+        y: layer_ys[node_layers[v]],
+    };
+
+    // the layout goes where the bubbles were, so it stays in view and by the pinned ones
+    let center = bubbles
+        .iter()
+        .fold(Vector2::new(), |sum, b| sum.add(&b.position))
+        .mul_s(1.0 / n as f32);
+    let layout_center = (0..n)
+        .fold(Vector2::new(), |sum, v| sum.add(&position_of(v)))
+        .mul_s(1.0 / n as f32);
+    let offset = center.sub(&layout_center);
+
+    for (v, bubble) in bubbles.iter_mut().enumerate() {
+        bubble.v = Vector2::new();
+        if !bubble.pinned {
+            bubble.position = position_of(v).add(&offset);
+        }
+    }
+    for (edge, (chain, reversed)) in edges.iter_mut().zip(graph.chains.iter()) {
+        edge.route = chain.iter().map(|&d| position_of(d).add(&offset)).collect();
+        if *reversed {
+            edge.route.reverse();
+        }
+    }
+}
+
+// the edges a depth-first search finds going back up its own path, turning them around leaves no cycle
+fn reversed_edges(n: usize, edges: &[Edge]) -> Vec<bool> {
+    let mut outgoing: Vec<Vec<usize>> = vec![vec![]; n];
+    for (i, edge) in edges.iter().enumerate() {
+        if edge.from != edge.to {
+            outgoing[edge.from].push(i);
+        }
+    }
+    let mut reversed = vec![false; edges.len()];
+    // 0 not seen yet, 1 on the search path, 2 done
+    let mut state = vec![0u8; n];
+    for start in 0..n {
+        if state[start] != 0 {
+            continue;
+        }
+        state[start] = 1;
+        let mut stack = vec![(start, 0)];
+        while let Some((v, next)) = stack.last_mut() {
+            let v = *v;
+            if let Some(&i) = outgoing[v].get(*next) {
+                *next += 1;
+                let w = edges[i].to;
+                match state[w] {
+                    0 => {
+                        state[w] = 1;
+                        stack.push((w, 0));
+                    }
+                    1 => reversed[i] = true,
+                    _ => {}
+                }
+            } else {
+                state[v] = 2;
+                stack.pop();
+            }
+        }
+    }
+    reversed
+}
+
+// every edge as (upper end, lower end), once the cycles are broken, the self loops are left out
+fn directed<'a>(edges: &'a [Edge], reversed: &'a [bool]) -> impl Iterator<Item = (usize, usize, usize)> + 'a {
+    edges
+        .iter()
+        .zip(reversed.iter())
+        .enumerate()
+        .filter(|(_, (edge, _))| edge.from != edge.to)
+        .map(|(i, (edge, &reversed))| if reversed { (edge.to, edge.from, i) } else { (edge.from, edge.to, i) })
+}
+
+// a bubble goes one layer below the deepest of the bubbles pointing to it,
+// then the sources are moved down next to their highest successor so their edges are short
+fn longest_path_layers(n: usize, edges: &[Edge], reversed: &[bool]) -> Vec<usize> {
+    let mut in_degrees = vec![0; n];
+    let mut successors: Vec<Vec<usize>> = vec![vec![]; n];
+    for (from, to, _) in directed(edges, reversed) {
+        in_degrees[to] += 1;
+        successors[from].push(to);
+    }
+    let mut layers = vec![0; n];
+    let mut remaining = in_degrees.clone();
+    let mut ready: Vec<usize> = (0..n).filter(|&v| in_degrees[v] == 0).collect();
+    while let Some(v) = ready.pop() {
+        for &w in successors[v].iter() {
+            layers[w] = layers[w].max(layers[v] + 1);
+            remaining[w] -= 1;
+            if remaining[w] == 0 {
+                ready.push(w);
+            }
+        }
+    }
+    for v in (0..n).filter(|&v| in_degrees[v] == 0) {
+        if let Some(highest) = successors[v].iter().map(|&w| layers[w]).min() {
+            layers[v] = highest - 1;
+        }
+    }
+    layers
+}
+
+impl LayeredGraph {
+    fn new(bubbles: &[Bubble], edges: &[Edge], reversed: &[bool], layer_of: &[usize]) -> Self {
+        let layer_count = layer_of.iter().max().map_or(0, |l| l + 1);
+        let mut graph = LayeredGraph {
+            bubble_count: bubbles.len(),
+            layers: vec![vec![]; layer_count],
+            upper: vec![vec![]; bubbles.len()],
+            lower: vec![vec![]; bubbles.len()],
+            widths: bubbles.iter().map(|b| 2.0 * b.radius()).collect(),
+            chains: vec![(vec![], false); edges.len()],
+        };
+        for (v, &layer) in layer_of.iter().enumerate() {
+            graph.layers[layer].push(v);
+        }
+        for (from, to, i) in directed(edges, reversed) {
+            let mut above = from;
+            for layer in (layer_of[from] + 1)..layer_of[to] {
+                let dummy = graph.widths.len();
+                graph.widths.push(0.0);
+                graph.upper.push(vec![]);
+                graph.lower.push(vec![]);
+                graph.layers[layer].push(dummy);
+                graph.chains[i].0.push(dummy);
+                graph.link(above, dummy);
+                above = dummy;
+            }
+            graph.link(above, to);
+            graph.chains[i].1 = reversed[i];
+        }
+        graph
+    }
+
+    fn link(&mut self, above: usize, below: usize) {
+        self.lower[above].push(below);
+        self.upper[below].push(above);
+    }
+
+    fn is_dummy(&self, v: usize) -> bool {
+        v >= self.bubble_count
+    }
+
+    // where every node is in its layer
+    fn positions(layers: &[Vec<usize>], node_count: usize) -> Vec<usize> {
+        let mut positions = vec![0; node_count];
+        for layer in layers {
+            for (p, &v) in layer.iter().enumerate() {
+                positions[v] = p;
+            }
+        }
+        positions
+    }
+}
+
+fn minimise_crossings(graph: &mut LayeredGraph) {
+    let node_count = graph.widths.len();
+    let mut best = graph.layers.clone();
+    let mut best_crossings = count_crossings(graph);
+    for sweep in 0..ORDERING_SWEEPS {
+        let mut positions = LayeredGraph::positions(&graph.layers, node_count);
+        let down = sweep % 2 == 0;
+        let order: Vec<usize> = if down {
+            (1..graph.layers.len()).collect()
+        } else {
+            (0..graph.layers.len().saturating_sub(1)).rev().collect()
+        };
+        for i in order {
+            let neighbours = if down { &graph.upper } else { &graph.lower };
+            sort_by_median(&mut graph.layers[i], neighbours, &mut positions);
+        }
+        let crossings = count_crossings(graph);
+        if crossings < best_crossings {
+            best_crossings = crossings;
+            best = graph.layers.clone();
+        }
+        if best_crossings == 0 {
+            break;
+        }
+    }
+    graph.layers = best;
+}
+
+// every node goes to the median position of its neighbours in the layer next to it, the barycentre breaks the ties,
+// and the nodes without any keep their place
+fn sort_by_median(layer: &mut [usize], neighbours: &[Vec<usize>], positions: &mut [usize]) {
+    let keys: Vec<(f32, f32)> = layer
+        .iter()
+        .map(|&v| {
+            let mut ps: Vec<f32> = neighbours[v].iter().map(|&u| positions[u] as f32).collect();
+            if ps.is_empty() {
+                let p = positions[v] as f32;
+                return (p, p);
+            }
+            ps.sort_by(|a, b| a.total_cmp(b));
+            let barycentre = ps.iter().sum::<f32>() / ps.len() as f32;
+            (weighted_median(&ps), barycentre)
+        })
+        .collect();
+    let mut order: Vec<usize> = (0..layer.len()).collect();
+    order.sort_by(|&a, &b| keys[a].0.total_cmp(&keys[b].0).then(keys[a].1.total_cmp(&keys[b].1)));
+    let sorted: Vec<usize> = order.iter().map(|&i| layer[i]).collect();
+    for (p, (slot, v)) in layer.iter_mut().zip(sorted).enumerate() {
+        *slot = v;
+        positions[v] = p;
+    }
+}
+
+// the median of sorted positions, with an even count it leans towards the side where they're packed closer (Gansner et al.)
+fn weighted_median(ps: &[f32]) -> f32 {
+    let m = ps.len() / 2;
+    if ps.len() % 2 == 1 {
+        ps[m]
+    } else if ps.len() == 2 {
+        (ps[0] + ps[1]) / 2.0
+    } else {
+        let left = ps[m - 1] - ps[0];
+        let right = ps[ps.len() - 1] - ps[m];
+        if left + right == 0.0 {
+            (ps[m - 1] + ps[m]) / 2.0
+        } else {
+            (ps[m - 1] * right + ps[m] * left) / (left + right)
+        }
+    }
+}
+
+// the crossings between every two layers next to each other, counted as inversions with a Fenwick tree
+fn count_crossings(graph: &LayeredGraph) -> usize {
+    let positions = LayeredGraph::positions(&graph.layers, graph.widths.len());
+    let mut crossings = 0;
+    for pair in graph.layers.windows(2) {
+        let mut ends: Vec<(usize, usize)> = pair[0]
+            .iter()
+            .flat_map(|&u| graph.lower[u].iter().map(move |&w| (u, w)))
+            .map(|(u, w)| (positions[u], positions[w]))
+            .collect();
+        ends.sort_unstable();
+        let size = pair[1].len();
+        let mut tree = vec![0usize; size + 1];
+        for (inserted, &(_, lower)) in ends.iter().enumerate() {
+            // how many of the edges so far end at or left of this one
+            let mut at_or_left = 0;
+            let mut i = lower + 1;
+            while i > 0 {
+                at_or_left += tree[i];
+                i -= i & i.wrapping_neg();
+            }
+            crossings += inserted - at_or_left;
+            let mut i = lower + 1;
+            while i <= size {
+                tree[i] += 1;
+                i += i & i.wrapping_neg();
+            }
+        }
+    }
+    crossings
+}
+
+// the gap the centres of two nodes side by side need
+fn separation(graph: &LayeredGraph, a: usize, b: usize) -> f32 {
+    0.5 * (graph.widths[a] + graph.widths[b]) + NODE_SPACING
+}
+
+/// Brandes-Köpf: four layouts aligning every node with its median neighbour above or below, leaning left or right,
+/// each one packed as tight as it goes, then balanced into one.
+fn brandes_koepf(graph: &LayeredGraph) -> Vec<f32> {
+    let mut layouts = vec![];
+    for &down in &[true, false] {
+        for &left in &[true, false] {
+            let mut layers = graph.layers.clone();
+            if !down {
+                layers.reverse();
+            }
+            if !left {
+                layers.iter_mut().for_each(|layer| layer.reverse());
+            }
+            let above = if down { &graph.upper } else { &graph.lower };
+            let mut x = align_and_compact(graph, &layers, above);
+            if !left {
+                x.iter_mut().for_each(|x| *x = -*x);
+            }
+            layouts.push((x, left));
+        }
+    }
+
+    // the narrowest layout sets where the others line up, the left ones by their left side, the right ones by their right side
+    let extent = |x: &[f32]| {
+        let min = x.iter().copied().fold(f32::INFINITY, f32::min);
+        let max = x.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        (min, max)
+    };
+    let narrowest = layouts
+        .iter()
+        .map(|(x, _)| extent(x))
+        .min_by(|a, b| (a.1 - a.0).total_cmp(&(b.1 - b.0)))
+        .unwrap();
+    for (x, left) in layouts.iter_mut() {
+        let (min, max) = extent(x);
+        let shift = if *left { narrowest.0 - min } else { narrowest.1 - max };
+        x.iter_mut().for_each(|x| *x += shift);
+    }
+
+    let mut x: Vec<f32> = (0..graph.widths.len())
+        .map(|v| {
+            let mut candidates: Vec<f32> = layouts.iter().map(|(x, _)| x[v]).collect();
+            candidates.sort_by(|a, b| a.total_cmp(b));
+            0.5 * (candidates[1] + candidates[2])
+        })
+        .collect();
+
+    // the balancing keeps the nodes apart, but not always the class shifts of the original compaction, this makes sure
+    for layer in graph.layers.iter() {
+        for pair in layer.windows(2) {
+            x[pair[1]] = x[pair[1]].max(x[pair[0]] + separation(graph, pair[0], pair[1]));
+        }
+    }
+    x
+}
+
+// one of the four layouts, with the layers flipped so it always aligns with the layer above, leaning left
+fn align_and_compact(graph: &LayeredGraph, layers: &[Vec<usize>], above: &[Vec<usize>]) -> Vec<f32> {
+    let node_count = graph.widths.len();
+    let positions = LayeredGraph::positions(layers, node_count);
+    let conflicts = type_one_conflicts(graph, layers, above, &positions);
+
+    // the nodes aligned with each other make blocks, `align` runs round every block and `root` is its top node
+    let mut root: Vec<usize> = (0..node_count).collect();
+    let mut align: Vec<usize> = (0..node_count).collect();
+    for layer in layers.iter().skip(1) {
+        let mut r: Option<usize> = None;
+        for &v in layer {
+            let mut ups = above[v].clone();
+            if ups.is_empty() {
+                continue;
+            }
+            ups.sort_by_key(|&u| positions[u]);
+            let d = ups.len();
+            let mut medians = vec![(d - 1) / 2];
+            if d.is_multiple_of(2) {
+                medians.push(d / 2);
+            }
+            for m in medians {
+                let u = ups[m];
+                if align[v] == v && !conflicts.contains(&(u, v)) && r.is_none_or(|r| r < positions[u]) {
+                    align[u] = v;
+                    root[v] = root[u];
+                    align[v] = root[v];
+                    r = Some(positions[u]);
+                }
+            }
+        }
+    }
+
+    let mut predecessors = vec![None; node_count];
+    for layer in layers {
+        for pair in layer.windows(2) {
+            predecessors[pair[1]] = Some(pair[0]);
+        }
+    }
+    let mut compaction = Compaction {
+        graph,
+        root: &root,
+        align: &align,
+        predecessors,
+        sink: (0..node_count).collect(),
+        shift: vec![f32::INFINITY; node_count],
+        x: vec![None; node_count],
+    };
+    for v in (0..node_count).filter(|&v| root[v] == v) {
+        compaction.place_block(v);
+    }
+    (0..node_count)
+        .map(|v| {
+            let r = root[v];
+            let shift = compaction.shift[compaction.sink[r]];
+            compaction.x[r].unwrap() + if shift < f32::INFINITY { shift } else { 0.0 }
+        })
+        .collect()
+}
+
+// the segments between two dummies are kept straight, so the edges they cross are marked not to be aligned
+fn type_one_conflicts(
+    graph: &LayeredGraph,
+    layers: &[Vec<usize>],
+    above: &[Vec<usize>],
+    positions: &[usize],
+) -> HashSet<(usize, usize)> {
+    let mut conflicts = HashSet::new();
+    for pair in layers.windows(2) {
+        let (upper, lower) = (&pair[0], &pair[1]);
+        let inner_segment = |v: usize| {
+            if graph.is_dummy(v) {
+                above[v].iter().copied().find(|&u| graph.is_dummy(u))
+            } else {
+                None
+            }
+        };
+        let mut k0 = 0;
+        let mut l = 0;
+        for (l1, &v) in lower.iter().enumerate() {
+            let inner = inner_segment(v);
+            if l1 + 1 == lower.len() || inner.is_some() {
+                let k1 = inner.map_or(upper.len().saturating_sub(1), |u| positions[u]);
+                while l <= l1 {
+                    let w = lower[l];
+                    for &u in above[w].iter() {
+                        if positions[u] < k0 || positions[u] > k1 {
+                            conflicts.insert((u, w));
+                        }
+                    }
+                    l += 1;
+                }
+                k0 = k1;
+            }
+        }
+    }
+    conflicts
+}
+
+// the horizontal compaction, every block is placed as far left as the blocks left of it allow
+struct Compaction<'a> {
+    graph: &'a LayeredGraph,
+    root: &'a [usize],
+    align: &'a [usize],
+    // the node left of every node in its layer
+    predecessors: Vec<Option<usize>>,
+    sink: Vec<usize>,
+    shift: Vec<f32>,
+    x: Vec<Option<f32>>,
+}
+
+impl Compaction<'_> {
+    fn place_block(&mut self, v: usize) {
+        if self.x[v].is_some() {
+            return;
+        }
+        self.x[v] = Some(0.0);
+        let mut w = v;
+        loop {
+            if let Some(p) = self.predecessors[w] {
+                let u = self.root[p];
+                self.place_block(u);
+                if self.sink[v] == v {
+                    self.sink[v] = self.sink[u];
+                }
+                let delta = separation(self.graph, p, w);
+                let (xv, xu) = (self.x[v].unwrap(), self.x[u].unwrap());
+                if self.sink[v] != self.sink[u] {
+                    let sink = self.sink[u];
+                    self.shift[sink] = self.shift[sink].min(xv - xu - delta);
+                } else {
+                    self.x[v] = Some(xv.max(xu + delta));
+                }
+            }
+            w = self.align[w];
+            if w == v {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout_quality::LayoutQuality;
+    use crate::test_graph::graph;
+
+    #[test]
+    fn a_tree_comes_out_without_crossings() {
+        // a binary tree from 0 down, numbered so the first order of the layers crosses its edges
+        let (mut bubbles, mut edges) = graph(7, &[(0, 4), (0, 1), (4, 2), (4, 6), (1, 5), (1, 3)]);
+        sugiyama(&mut bubbles, &mut edges);
+        assert_eq!(LayoutQuality::evaluate(&bubbles, &edges).edge_crossings, Some(0));
+        for edge in edges.iter() {
+            assert!(bubbles[edge.from].position.y < bubbles[edge.to].position.y);
+            assert!(edge.route.is_empty());
+        }
+    }
+
+    #[test]
+    fn the_long_edges_are_routed_and_the_cycles_broken() {
+        let (mut bubbles, mut edges) = graph(4, &[(0, 1), (1, 2), (2, 3), (0, 3), (3, 1)]);
+        sugiyama(&mut bubbles, &mut edges);
+        let mut ys: Vec<f32> = bubbles.iter().map(|b| b.position.y).collect();
+        ys.sort_by(|a, b| a.total_cmp(b));
+        ys.dedup();
+        assert!(ys.len() >= 3, "{:?}", ys);
+        assert!(edges.iter().any(|e| !e.route.is_empty()));
+    }
+}
@@ -0,0 +1,69 @@
+// small graphs for the tests, built without a device or a dataset
+
+use std::collections::BTreeMap;
+
+use crate::bubble::Bubble;
+use crate::edge::Edge;
+use crate::math::Vector2;
+use crate::mesh::Mesh;
+
+pub fn bubble(x: f32, y: f32) -> Bubble {
+    Bubble {
+        position: Vector2 { x, y },
+        size: 100.0,
+        v: Vector2::new(),
+        a: Vector2::new(),
+        meshes: [Mesh::default(), Mesh::default(), Mesh::default()],
+        label: String::new(),
+        pinned: false,
+        attributes: BTreeMap::new(),
+    }
+}
+
+pub fn edge(from: usize, to: usize, weight: f32) -> Edge {
+    Edge {
+        from,
+        to,
+        weight,
+        pull_force: 0.0,
+        mesh: Mesh::default(),
+        route: vec![],
+    }
+}
+
+// `n` bubbles labelled by their index, scattered so no two of them start in the same place up to 143 of them,
+// and not on a line either, the layouts that only move the bubbles towards each other couldn't leave it
+pub fn graph(n: usize, edges: &[(usize, usize)]) -> (Vec<Bubble>, Vec<Edge>) {
+    let bubbles = (0..n)
+        .map(|i| {
+            let mut b = bubble((i * 7 % 11) as f32 * 50.0, (i * 5 % 13) as f32 * 50.0);
+            b.label = i.to_string();
+            b
+        })
+        .collect();
+    let edges = edges.iter().map(|&(from, to)| edge(from, to, 1.0)).collect();
+    (bubbles, edges)
+}
+
+// a ring of `n` bubbles, scattered like `graph` has them
+pub fn cycle(n: usize) -> (Vec<Bubble>, Vec<Edge>) {
+    let edges: Vec<(usize, usize)> = (0..n).map(|i| (i, (i + 1) % n)).collect();
+    graph(n, &edges)
+}
+
+// a `width` by `height` grid, the bubbles row by row
+pub fn grid(width: usize, height: usize) -> (Vec<Bubble>, Vec<Edge>) {
+    let mut edges = vec![];
+    for y in 0..height {
+        for x in 0..width {
+            let i = y * width + x;
+            if x + 1 < width {
+                edges.push((i, i + 1));
+            }
+            if y + 1 < height {
+                edges.push((i, i + width));
+            }
+        }
+    }
+    graph(width * height, &edges)
+}
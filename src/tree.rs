@@ -0,0 +1,423 @@
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+
+use crate::adjacency::Adjacency;
+use crate::bubble::Bubble;
+use crate::edge::Edge;
+use crate::math::Vector2;
+use crate::metrics;
+use crate::stress::EDGE_LENGTH;
+
+// the room left between two bubbles side by side, and between two levels, past their radii
+const NODE_SPACING: f32 = 0.5 * EDGE_LENGTH;
+const LEVEL_SPACING: f32 = EDGE_LENGTH;
+
+/// A spanning tree of the graph, found breadth first so every bubble is as close to the root as it gets.
+/// When the graph falls apart, every other component hangs from its own root with the most edges,
+/// and they're all held together by a virtual root, the last node, which isn't a bubble.
+pub struct SpanningTree {
+    pub root: usize,
+    pub children: Vec<Vec<usize>>,
+    pub depths: Vec<usize>,
+    virtual_root: bool,
+}
+
+impl SpanningTree {
+    pub fn new(adjacency: &Adjacency, root: usize) -> Self {
+        let n = adjacency.bubble_count();
+        let degrees = metrics::degree(adjacency);
+        let mut children: Vec<Vec<usize>> = vec![vec![]; n];
+        let mut reached = vec![false; n];
+        let mut component_roots = vec![];
+        let mut by_degree: Vec<usize> = (0..n).collect();
+        by_degree.sort_by(|&a, &b| degrees[b].total_cmp(&degrees[a]));
+        for start in std::iter::once(root).chain(by_degree) {
+            if reached[start] {
+                continue;
+            }
+            component_roots.push(start);
+            reached[start] = true;
+            let mut queue = VecDeque::from(vec![start]);
+            while let Some(v) = queue.pop_front() {
+                for (w, _, _) in adjacency.neighbours_of(v) {
+                    if !reached[w] {
+                        reached[w] = true;
+                        children[v].push(w);
+                        queue.push_back(w);
+                    }
+                }
+            }
+        }
+
+        let virtual_root = component_roots.len() > 1;
+        let root = if virtual_root {
+            children.push(component_roots);
+            n
+        } else {
+            root
+        };
+        let mut depths = vec![0; children.len()];
+        let mut stack = vec![root];
+        while let Some(v) = stack.pop() {
+            for &w in children[v].iter() {
+                depths[w] = depths[v] + 1;
+                stack.push(w);
+            }
+        }
+        SpanningTree {
+            root,
+            children,
+            depths,
+            virtual_root,
+        }
+    }
+
+    // the nodes with every parent after its children, and the children from left to right
+    fn postorder(&self) -> Vec<usize> {
+        let mut order = vec![];
+        let mut stack = vec![self.root];
+        while let Some(v) = stack.pop() {
+            order.push(v);
+            stack.extend(self.children[v].iter());
+        }
+        order.reverse();
+        order
+    }
+}
+
+/// Reingold-Tilford tidy tree, with Walker's placement of the subtrees in between in the linear time of Buchheim et al.
+/// The root is at the top and every level below it, the pinned bubbles stay put.
+/// Without a root, the bubble with the most edges is picked.
+pub fn tidy_tree(bubbles: &mut [Bubble], edges: &[Edge], root: Option<usize>) {
+    let tree = match spanning_tree(bubbles, edges, root) {
+        Some(tree) => tree,
+        None => return,
+    };
+    let widths = node_widths(bubbles, &tree);
+    let x = walker(&tree, &widths);
+
+    let level_count = tree.depths.iter().max().map_or(0, |d| d + 1);
+    let mut half_heights = vec![0.0f32; level_count];
+    for (v, &depth) in tree.depths.iter().enumerate() {
+        half_heights[depth] = half_heights[depth].max(0.5 * widths[v]);
+    }
+    let mut level_ys = vec![0.0; level_count];
+    for d in 1..level_count {
+        level_ys[d] = level_ys[d - 1] + half_heights[d - 1] + half_heights[d] + LEVEL_SPACING;
+    }
+    // the virtual root's level is dropped, the component roots make the top row
+    let top = if tree.virtual_root { level_ys.get(1).copied().unwrap_or(0.0) } else { 0.0 };
+    let positions: Vec<Vector2> = (0..bubbles.len())
+        .map(|v| Vector2 {
+            x: x[v],
+            y: level_ys[tree.depths[v]] - top,
+        })
+        .collect();
+    move_into_place(bubbles, &positions);
+}
+
+/// The tidy tree bent round the root: the levels become rings and the order along them becomes the angle.
+/// The rings are far enough apart that the outermost one has room for all the bubbles.
+pub fn radial_tree(bubbles: &mut [Bubble], edges: &[Edge], root: Option<usize>) {
+    let tree = match spanning_tree(bubbles, edges, root) {
+        Some(tree) => tree,
+        None => return,
+    };
+    let widths = node_widths(bubbles, &tree);
+    let x = walker(&tree, &widths);
+
+    let min = x.iter().copied().fold(f32::INFINITY, f32::min);
+    let max = x.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let widest = widths.iter().copied().fold(0.0, f32::max);
+    // the two ends of the row meet round the back, so they're kept one gap apart too
+    let circumference = max - min + widest + NODE_SPACING;
+    let angles: Vec<f32> = x.iter().map(|x| 2.0 * PI * (x - min) / circumference).collect();
+
+    // the rings are spaced so that on every one of them, the chord between two neighbours is long enough to part them
+    let level_count = tree.depths.iter().max().map_or(0, |d| d + 1);
+    let mut rings: Vec<Vec<usize>> = vec![vec![]; level_count];
+    for (v, &depth) in tree.depths.iter().enumerate() {
+        rings[depth].push(v);
+    }
+    let mut ring_spacing = widest + LEVEL_SPACING;
+    for (depth, ring) in rings.iter_mut().enumerate().filter(|(_, ring)| ring.len() > 1) {
+        ring.sort_by(|&a, &b| angles[a].total_cmp(&angles[b]));
+        let last = ring[ring.len() - 1];
+        let pairs = ring.windows(2).map(|pair| (pair[0], pair[1], angles[pair[1]] - angles[pair[0]]));
+        for (a, b, angle) in pairs.chain(std::iter::once((last, ring[0], 2.0 * PI - angles[last] + angles[ring[0]]))) {
+            let chord = 2.0 * (0.5 * angle).sin();
+            if chord > 0.0 {
+                ring_spacing = ring_spacing.max(separation(&widths, a, b) / (chord * depth as f32));
+            }
+        }
+    }
+    let positions: Vec<Vector2> = (0..bubbles.len())
+        .map(|v| {
+            let angle = angles[v];
+            let radius = tree.depths[v] as f32 * ring_spacing;
+            Vector2 {
+                x: radius * angle.cos(),
+                y: radius * angle.sin(),
+            }
+        })
+        .collect();
+    move_into_place(bubbles, &positions);
+}
+
+fn spanning_tree(bubbles: &[Bubble], edges: &[Edge], root: Option<usize>) -> Option<SpanningTree> {
+    let n = bubbles.len();
+    if n == 0 {
+        return None;
+    }
+    let adjacency = Adjacency::new(n, edges.iter().map(|e| (e.from, e.to, e.weight)));
+    let root = root.filter(|&r| r < n).unwrap_or_else(|| {
+        let degrees = metrics::degree(&adjacency);
+        (0..n).max_by(|&a, &b| degrees[a].total_cmp(&degrees[b])).unwrap()
+    });
+    Some(SpanningTree::new(&adjacency, root))
+}
+
+// the virtual root takes no room
+fn node_widths(bubbles: &[Bubble], tree: &SpanningTree) -> Vec<f32> {
+    (0..tree.children.len())
+        .map(|v| bubbles.get(v).map_or(0.0, |b| 2.0 * b.radius()))
+        .collect()
+}
+
+// how far apart the middles of two bubbles side by side are kept
+fn separation(widths: &[f32], a: usize, b: usize) -> f32 {
+    0.5 * (widths[a] + widths[b]) + NODE_SPACING
+}
+
+/// Moves the bubbles to a layout's `positions`, centred where the bubbles were so it stays in view and by the pinned ones,
+/// which stay put. Returns how far the layout was moved.
+pub fn move_into_place(bubbles: &mut [Bubble], positions: &[Vector2]) -> Vector2 {
+    let n = bubbles.len() as f32;
+    let center = bubbles
+        .iter()
+        .fold(Vector2::new(), |sum, b| sum.add(&b.position))
+        .mul_s(1.0 / n);
+    let layout_center = positions
+        .iter()
+        .fold(Vector2::new(), |sum, p| sum.add(p))
+        .mul_s(1.0 / n);
+    let offset = center.sub(&layout_center);
+    for (bubble, position) in bubbles.iter_mut().zip(positions) {
+        bubble.v = Vector2::new();
+        if !bubble.pinned {
+            bubble.position = position.add(&offset);
+        }
+    }
+    offset
+}
+
+// what Walker's algorithm keeps for every node while it runs
+struct Walker<'a> {
+    tree: &'a SpanningTree,
+    widths: &'a [f32],
+    parents: Vec<Option<usize>>,
+    // where each node is among its siblings
+    numbers: Vec<usize>,
+    prelim: Vec<f32>,
+    modifiers: Vec<f32>,
+    shifts: Vec<f32>,
+    changes: Vec<f32>,
+    threads: Vec<Option<usize>>,
+    ancestors: Vec<usize>,
+}
+
+// the x of every node of the tree, each one in the middle of its children and the subtrees packed as close as they go
+fn walker(tree: &SpanningTree, widths: &[f32]) -> Vec<f32> {
+    let node_count = tree.children.len();
+    let mut parents = vec![None; node_count];
+    let mut numbers = vec![0; node_count];
+    for (v, children) in tree.children.iter().enumerate() {
+        for (i, &w) in children.iter().enumerate() {
+            parents[w] = Some(v);
+            numbers[w] = i;
+        }
+    }
+    let mut walker = Walker {
+        tree,
+        widths,
+        parents,
+        numbers,
+        prelim: vec![0.0; node_count],
+        modifiers: vec![0.0; node_count],
+        shifts: vec![0.0; node_count],
+        changes: vec![0.0; node_count],
+        threads: vec![None; node_count],
+        ancestors: (0..node_count).collect(),
+    };
+
+    // the first walk goes up from the leaves, every subtree is placed next to its left siblings as soon as it's done,
+    // the tree can be as deep as the graph is long so it's walked with a stack rather than recursively
+    let mut default_ancestors: Vec<usize> = (0..node_count)
+        .map(|v| tree.children[v].first().copied().unwrap_or(v))
+        .collect();
+    for v in tree.postorder() {
+        walker.first_walk(v);
+        if let Some(parent) = walker.parents[v] {
+            default_ancestors[parent] = walker.apportion(v, default_ancestors[parent]);
+        }
+    }
+
+    // the second walk adds up the modifiers on the way down
+    let mut x = vec![0.0; node_count];
+    let mut stack = vec![(tree.root, 0.0)];
+    while let Some((v, modifier)) = stack.pop() {
+        x[v] = walker.prelim[v] + modifier;
+        for &w in tree.children[v].iter() {
+            stack.push((w, modifier + walker.modifiers[v]));
+        }
+    }
+    x
+}
+
+impl Walker<'_> {
+    fn separation(&self, a: usize, b: usize) -> f32 {
+        separation(self.widths, a, b)
+    }
+
+    fn left_sibling(&self, v: usize) -> Option<usize> {
+        let parent = self.parents[v]?;
+        let i = self.numbers[v];
+        (i > 0).then(|| self.tree.children[parent][i - 1])
+    }
+
+    fn leftmost_sibling(&self, v: usize) -> usize {
+        self.parents[v].map_or(v, |parent| self.tree.children[parent][0])
+    }
+
+    fn next_left(&self, v: usize) -> Option<usize> {
+        self.tree.children[v].first().copied().or(self.threads[v])
+    }
+
+    fn next_right(&self, v: usize) -> Option<usize> {
+        self.tree.children[v].last().copied().or(self.threads[v])
+    }
+
+    // the placement of a node once its children are placed, with the shifts of its subtrees carried out
+    fn first_walk(&mut self, v: usize) {
+        let children = &self.tree.children[v];
+        let left_sibling = self.left_sibling(v);
+        if children.is_empty() {
+            self.prelim[v] = left_sibling.map_or(0.0, |w| self.prelim[w] + self.separation(w, v));
+            return;
+        }
+        let (mut shift, mut change) = (0.0, 0.0);
+        for &w in children.iter().rev() {
+            self.prelim[w] += shift;
+            self.modifiers[w] += shift;
+            change += self.changes[w];
+            shift += self.shifts[w] + change;
+        }
+        let midpoint = 0.5 * (self.prelim[children[0]] + self.prelim[children[children.len() - 1]]);
+        match left_sibling {
+            Some(w) => {
+                self.prelim[v] = self.prelim[w] + self.separation(w, v);
+                self.modifiers[v] = self.prelim[v] - midpoint;
+            }
+            None => self.prelim[v] = midpoint,
+        }
+    }
+
+    // pushes the subtree of v right until it clears the subtrees left of it, contour by contour,
+    // and spreads the push over the subtrees in between
+    fn apportion(&mut self, v: usize, default_ancestor: usize) -> usize {
+        let w = match self.left_sibling(v) {
+            Some(w) => w,
+            None => return default_ancestor,
+        };
+        let mut default_ancestor = default_ancestor;
+        // i for inner, o for outer, p for the right (plus) side and m for the left (minus) side
+        let (mut vip, mut vop, mut vim, mut vom) = (v, v, w, self.leftmost_sibling(v));
+        let (mut sip, mut sop, mut sim, mut som) =
+            (self.modifiers[vip], self.modifiers[vop], self.modifiers[vim], self.modifiers[vom]);
+        while let (Some(next_vim), Some(next_vip)) = (self.next_right(vim), self.next_left(vip)) {
+            vim = next_vim;
+            vip = next_vip;
+            vom = self.next_left(vom).unwrap();
+            vop = self.next_right(vop).unwrap();
+            self.ancestors[vop] = v;
+            let shift = (self.prelim[vim] + sim) - (self.prelim[vip] + sip) + self.separation(vim, vip);
+            if shift > 0.0 {
+                let ancestor = self.ancestor(vim, v, default_ancestor);
+                self.move_subtree(ancestor, v, shift);
+                sip += shift;
+                sop += shift;
+            }
+            sim += self.modifiers[vim];
+            sip += self.modifiers[vip];
+            som += self.modifiers[vom];
+            sop += self.modifiers[vop];
+        }
+        if let (Some(next), None) = (self.next_right(vim), self.next_right(vop)) {
+            self.threads[vop] = Some(next);
+            self.modifiers[vop] += sim - sop;
+        }
+        if let (Some(next), None) = (self.next_left(vip), self.next_left(vom)) {
+            self.threads[vom] = Some(next);
+            self.modifiers[vom] += sip - som;
+            default_ancestor = v;
+        }
+        default_ancestor
+    }
+
+    fn ancestor(&self, vim: usize, v: usize, default_ancestor: usize) -> usize {
+        let candidate = self.ancestors[vim];
+        if self.parents[candidate] == self.parents[v] {
+            candidate
+        } else {
+            default_ancestor
+        }
+    }
+
+    fn move_subtree(&mut self, wm: usize, wp: usize, shift: f32) {
+        let subtrees = (self.numbers[wp] - self.numbers[wm]) as f32;
+        self.changes[wp] -= shift / subtrees;
+        self.shifts[wp] += shift;
+        self.changes[wm] += shift / subtrees;
+        self.prelim[wp] += shift;
+        self.modifiers[wp] += shift;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout_quality::LayoutQuality;
+    use crate::test_graph::graph;
+
+    // a full binary tree four levels deep, every bubble above its two children
+    fn binary_tree() -> (Vec<Bubble>, Vec<Edge>) {
+        let pairs: Vec<(usize, usize)> = (0..7).flat_map(|i| vec![(i, 2 * i + 1), (i, 2 * i + 2)]).collect();
+        graph(15, &pairs)
+    }
+
+    #[test]
+    fn the_tidy_tree_has_no_overlaps_or_crossings() {
+        let (mut bubbles, edges) = binary_tree();
+        tidy_tree(&mut bubbles, &edges, Some(0));
+        let quality = LayoutQuality::evaluate(&bubbles, &edges);
+        assert_eq!(quality.overlaps, 0);
+        assert_eq!(quality.edge_crossings, Some(0));
+        for edge in edges.iter() {
+            assert!(bubbles[edge.from].position.y < bubbles[edge.to].position.y);
+        }
+        // the root is centred over its children
+        let children_x = 0.5 * (bubbles[1].position.x + bubbles[2].position.x);
+        assert!((bubbles[0].position.x - children_x).abs() < 1e-3);
+    }
+
+    #[test]
+    fn the_radial_tree_has_no_overlaps() {
+        let (mut bubbles, edges) = binary_tree();
+        radial_tree(&mut bubbles, &edges, Some(0));
+        assert_eq!(LayoutQuality::evaluate(&bubbles, &edges).overlaps, 0);
+        // every level is a ring round the root
+        let radius = |i: usize| bubbles[i].position.sub(&bubbles[0].position).len();
+        assert!((radius(1) - radius(2)).abs() < 1e-2);
+        assert!(radius(7) > radius(3));
+    }
+}